@@ -216,6 +216,8 @@ Bitboard Board::pieceBitboard(Player player, PieceType piece) const {
 
 int Board::fullTurnNumber() const { return board_get_full_turn_number(&board); }
 
+uint64_t Board::hash() const { return board_hash(&board); }
+
 void connectToServer(std::string url, const std::string& port, const std::string& apikey, const std::string& name, const std::function<std::pair<Move, std::unordered_map<std::string, std::string>>(Board &)> &function) {
   move_gen_pregenerate();
 
@@ -15,7 +15,9 @@ typedef uint8_t __chess_util_board_pos;
 struct __chess_util_board {
   __chess_util_bitboard players[2];
   __chess_util_bitboard pieces[6];
-  uint16_t flags;
+  uint32_t flags;
+  uint8_t castle_files[4];
+  uint64_t hash;
 };
 
 typedef uint64_t __chess_util_move;
@@ -343,7 +345,7 @@ public:
    /**
     * Return true if the move is castling, false otherwise.
     *
-    * Castling source and destinations are the source and destination of the king. For example, in white kingside castling, the source is e1 and the destination g1.
+    * Castling source and destinations are the source of the king and the square of the rook it castles with. For example, in white kingside castling, the source is e1 and the destination h1.
     */
    bool isCastle() const;
 
@@ -525,6 +527,10 @@ public:
     * Check if the player has castling rights on side. side should be either PieceType::King or PieceType::Queen. Having castling rights does not mean the player can castle -- rather, it means only they have not moved their king and rook */
    bool hasCastlingRights(Player player, PieceType side) const;
 
+   /**
+    * Get the zobrist hash of the board. The hash uses the same keys as polyglot opening books, and is updated incrementally when moves are made and unmade. */
+   uint64_t hash() const;
+
    struct __chess_util_board * getInternalValue();
 
   const struct __chess_util_board * getInternalValue() const;
@@ -211,7 +211,7 @@ func (move Move) CaptureSquare() BoardPos {
 }
 
 // IsCastle returns true if the move is a castling, false otherwise.
-// If the move is castling, then the source and destination squares are the source and destination of the king (eg white kingside castling is e1 -> g1).
+// If the move is castling, then the source square is the source of the king and the destination square is the square of the rook it castles with (eg white kingside castling is e1 -> h1).
 func (move Move) IsCastle() bool {
 	return C.move_is_castle(C.move(move)) != 0
 }
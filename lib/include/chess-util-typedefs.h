@@ -9,7 +9,11 @@ typedef uint8_t __chess_util_board_pos;
 struct __chess_util_board {
   __chess_util_bitboard players[2];
   __chess_util_bitboard pieces[6];
-  uint16_t flags;
+  uint32_t flags;
+  // file of the rook each player castles with on each side
+  uint8_t castle_files[4];
+  // polyglot compatible zobrist hash, updated incrementally by moves
+  uint64_t hash;
 };
 
 typedef uint64_t __chess_util_move;
@@ -13,6 +13,10 @@ struct __chess_util_board {
   __chess_util_bitboard players[2];
   __chess_util_bitboard pieces[6];
   uint32_t flags;
+  // file of the rook each player castles with on each side
+  uint8_t castle_files[4];
+  // polyglot compatible zobrist hash, updated incrementally by moves
+  uint64_t hash;
 };
 
 typedef uint64_t __chess_util_move;
@@ -159,7 +163,9 @@ board_pos board_pos_from_str(const char *str);
  * Implementation:
  * A bitboard for each player indicating what squares they have pieces on
  * A bitboard for pawns/rooks/knights/bishops/queens/kings
- * A value with flags for next player to move and en passant target square */
+ * A value with flags for next player to move and en passant target square
+ * The files of the rooks used for castling (to support chess960)
+ * A zobrist hash of the position */
 #define WHITE 0
 #define BLACK 1
 
@@ -178,6 +184,8 @@ board_pos board_pos_from_str(const char *str);
 #define BOARD_FLAGS_W_CASTLE_QUEEN 512
 #define BOARD_FLAGS_B_CASTLE_KING 1024
 #define BOARD_FLAGS_B_CASTLE_QUEEN 2048
+// set if the board uses chess960 castling notation
+#define BOARD_FLAGS_CHESS960 4096
 
 #define BOARD_FLAGS_TURN_NUM 0xffff0000
 #define BOARD_FLAGS_TURN_NUM_SHIFT 16
@@ -191,9 +199,26 @@ typedef struct __chess_util_board board;
 void board_invariants(const board *board);
 
 /**
- * initialize a board from a board in FEN notation */
+ * return 1 if board is in a consistent state (as checked by board_invariants),
+ * 0 otherwise */
+int board_is_valid(const board *board);
+
+/**
+ * initialize a board from a board in FEN notation
+ * castling rights may be given in standard notation (KQkq), Shredder-FEN
+ * (rook files, eg HAha), or X-FEN (KQkq referring to the outermost rooks).
+ * Shredder-FEN, or X-FEN with the king or rooks off their standard squares,
+ * marks the board as a chess960 board */
 void board_from_fen_str(board *board, const char *fen_string);
 
+/**
+ * check if a FEN string is wellformed and describes a valid position
+ * (consistent according to board_invariants, no pawns on the first or last
+ * rank, and the player not to move isn't in check)
+ * EPD strings (FEN without the halfmove clock and turn counter, optionally
+ * followed by operations) are also accepted */
+int board_fen_str_is_valid(const char *fen_string);
+
 /**
  * express a board in FEN notation (minus halfmove + turn counters)
  * res_str must have 90 bytes allocated */
@@ -237,6 +262,26 @@ board_pos board_get_en_passant_target(const board *board);
  * check if player can castle (side should be either KING or QUEEN) */
 int board_can_castle(const board *board, int player, int side);
 
+/**
+ * get the file of the rook player castles with on side (side should be either
+ * KING or QUEEN) */
+int board_castle_rook_file(const board *board, int player, int side);
+
+/**
+ * check if the board uses chess960 castling notation */
+int board_is_chess960(const board *board);
+
+/**
+ * get the zobrist hash of the board
+ * the hash uses the same keys as polyglot opening books, so it can be used to
+ * look up positions in them. The hash is updated incrementally when moves are
+ * made and unmade. */
+uint64_t board_hash(const board *board);
+
+/**
+ * compute the zobrist hash of the board from scratch */
+uint64_t board_compute_hash(const board *board);
+
 /**
  * print a board on stdout using ony ascii characters */
 void board_print(const board *board);
@@ -284,6 +329,10 @@ void move_gen_pregenerate();
  * bits 33-35 : type of captured piece
  * bits 36-41 : square capture piece was on (may be different from move
  * destination due to en passant)
+ * bit  42    : set if the move is a castle
+ *
+ * castles are stored as the king moving onto the square of the rook it castles
+ * with
  */
 typedef __chess_util_move move;
 
@@ -336,6 +385,8 @@ board_pos move_capture_square(move move);
  * a7a8n (promoting to knight)
  * a7a8b (promoting to bishop)
  * a7a8r (promoting to rook)
+ * castles are written as the king's movement (e1g1) on standard boards, and as
+ * the king moving onto its rook (e1h1) on chess960 boards
  *
  * res_str must have 6 bytes allocated */
 void move_to_str(move move, char *res_str);
@@ -390,6 +441,15 @@ move move_gen_next_move(move_gen *generator);
  * move_gen_next_move again */
 move move_gen_make_next_move(move_gen *generator);
 
+/**
+ * get the next pseudo-legal move from the move generator. pseudo-legal moves
+ * follow how pieces move, but may leave the player to move in check (castles
+ * are only generated when legal)
+ * if no more moves are available, the method returns MOVE_END
+ * move_gen_is_checkmate and move_gen_is_stalemate don't detect checkmate or
+ * stalemate after pseudo-legal move generation */
+move move_gen_next_pseudo_legal_move(move_gen *generator);
+
 /**
  * make the given move on the given board
  * mutates board */
@@ -15,6 +15,7 @@ static inline int __builtin_ctzll(unsigned long long x) {
 #include <ctype.h>
 #include <stdio.h>
 #include <stdlib.h>
+#include <string.h>
 
 int bitboard_popcount(bitboard board) { return __builtin_popcountll(board); }
 
@@ -166,32 +167,47 @@ board_pos board_pos_from_str(const char *str) {
   return board_pos_from_xy(x, y);
 }
 
-void board_invariants(const board *board) {
-#ifndef NDEBUG
+int board_is_valid(const board *board) {
   // make sure player occupancy don't overlap
-  assert((board->players[0] & board->players[1]) == 0);
+  if ((board->players[0] & board->players[1]) != 0) {
+    return 0;
+  }
+  // make sure pieces don't overlap
   for (int i = 0; i < 6; i++) {
     for (int j = 0; j < 6; j++) {
       if (i == j)
         continue;
-      assert((board->pieces[i] & board->pieces[j]) == 0);
+      if ((board->pieces[i] & board->pieces[j]) != 0) {
+        return 0;
+      }
     }
   }
-  // make sure pieces don't overlap
-  assert(bitboard_popcount(board->players[0] & board->pieces[KING]) == 1);
-  assert(bitboard_popcount(board->players[1] & board->pieces[KING]) == 1);
+  // make sure each player has exactly one king
+  if (bitboard_popcount(board->players[0] & board->pieces[KING]) != 1 ||
+      bitboard_popcount(board->players[1] & board->pieces[KING]) != 1) {
+    return 0;
+  }
   // make sure ep target square is empty if present and on rank 3 or 6
   if (board->flags & BOARD_FLAGS_EP_PRESENT) {
     int player_to_move = board_player_to_move(board);
     board_pos ep_target = board->flags & BOARD_FLAGS_EP_SQUARE;
     int x, y;
     board_pos_to_xy(ep_target, &x, &y);
-    assert((player_to_move == WHITE && y == 5) ||
-           (player_to_move == BLACK && y == 2));
-    assert(!bitboard_check_square(board->players[0] & board->players[1],
-                                  ep_target));
+    if (!((player_to_move == WHITE && y == 5) ||
+          (player_to_move == BLACK && y == 2))) {
+      return 0;
+    }
+    if (bitboard_check_square(board->players[0] | board->players[1],
+                              ep_target)) {
+      return 0;
+    }
   }
-#endif
+  return 1;
+}
+
+void board_invariants(const board *board) {
+  assert(board_is_valid(board));
+  assert(board->hash == board_compute_hash(board));
 }
 
 int board_piece_char_to_piece(char c) {
@@ -251,7 +267,78 @@ char board_piece_char_from_piece_player(int piece, int player) {
   return piece_str[player][piece];
 }
 
-void board_from_fen_str(board *board, const char *fen_string) {
+/**
+ * check if player has a rook on square */
+static int board_player_rook_on_square(const board *board, int player,
+                                       board_pos square) {
+  return bitboard_check_square(board->players[player] & board->pieces[ROOK],
+                               square);
+}
+
+/**
+ * set the castling rights for player on side, with the castling rook on
+ * rook_x */
+static void board_set_castling(board *board, int player, int side,
+                               int rook_x) {
+  int flag = player == WHITE ? (side == QUEEN ? BOARD_FLAGS_W_CASTLE_QUEEN
+                                              : BOARD_FLAGS_W_CASTLE_KING)
+                             : (side == QUEEN ? BOARD_FLAGS_B_CASTLE_QUEEN
+                                              : BOARD_FLAGS_B_CASTLE_KING);
+  board->flags |= flag;
+  board->castle_files[player * 2 + (side == QUEEN)] = rook_x;
+}
+
+/**
+ * read a single character of castling availability from a FEN string
+ * rights that don't have a king and rook on the back rank are ignored */
+static void board_read_castling_char(board *board, char c) {
+  if (c == '-') {
+    return;
+  }
+  int player = isupper(c) ? WHITE : BLACK;
+  int y = player == WHITE ? 0 : 7;
+  char lower = tolower(c);
+  bitboard king_mask = board->players[player] & board->pieces[KING];
+  if (bitboard_popcount(king_mask) != 1) {
+    return;
+  }
+  board_pos king = bitboard_scan_lsb(king_mask);
+  if (board_pos_to_y(king) != y) {
+    return;
+  }
+  int king_x = board_pos_to_x(king);
+
+  int rook_x = -1;
+  if (lower == 'k' || lower == 'q') {
+    // X-FEN: castle with the outermost rook on the side
+    int dir = lower == 'k' ? 1 : -1;
+    for (int x = king_x + dir; x >= 0 && x < 8; x += dir) {
+      if (board_player_rook_on_square(board, player, board_pos_from_xy(x, y))) {
+        rook_x = x;
+      }
+    }
+    int standard_x = lower == 'k' ? 7 : 0;
+    if (rook_x != -1 && (king_x != 4 || rook_x != standard_x)) {
+      board->flags |= BOARD_FLAGS_CHESS960;
+    }
+  } else if (lower >= 'a' && lower <= 'h') {
+    // Shredder-FEN: castle with the rook on the given file
+    rook_x = lower - 'a';
+    if (!board_player_rook_on_square(board, player,
+                                     board_pos_from_xy(rook_x, y))) {
+      return;
+    }
+    board->flags |= BOARD_FLAGS_CHESS960;
+  }
+
+  if (rook_x != -1) {
+    board_set_castling(board, player, rook_x > king_x ? KING : QUEEN, rook_x);
+  }
+}
+
+/**
+ * read a board from a FEN or EPD string without checking that it is valid */
+static void board_read_fen_str(board *board, const char *fen_string) {
   // clear board
   for (int i = 0; i < 2; i++) {
     board->players[i] = 0L;
@@ -260,6 +347,10 @@ void board_from_fen_str(board *board, const char *fen_string) {
     board->pieces[i] = 0L;
   }
   board->flags = 0;
+  for (int player = WHITE; player <= BLACK; player++) {
+    board->castle_files[player * 2] = 7;
+    board->castle_files[player * 2 + 1] = 0;
+  }
 
   // read piece locations
   for (int rank = 7; rank >= 0; rank--) {
@@ -300,25 +391,8 @@ void board_from_fen_str(board *board, const char *fen_string) {
   }
 
   // read castling availability
-  while (*fen_string == 'K' || *fen_string == 'Q' || *fen_string == 'k' ||
-         *fen_string == 'q' || *fen_string == '-') {
-    switch (*fen_string) {
-    case 'K':
-      board->flags |= BOARD_FLAGS_W_CASTLE_KING;
-      break;
-    case 'Q':
-      board->flags |= BOARD_FLAGS_W_CASTLE_QUEEN;
-      break;
-    case 'k':
-      board->flags |= BOARD_FLAGS_B_CASTLE_KING;
-      break;
-    case 'q':
-      board->flags |= BOARD_FLAGS_B_CASTLE_QUEEN;
-      break;
-    case '-':
-    default:
-      break;
-    }
+  while (*fen_string != '\0' && !isspace(*fen_string)) {
+    board_read_castling_char(board, *fen_string);
     fen_string++;
   }
 
@@ -339,11 +413,11 @@ void board_from_fen_str(board *board, const char *fen_string) {
     board->flags |= board_pos_from_str(square_str) & BOARD_FLAGS_EP_SQUARE;
   }
 
-  // ignore halfmove
+  // ignore halfmove (or EPD operations)
   while(isspace(*fen_string)) {
     fen_string++;
   }
-  while(!isspace(*fen_string)) {
+  while(*fen_string != '\0' && !isspace(*fen_string)) {
     fen_string++;
   }
   while(isspace(*fen_string)) {
@@ -359,17 +433,162 @@ void board_from_fen_str(board *board, const char *fen_string) {
   }
   turn_count_str[turn_count_index] = '\0';
 
+  // EPD strings don't have a turn counter
   int turn_count = atoi(turn_count_str);
+  if (turn_count <= 0) {
+    turn_count = 1;
+  }
   board->flags |= (turn_count << BOARD_FLAGS_TURN_NUM_SHIFT) & BOARD_FLAGS_TURN_NUM;
 
+  board->hash = board_compute_hash(board);
+}
+
+void board_from_fen_str(board *board, const char *fen_string) {
+  board_read_fen_str(board, fen_string);
   board_invariants(board);
 }
 
+/**
+ * skip whitespace in a string, and return 0 if there wasn't any */
+static int skip_fen_space(const char **fen_string) {
+  if (!isspace(**fen_string)) {
+    return 0;
+  }
+  while (isspace(**fen_string)) {
+    (*fen_string)++;
+  }
+  return 1;
+}
+
+/**
+ * check that a FEN or EPD string has the expected fields */
+static int board_fen_str_is_wellformed(const char *fen_string) {
+  // piece placement
+  for (int rank = 7; rank >= 0; rank--) {
+    int file = 0;
+    while (*fen_string != '/' && *fen_string != '\0' && !isspace(*fen_string)) {
+      if (*fen_string >= '1' && *fen_string <= '8') {
+        file += *fen_string - '0';
+      } else if (strchr("PNBRQKpnbrqk", *fen_string) != NULL) {
+        file++;
+      } else {
+        return 0;
+      }
+      if (file > 8) {
+        return 0;
+      }
+      fen_string++;
+    }
+    if (file != 8) {
+      return 0;
+    }
+    if (rank > 0 && *fen_string++ != '/') {
+      return 0;
+    }
+  }
+  // player to move
+  if (!skip_fen_space(&fen_string)) {
+    return 0;
+  }
+  if (*fen_string != 'w' && *fen_string != 'b') {
+    return 0;
+  }
+  fen_string++;
+  // castling availability
+  if (!skip_fen_space(&fen_string)) {
+    return 0;
+  }
+  if (*fen_string == '-') {
+    fen_string++;
+  } else {
+    int num_rights = 0;
+    while (*fen_string != '\0' && !isspace(*fen_string)) {
+      if (strchr("KQkqABCDEFGHabcdefgh", *fen_string) == NULL) {
+        return 0;
+      }
+      num_rights++;
+      fen_string++;
+    }
+    if (num_rights == 0 || num_rights > 4) {
+      return 0;
+    }
+  }
+  // en passant target
+  if (!skip_fen_space(&fen_string)) {
+    return 0;
+  }
+  if (*fen_string == '-') {
+    fen_string++;
+  } else {
+    if (fen_string[0] < 'a' || fen_string[0] > 'h' ||
+        (fen_string[1] != '3' && fen_string[1] != '6')) {
+      return 0;
+    }
+    fen_string += 2;
+  }
+  if (*fen_string == '\0') {
+    return 1;
+  }
+  if (!skip_fen_space(&fen_string)) {
+    return 0;
+  }
+  // halfmove clock and turn counter (FEN), or operations (EPD)
+  if (!isdigit(*fen_string)) {
+    return 1;
+  }
+  for (int i = 0; i < 2 && *fen_string != '\0'; i++) {
+    while (isdigit(*fen_string)) {
+      fen_string++;
+    }
+    if (*fen_string != '\0' && !skip_fen_space(&fen_string)) {
+      return 0;
+    }
+    if (i == 0 && !isdigit(*fen_string)) {
+      return 0;
+    }
+  }
+  return *fen_string == '\0';
+}
+
+int board_fen_str_is_valid(const char *fen_string) {
+  if (!board_fen_str_is_wellformed(fen_string)) {
+    return 0;
+  }
+  board res;
+  board_read_fen_str(&res, fen_string);
+  if (!board_is_valid(&res)) {
+    return 0;
+  }
+  // pawns can't be on the first or last rank
+  if (res.pieces[PAWN] & 0xff000000000000ffULL) {
+    return 0;
+  }
+  // the player who just moved can't be left in check
+  return !board_player_in_check(&res, !board_player_to_move(&res));
+}
+
 /**
  * convert castling rights to a string
  * castling_str must have 5 bytes allocated */
 static void board_castling_to_str(const board *board, char *castling_str) {
   char *castling = castling_str;
+  // Shredder-FEN for chess960 boards
+  if (board_is_chess960(board)) {
+    for (int player = WHITE; player <= BLACK; player++) {
+      char file_a = player == WHITE ? 'A' : 'a';
+      if (board_can_castle(board, player, KING)) {
+        *castling++ = file_a + board_castle_rook_file(board, player, KING);
+      }
+      if (board_can_castle(board, player, QUEEN)) {
+        *castling++ = file_a + board_castle_rook_file(board, player, QUEEN);
+      }
+    }
+    if (castling == castling_str) {
+      *castling++ = '-';
+    }
+    *castling = '\0';
+    return;
+  }
   if (board->flags & BOARD_FLAGS_W_CASTLE_KING) {
     *castling++ = 'K';
   }
@@ -486,6 +705,17 @@ int board_can_castle(const board *board, int player, int side) {
   assert(0);
 }
 
+int board_castle_rook_file(const board *board, int player, int side) {
+  assert(player == WHITE || player == BLACK);
+  assert(side == KING || side == QUEEN);
+
+  return board->castle_files[player * 2 + (side == QUEEN)];
+}
+
+int board_is_chess960(const board *board) {
+  return board->flags & BOARD_FLAGS_CHESS960 ? 1 : 0;
+}
+
 /**
  * print player to move, castling rights, and en passant information */
 static void board_print_flags(const board *board) {
@@ -1,5 +1,6 @@
 #include "chess-util.h"
 #include "found_magics.h"
+#include "zobrist.h"
 #include <assert.h>
 #include <stdio.h>
 #include <string.h>
@@ -432,8 +433,15 @@ static board_pos en_passant_target_to_pawn_pos(board_pos ep_target) {
 static char promote_codes[6] = {'k', 'p', 'n', 'r', 'b', 'q'};
 
 void move_to_str(move move, char *res_str) {
-  board_pos_to_str(move_source_square(move), res_str);
-  board_pos_to_str(move_destination_square(move), res_str + 2);
+  board_pos src = move_source_square(move);
+  board_pos dst = move_destination_square(move);
+  // on standard boards, write castles as the king's movement
+  if (move_is_castle(move) && !(move & BOARD_FLAGS_CHESS960)) {
+    int side = board_pos_to_x(dst) > board_pos_to_x(src) ? KING : QUEEN;
+    dst = board_pos_from_xy(side == KING ? 6 : 2, board_pos_to_y(src));
+  }
+  board_pos_to_str(src, res_str);
+  board_pos_to_str(dst, res_str + 2);
   if (move_is_promotion(move)) {
     res_str[4] = promote_codes[move_promotion_piece(move)];
     res_str[5] = '\0';
@@ -443,6 +451,27 @@ void move_to_str(move move, char *res_str) {
 }
 
 move move_new(board_pos src, board_pos dst, int is_promote, int promote_piece, const board *board) {
+  // check for castling
+  int player = board_player_to_move(board);
+  int y = player == WHITE ? 0 : 7;
+  if (board_piece_on_square(board, src) == KING &&
+      board_player_on_square(board, src) == player &&
+      board_pos_to_y(src) == y && board_pos_to_y(dst) == y && !is_promote) {
+    // king moving onto its own rook (chess960 notation)
+    if (board_piece_on_square(board, dst) == ROOK &&
+        board_player_on_square(board, dst) == player) {
+      return construct_move(board->flags, src, dst, 0, 0, 0, 0, 0, 1);
+    }
+    // king moving two squares from the e file (standard notation)
+    if (!board_is_chess960(board) && board_pos_to_x(src) == 4 &&
+        (board_pos_to_x(dst) == 2 || board_pos_to_x(dst) == 6)) {
+      int side = board_pos_to_x(dst) == 6 ? KING : QUEEN;
+      board_pos rook =
+          board_pos_from_xy(board_castle_rook_file(board, player, side), y);
+      return construct_move(board->flags, src, rook, 0, 0, 0, 0, 0, 1);
+    }
+  }
+
   // check for capturing
   int is_capture = 0;
   board_pos capture_pos = BOARD_POS_INVALID;
@@ -465,18 +494,8 @@ move move_new(board_pos src, board_pos dst, int is_promote, int promote_piece, c
       return MOVE_END;
     }
   }
-  int is_castle = 0;
-  // check for castling
-  if (((src == board_pos_from_xy(4, 0) &&
-        (dst == board_pos_from_xy(2, 0) || dst == board_pos_from_xy(6, 0))) ||
-       (src == board_pos_from_xy(4, 7) &&
-        (dst == board_pos_from_xy(2, 7) || dst == board_pos_from_xy(6, 7)))) &&
-      board_piece_on_square(board, src) == KING) {
-    is_castle = 1;
-  }
-
   return construct_move(board->flags, src, dst, is_promote, promote_piece,
-                        is_capture, capture_piece, capture_pos, is_castle);
+                        is_capture, capture_piece, capture_pos, 0);
 }
 
 static int file_wellformed(char file) {
@@ -575,34 +594,78 @@ static void board_clear_castling(board *board, int player, int side) {
   board->flags &= ~(flag);
 }
 
-static void move_gen_make_castle(board *board, move move) {
-  board_pos dst = move_destination_square(move);
-  board_pos src = move_source_square(move);
-  int player = board_player_to_move(board);
-  int side = board_pos_to_x(dst) == 2 ? QUEEN : KING;
-  assert(board_pos_to_x(dst) == 2 || board_pos_to_x(dst) == 6);
-  assert(board_piece_on_square(board, src) == KING);
-  assert(!move_is_capture(move));
-  assert(!move_is_promotion(move));
-
+/**
+ * revoke the castling rights for player that use the rook on square */
+static void board_clear_castling_rook(board *board, int player,
+                                      board_pos square) {
   int y = player == WHITE ? 0 : 7;
-  assert(board_pos_to_y(dst) == y);
-  // move king
-  board->players[player] = bitboard_clear_square(board->players[player], src);
-  board->pieces[KING] = bitboard_clear_square(board->pieces[KING], src);
-  board->players[player] = bitboard_set_square(board->players[player], dst);
-  board->pieces[KING] = bitboard_set_square(board->pieces[KING], dst);
-  // move rook
-  board_pos rook_src =
-      side == QUEEN ? board_pos_from_xy(0, y) : board_pos_from_xy(7, y);
-  board_pos rook_dst =
-      side == QUEEN ? board_pos_from_xy(3, y) : board_pos_from_xy(5, y);
+  if (board_pos_to_y(square) != y) {
+    return;
+  }
+  if (board_castle_rook_file(board, player, KING) == board_pos_to_x(square)) {
+    board_clear_castling(board, player, KING);
+  }
+  if (board_castle_rook_file(board, player, QUEEN) == board_pos_to_x(square)) {
+    board_clear_castling(board, player, QUEEN);
+  }
+}
+
+/**
+ * get the squares the king and rook end up on after a castle */
+static void castle_destinations(move move, board_pos *king_dst,
+                                board_pos *rook_dst) {
+  board_pos king = move_source_square(move);
+  board_pos rook = move_destination_square(move);
+  int y = board_pos_to_y(king);
+  if (board_pos_to_x(rook) > board_pos_to_x(king)) {
+    *king_dst = board_pos_from_xy(6, y);
+    *rook_dst = board_pos_from_xy(5, y);
+  } else {
+    *king_dst = board_pos_from_xy(2, y);
+    *rook_dst = board_pos_from_xy(3, y);
+  }
+}
+
+/**
+ * move the king and rook between their castled and uncastled squares */
+static void board_move_castling_pieces(board *board, int player,
+                                       board_pos king_src, board_pos rook_src,
+                                       board_pos king_dst, board_pos rook_dst) {
+  // clear both pieces before placing them, since in chess960 the king and
+  // rook may land on each other's squares
+  board->players[player] =
+      bitboard_clear_square(board->players[player], king_src);
+  board->pieces[KING] = bitboard_clear_square(board->pieces[KING], king_src);
   board->players[player] =
       bitboard_clear_square(board->players[player], rook_src);
   board->pieces[ROOK] = bitboard_clear_square(board->pieces[ROOK], rook_src);
+  board->players[player] =
+      bitboard_set_square(board->players[player], king_dst);
+  board->pieces[KING] = bitboard_set_square(board->pieces[KING], king_dst);
   board->players[player] =
       bitboard_set_square(board->players[player], rook_dst);
   board->pieces[ROOK] = bitboard_set_square(board->pieces[ROOK], rook_dst);
+  board->hash ^= zobrist_piece_key(player, KING, king_src) ^
+                 zobrist_piece_key(player, KING, king_dst) ^
+                 zobrist_piece_key(player, ROOK, rook_src) ^
+                 zobrist_piece_key(player, ROOK, rook_dst);
+}
+
+static void move_gen_make_castle(board *board, move move) {
+  board_pos king = move_source_square(move);
+  board_pos rook = move_destination_square(move);
+  int player = board_player_to_move(board);
+  assert(board_piece_on_square(board, king) == KING);
+  assert(board_piece_on_square(board, rook) == ROOK);
+  assert(board_player_on_square(board, rook) == player);
+  assert(!move_is_capture(move));
+  assert(!move_is_promotion(move));
+
+  int y = player == WHITE ? 0 : 7;
+  assert(board_pos_to_y(king) == y && board_pos_to_y(rook) == y);
+  board_pos king_dst, rook_dst;
+  castle_destinations(move, &king_dst, &rook_dst);
+  board_move_castling_pieces(board, player, king, rook, king_dst, rook_dst);
 
   board_clear_castling(board, player, QUEEN);
   board_clear_castling(board, player, KING);
@@ -617,6 +680,9 @@ void board_make_move(board *board, move move) {
   int dst_piece = move_is_promotion(move) ? move_promotion_piece(move) : piece;
   int player = board_player_to_move(board);
   int opponent = !player;
+  // remove the castling, en passant, and turn keys from the hash, they are
+  // added back once the move has been made
+  board->hash ^= zobrist_state_key(board);
 
   if (move_is_castle(move)) {
     move_gen_make_castle(board, move);
@@ -631,15 +697,7 @@ void board_make_move(board *board, move move) {
     }
     // if rooks are moved, revoke castling rights
     if (piece == ROOK) {
-      if (player == WHITE && src == board_pos_from_xy(0, 0)) {
-        board_clear_castling(board, WHITE, QUEEN);
-      } else if (player == WHITE && src == board_pos_from_xy(7, 0)) {
-        board_clear_castling(board, WHITE, KING);
-      } else if (player == BLACK && src == board_pos_from_xy(0, 7)) {
-        board_clear_castling(board, BLACK, QUEEN);
-      } else if (player == BLACK && src == board_pos_from_xy(7, 7)) {
-        board_clear_castling(board, BLACK, KING);
-      }
+      board_clear_castling_rook(board, player, src);
     }
     // if move is capture, clear dst for opponent
     if (move_is_capture(move)) {
@@ -657,18 +715,11 @@ void board_make_move(board *board, move move) {
           bitboard_clear_square(board->players[opponent], cap_square);
       board->pieces[cap_piece] =
           bitboard_clear_square(board->pieces[cap_piece], cap_square);
+      board->hash ^= zobrist_piece_key(opponent, cap_piece, cap_square);
 
       // if rooks are captured on initial squares, revoke castling rights
       if (cap_piece == ROOK) {
-        if (opponent == WHITE && cap_square == board_pos_from_xy(0, 0)) {
-          board_clear_castling(board, WHITE, QUEEN);
-        } else if (opponent == WHITE && cap_square == board_pos_from_xy(7, 0)) {
-          board_clear_castling(board, WHITE, KING);
-        } else if (opponent == BLACK && cap_square == board_pos_from_xy(0, 7)) {
-          board_clear_castling(board, BLACK, QUEEN);
-        } else if (opponent == BLACK && cap_square == board_pos_from_xy(7, 7)) {
-          board_clear_castling(board, BLACK, KING);
-        }
+        board_clear_castling_rook(board, opponent, cap_square);
       }
     }
     // move piece from src to dst and clear src
@@ -677,6 +728,8 @@ void board_make_move(board *board, move move) {
     board->players[player] = bitboard_set_square(board->players[player], dst);
     board->pieces[piece] = bitboard_clear_square(board->pieces[piece], src);
     board->players[player] = bitboard_clear_square(board->players[player], src);
+    board->hash ^= zobrist_piece_key(player, dst_piece, dst) ^
+                   zobrist_piece_key(player, piece, src);
   }
   // clear en passant target
   board->flags &= ~(BOARD_FLAGS_EP_PRESENT);
@@ -710,11 +763,13 @@ void board_make_move(board *board, move move) {
   }
   // flip player to move
   board->flags ^= BOARD_FLAGS_TURN;
+  board->hash ^= zobrist_state_key(board);
   board_invariants(board);
 }
 
 void board_unmake_move(board *board, move move) {
   board_invariants(board);
+  board->hash ^= zobrist_state_key(board);
   // restore flags
   board->flags &= ~BOARD_FLAGS_LOW;
   board->flags |= move & MOVE_FLAGS_PREV_FLAGS;
@@ -723,7 +778,7 @@ void board_unmake_move(board *board, move move) {
   board_pos dst = move_destination_square(move);
   int piece_dst = board_piece_on_square(board, dst);
   int piece_src = move_is_promotion(move) ? PAWN : piece_dst;
-  assert(piece_dst != -1);
+  assert(piece_dst != -1 || move_is_castle(move));
   // player is the player that made the move
   int player = board_player_to_move(board);
   int opponent = !player;
@@ -737,12 +792,24 @@ void board_unmake_move(board *board, move move) {
     board -> flags |= ((prev_turn - 1) << BOARD_FLAGS_TURN_NUM_SHIFT) & BOARD_FLAGS_TURN_NUM;
   }
 
+  // if castling, move king and rook back
+  if (move_is_castle(move)) {
+    board_pos king_dst, rook_dst;
+    castle_destinations(move, &king_dst, &rook_dst);
+    board_move_castling_pieces(board, player, king_dst, rook_dst, src, dst);
+    board->hash ^= zobrist_state_key(board);
+    board_invariants(board);
+    return;
+  }
+
   // move dst to src
   board->pieces[piece_dst] =
       bitboard_clear_square(board->pieces[piece_dst], dst);
   board->players[player] = bitboard_clear_square(board->players[player], dst);
   board->pieces[piece_src] = bitboard_set_square(board->pieces[piece_src], src);
   board->players[player] = bitboard_set_square(board->players[player], src);
+  board->hash ^= zobrist_piece_key(player, piece_dst, dst) ^
+                 zobrist_piece_key(player, piece_src, src);
   // restore captures
   if (move_is_capture(move)) {
     int cap_piece = move_capture_piece(move);
@@ -751,23 +818,9 @@ void board_unmake_move(board *board, move move) {
         bitboard_set_square(board->pieces[cap_piece], cap_square);
     board->players[opponent] =
         bitboard_set_square(board->players[opponent], cap_square);
+    board->hash ^= zobrist_piece_key(opponent, cap_piece, cap_square);
   }
-  // if castling, move rook back
-  if (move_is_castle(move)) {
-    int side = board_pos_to_x(dst) == 2 ? QUEEN : KING;
-    int rook_y = player == WHITE ? 0 : 7;
-    int rook_src_x = side == QUEEN ? 0 : 7;
-    int rook_dst_x = side == QUEEN ? 3 : 5;
-    board_pos rook_src = board_pos_from_xy(rook_src_x, rook_y);
-    board_pos rook_dst = board_pos_from_xy(rook_dst_x, rook_y);
-    // set src and clear dst
-    board->players[player] =
-        bitboard_set_square(board->players[player], rook_src);
-    board->pieces[ROOK] = bitboard_set_square(board->pieces[ROOK], rook_src);
-    board->players[player] =
-        bitboard_clear_square(board->players[player], rook_dst);
-    board->pieces[ROOK] = bitboard_clear_square(board->pieces[ROOK], rook_dst);
-  }
+  board->hash ^= zobrist_state_key(board);
   board_invariants(board);
 }
 
@@ -844,40 +897,57 @@ static move move_gen_castle(move_gen *generator, int player, int side,
     return MOVE_END;
   }
   int y = player == WHITE ? 0 : 7;
-  // direction from king to rook
-  int dir = side == QUEEN ? -1 : 1;
   // make sure king and rook positions are in expected
-  board_pos king = board_pos_from_xy(4, y);
-  board_pos rook =
-      side == QUEEN ? board_pos_from_xy(0, y) : board_pos_from_xy(7, y);
-  assert(board_piece_on_square(generator->board, king) == KING);
+  board_pos king = bitboard_scan_lsb(generator->board->pieces[KING] &
+                                     generator->board->players[player]);
+  board_pos rook = board_pos_from_xy(
+      board_castle_rook_file(generator->board, player, side), y);
+  assert(board_pos_to_y(king) == y);
   assert(board_piece_on_square(generator->board, rook) == ROOK);
-  assert(board_player_on_square(generator->board, king) == player);
   assert(board_player_on_square(generator->board, rook) == player);
 
-  // check for pieces between king and rook
-  for (int x = board_pos_to_x(king) + dir; x != board_pos_to_x(rook);
-       x += dir) {
-    if (bitboard_check_square(generator->occupancy_for_sliders,
+  move move = construct_move(generator->board->flags, king, rook, 0, 0, 0, 0,
+                             0, 1);
+  board_pos king_dst, rook_dst;
+  castle_destinations(move, &king_dst, &rook_dst);
+
+  // check for pieces (other than the king and rook) between the king, the
+  // rook, and their destinations
+  int king_x = board_pos_to_x(king);
+  int rook_x = board_pos_to_x(rook);
+  int min_x = king_x, max_x = king_x;
+  int xs[3] = {rook_x, board_pos_to_x(king_dst), board_pos_to_x(rook_dst)};
+  for (int i = 0; i < 3; i++) {
+    min_x = xs[i] < min_x ? xs[i] : min_x;
+    max_x = xs[i] > max_x ? xs[i] : max_x;
+  }
+  for (int x = min_x; x <= max_x; x++) {
+    if (x != king_x && x != rook_x &&
+        bitboard_check_square(generator->occupancy_for_sliders,
                               board_pos_from_xy(x, y)))
       return MOVE_END;
   }
   // make sure square from king until dest aren't threatened
-  int x = board_pos_to_x(king);
-  for (int i = 0; i < 3; i++, x += dir) {
+  int dir = board_pos_to_x(king_dst) >= king_x ? 1 : -1;
+  for (int x = king_x;; x += dir) {
     if (board_is_square_attacked(generator->board, board_pos_from_xy(x, y),
                                  !player)) {
       return MOVE_END;
     }
+    if (x == board_pos_to_x(king_dst)) {
+      break;
+    }
   }
 
-  move move = construct_move(
-      generator->board->flags, king,
-      board_pos_from_xy(board_pos_to_x(king) + 2 * dir, y), 0, 0, 0, 0, 0, 1);
-  // make move if needed
-  if (!undo_move) {
-    board_make_move(generator->board, move);
-    assert(!board_player_in_check(generator->board, player));
+  // in chess960, the castling rook may have been blocking an attack on the
+  // king's destination
+  board_make_move(generator->board, move);
+  int in_check = board_player_in_check(generator->board, player) != 0;
+  if (in_check || undo_move) {
+    board_unmake_move(generator->board, move);
+  }
+  if (in_check) {
+    return MOVE_END;
   }
   return move;
 }
@@ -886,7 +956,8 @@ static move move_gen_castle(move_gen *generator, int player, int side,
 #define MOVE_DONE_CHECKMATE 2
 #define MOVE_DONE_STALEMATE 3
 
-static move move_gen_next(move_gen *generator, int undo_moves) {
+static move move_gen_next(move_gen *generator, int undo_moves,
+                          int legal_only) {
   board_invariants(generator->board);
   int player = board_player_to_move(generator->board);
   int opponent = !player;
@@ -911,7 +982,7 @@ static move move_gen_next(move_gen *generator, int undo_moves) {
       generator->hit_move = 1;
       return castle;
     } else {
-      return move_gen_next(generator, undo_moves);
+      return move_gen_next(generator, undo_moves, legal_only);
     }
   } else if (generator->cur_mode == MOVE_GEN_MODE_CASTLE_QUEEN) {
     generator->cur_mode = MOVE_GEN_MODE_END;
@@ -920,21 +991,25 @@ static move move_gen_next(move_gen *generator, int undo_moves) {
       generator->hit_move = 1;
       return castle;
     } else {
-      return move_gen_next(generator, undo_moves);
+      return move_gen_next(generator, undo_moves, legal_only);
     }
   } else if (generator->cur_mode == MOVE_GEN_MODE_NORMAL) {
     // if moves are remaining in bitboard, return them
     if (generator->cur_moves) {
       move next_move = move_gen_next_from_cur_moves(generator);
-      // make move
-      board_make_move(generator->board, next_move);
-      if (board_player_in_check(generator->board, player)) {
-        board_unmake_move(generator->board, next_move);
-        return move_gen_next(generator, undo_moves);
-      }
+      if (legal_only) {
+        // make move, and skip it if it leaves the player in check
+        board_make_move(generator->board, next_move);
+        if (board_player_in_check(generator->board, player)) {
+          board_unmake_move(generator->board, next_move);
+          return move_gen_next(generator, undo_moves, legal_only);
+        }
 
-      if (undo_moves) {
-        board_unmake_move(generator->board, next_move);
+        if (undo_moves) {
+          board_unmake_move(generator->board, next_move);
+        }
+      } else if (!undo_moves) {
+        board_make_move(generator->board, next_move);
       }
       generator->hit_move = 1;
       return next_move;
@@ -949,7 +1024,7 @@ static move move_gen_next(move_gen *generator, int undo_moves) {
       // if we reached the end of all the pieces, move to castles
       if (generator->cur_piece_type > QUEEN) {
         generator->cur_mode = MOVE_GEN_MODE_CASTLE_KING;
-        return move_gen_next(generator, undo_moves);
+        return move_gen_next(generator, undo_moves, legal_only);
       }
     } while (!bitboard_check_square(
         generator->board->pieces[generator->cur_piece_type] & player_mask,
@@ -961,18 +1036,22 @@ static move move_gen_next(move_gen *generator, int undo_moves) {
             generator->occupancy_for_sliders, generator->occupancy_for_pawns,
             generator->cur_piece_type, player, generator->cur_square) &
         generator->final_moves_mask;
-    return move_gen_next(generator, undo_moves);
+    return move_gen_next(generator, undo_moves, legal_only);
   } else {
     assert(0);
   }
 }
 
 move move_gen_next_move(move_gen *generator) {
-  return move_gen_next(generator, 1);
+  return move_gen_next(generator, 1, 1);
 }
 
 move move_gen_make_next_move(move_gen *generator) {
-  return move_gen_next(generator, 0);
+  return move_gen_next(generator, 0, 1);
+}
+
+move move_gen_next_pseudo_legal_move(move_gen *generator) {
+  return move_gen_next(generator, 1, 0);
 }
 
 int move_gen_is_checkmate(move_gen *const move_gen) {
@@ -0,0 +1,22 @@
+#ifndef ZOBRIST_H_INCL
+#define ZOBRIST_H_INCL
+
+#include "chess-util.h"
+#include <stdint.h>
+
+/**
+ * zobrist hashing helpers used to keep board->hash up to date
+ *
+ * a board's hash is the xor of the key for each piece on the board and the
+ * state key for its castling rights, en passant target, and player to move */
+
+/**
+ * get the key for a player's piece on a square */
+uint64_t zobrist_piece_key(int player, int piece, board_pos square);
+
+/**
+ * get the combined key for castling rights, en passant target, and player to
+ * move */
+uint64_t zobrist_state_key(const board *board);
+
+#endif
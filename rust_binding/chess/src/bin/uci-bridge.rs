@@ -0,0 +1,403 @@
+//! Connects a UCI engine (such as Stockfish) to a codekata server.
+//!
+//! The bridge logs in with protocol version 2 and answers each `go` request by sending the engine
+//! `position fen ...` and `go wtime ... btime ... winc ... binc ...`, then plays the engine's
+//! `bestmove`. The score, depth, nodes and pv from the engine's last `info` line are reported back
+//! to the server with the move. Engine processes are started as needed, so several games can be
+//! searched at once, and are reused for later moves. If the engine fails, the search is retried with
+//! a new engine, and the game is resigned if that fails too. Progress and errors go to stderr.
+use chess::client::{Client, ClientSender, Event, GoRequest};
+use chess::{Board, Move, Player};
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+use std::io;
+use std::process;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+
+// eval reported for a forced mate (less the number of plies to mate)
+const MATE_EVAL: i64 = 30000;
+// number of engines to try searching a position with before resigning the game
+const ENGINE_ATTEMPTS: usize = 2;
+
+/// A running UCI engine process
+struct UciEngine {
+    // kept so the engine is killed when dropped
+    _child: Child,
+    stdin: ChildStdin,
+    stdout: Lines<BufReader<ChildStdout>>,
+    // if the engine has the UCI_Chess960 option
+    supports_chess960: bool,
+    // if UCI_Chess960 is currently enabled
+    chess960: bool,
+    // the game the engine last searched, so it can be told when a new game starts
+    game_id: Option<i32>,
+}
+
+/// The result of an engine's search
+struct SearchOutput {
+    best_move: String,
+    info: HashMap<String, String>,
+}
+
+impl UciEngine {
+    /// Start an engine and wait for it to finish initializing
+    async fn start(command: &[String]) -> io::Result<UciEngine> {
+        let mut child = Command::new(&command[0])
+            .args(&command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap()).lines();
+        let mut engine = UciEngine {
+            _child: child,
+            stdin,
+            stdout,
+            supports_chess960: false,
+            chess960: false,
+            game_id: None,
+        };
+
+        engine.send("uci").await?;
+        loop {
+            let line = engine.read_line().await?;
+            if line == "uciok" {
+                break;
+            }
+            if line.starts_with("option name UCI_Chess960 ") {
+                engine.supports_chess960 = true;
+            }
+        }
+        engine.wait_ready().await?;
+        Ok(engine)
+    }
+
+    /// Send a command to the engine
+    async fn send(&mut self, cmd: &str) -> io::Result<()> {
+        self.stdin
+            .write_all(format!("{}\n", cmd).as_bytes())
+            .await?;
+        self.stdin.flush().await
+    }
+
+    /// Read a line from the engine. Fails if the engine exits.
+    async fn read_line(&mut self) -> io::Result<String> {
+        match self.stdout.next_line().await? {
+            Some(line) => Ok(line.trim().to_string()),
+            None => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "engine exited",
+            )),
+        }
+    }
+
+    /// Wait until the engine has processed all commands sent to it
+    async fn wait_ready(&mut self) -> io::Result<()> {
+        self.send("isready").await?;
+        while self.read_line().await? != "readyok" {}
+        Ok(())
+    }
+
+    /// Search for a move in the position given by a go request
+    async fn search(&mut self, go: &GoRequest, board: &Board) -> io::Result<SearchOutput> {
+        let chess960 = go.game_type == "chess960";
+        if chess960 && !self.supports_chess960 {
+            return Err(io::Error::other("engine doesn't support chess960"));
+        }
+        if self.supports_chess960 && chess960 != self.chess960 {
+            self.send(&format!("setoption name UCI_Chess960 value {}", chess960))
+                .await?;
+            self.chess960 = chess960;
+        }
+        if self.game_id != Some(go.game_id) {
+            self.send("ucinewgame").await?;
+            self.wait_ready().await?;
+            self.game_id = Some(go.game_id);
+        }
+
+        // the server doesn't send the opponent's time, so give the engine the same time for both players
+        let (time, inc) = (go.time_remaining.max(0), go.time_for_move.max(0));
+        self.send(&format!("position fen {}", go.state)).await?;
+        self.send(&format!(
+            "go wtime {} btime {} winc {} binc {}",
+            time, time, inc, inc
+        ))
+        .await?;
+
+        let mut info = HashMap::new();
+        loop {
+            let line = self.read_line().await?;
+            if let Some(args) = line.strip_prefix("info ") {
+                if args.contains("score ") {
+                    info = parse_info(args);
+                }
+            } else if let Some(args) = line.strip_prefix("bestmove ") {
+                let best_move = args.split_whitespace().next().unwrap_or("").to_string();
+                if Move::from_str(&best_move, board).is_none() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("engine returned invalid move: {}", best_move),
+                    ));
+                }
+                return Ok(SearchOutput { best_move, info });
+            }
+        }
+    }
+}
+
+/// Convert the arguments of a UCI `info` line to the keys reported to the server
+fn parse_info(args: &str) -> HashMap<String, String> {
+    let mut info = HashMap::new();
+    let mut tokens = args.split_whitespace();
+    while let Some(token) = tokens.next() {
+        match token {
+            "depth" | "seldepth" | "nodes" | "nps" | "time" => {
+                if let Some(value) = tokens.next() {
+                    info.insert(token.to_string(), value.to_string());
+                }
+            }
+            "score" => match (
+                tokens.next(),
+                tokens.next().and_then(|v| v.parse::<i64>().ok()),
+            ) {
+                (Some("cp"), Some(cp)) => {
+                    info.insert("eval".to_string(), cp.to_string());
+                }
+                (Some("mate"), Some(moves)) => {
+                    let plies = moves.abs() * 2 - if moves > 0 { 1 } else { 0 };
+                    let eval = (MATE_EVAL - plies) * moves.signum();
+                    info.insert("eval".to_string(), eval.to_string());
+                    info.insert("mate".to_string(), moves.to_string());
+                }
+                _ => {}
+            },
+            // the pv is the rest of the line
+            "pv" => {
+                info.insert(
+                    "pv".to_string(),
+                    tokens.by_ref().collect::<Vec<&str>>().join(" "),
+                );
+            }
+            // the rest of the line is free text
+            "string" => break,
+            _ => {}
+        }
+    }
+    info
+}
+
+/// Search for and play a move requested by the server
+async fn play_move(
+    go: GoRequest,
+    sender: ClientSender,
+    idle: Arc<Mutex<Vec<UciEngine>>>,
+    command: Arc<Vec<String>>,
+) {
+    if go.game_type != "chess" && go.game_type != "chess960" {
+        eprintln!(
+            "can't play game {}: unsupported game type {}",
+            go.game_id, go.game_type
+        );
+        return;
+    }
+    let board = match go.board() {
+        Some(board) => board,
+        None => {
+            eprintln!("invalid position from server: {}", go.state);
+            return;
+        }
+    };
+
+    // prefer an engine that has been playing this game, so its hash table is still useful
+    let mut engine = {
+        let mut idle = idle.lock().unwrap();
+        match idle.iter().position(|e| e.game_id == Some(go.game_id)) {
+            Some(i) => Some(idle.swap_remove(i)),
+            None => idle.pop(),
+        }
+    };
+
+    // a failed engine is dropped (and killed), and the search is retried with a new engine
+    let mut result = None;
+    for _ in 0..ENGINE_ATTEMPTS {
+        let mut current = match engine.take() {
+            Some(engine) => engine,
+            None => match UciEngine::start(&command).await {
+                Ok(engine) => engine,
+                Err(e) => {
+                    eprintln!("error starting engine: {}", e);
+                    continue;
+                }
+            },
+        };
+        match current.search(&go, &board).await {
+            Ok(output) => {
+                result = Some((current, output));
+                break;
+            }
+            Err(e) => eprintln!("engine error in game {}: {}", go.game_id, e),
+        }
+    }
+
+    match result {
+        Some((engine, output)) => {
+            let m = Move::from_str(&output.best_move, &board).unwrap();
+            let side = match board.player_to_move() {
+                Player::White => "white",
+                Player::Black => "black",
+            };
+            eprintln!("game {} ({}): {}", go.game_id, side, m);
+            if sender.play(go.game_id, m).is_ok() && !output.info.is_empty() {
+                let _ = sender.info(go.game_id, &output.info);
+            }
+            idle.lock().unwrap().push(engine);
+        }
+        // resign rather than leave the game to run out its clock
+        None => {
+            eprintln!(
+                "resigning game {}: the engine failed {} times",
+                go.game_id, ENGINE_ATTEMPTS
+            );
+            if let Err(e) = sender.resign(go.game_id) {
+                eprintln!("error resigning game {}: {}", go.game_id, e);
+            }
+        }
+    }
+}
+
+async fn run(
+    host: &str,
+    port: &str,
+    apikey: &str,
+    name: &str,
+    command: Vec<String>,
+) -> Result<(), Box<dyn Error>> {
+    // start an engine up front, so a bad engine command is reported immediately
+    let engine = UciEngine::start(&command)
+        .await
+        .map_err(|e| format!("error starting engine {}: {}", command[0], e))?;
+    let idle = Arc::new(Mutex::new(vec![engine]));
+    let command = Arc::new(command);
+
+    let mut client = Client::connect(host, port, apikey, name).await?;
+    loop {
+        match client.next_event().await? {
+            Event::Go(go) => {
+                tokio::spawn(play_move(
+                    go,
+                    client.sender(),
+                    idle.clone(),
+                    command.clone(),
+                ));
+            }
+            Event::Error(msg) => eprintln!("error from server: {}", msg),
+            _ => {}
+        }
+    }
+}
+
+fn main() {
+    let args = env::args().collect::<Vec<String>>();
+    if args.len() < 6 {
+        eprintln!(
+            "usage: {} host port apikey name engine [engine args...]",
+            args[0]
+        );
+        eprintln!(
+            "example: {} codekata-chess.herokuapp.com 80 API_KEY Stockfish /usr/bin/stockfish",
+            args[0]
+        );
+        process::exit(1);
+    }
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let res = runtime.block_on(run(
+        &args[1],
+        &args[2],
+        &args[3],
+        &args[4],
+        args[5..].to_vec(),
+    ));
+    if let Err(e) = res {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn info(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parse_info_keys() {
+        assert_eq!(
+            parse_info(
+                "depth 12 seldepth 18 multipv 1 score cp -34 nodes 12345 nps 98000 time 126 \
+                 pv e2e4 e7e5 g1f3"
+            ),
+            info(&[
+                ("depth", "12"),
+                ("seldepth", "18"),
+                ("eval", "-34"),
+                ("nodes", "12345"),
+                ("nps", "98000"),
+                ("time", "126"),
+                ("pv", "e2e4 e7e5 g1f3"),
+            ])
+        );
+        // bounds and unknown keys are ignored
+        assert_eq!(
+            parse_info("score cp 20 lowerbound hashfull 500 tbhits 0"),
+            info(&[("eval", "20")])
+        );
+        assert_eq!(parse_info("score wdl 1"), info(&[]));
+        assert_eq!(parse_info("depth"), info(&[]));
+    }
+
+    #[test]
+    fn parse_info_mate_scores() {
+        // mate in n moves is n * 2 - 1 plies away for the player to move, and n * 2 plies for the other player
+        assert_eq!(
+            parse_info("score mate 1"),
+            info(&[("eval", "29999"), ("mate", "1")])
+        );
+        assert_eq!(
+            parse_info("score mate 3"),
+            info(&[("eval", "29995"), ("mate", "3")])
+        );
+        assert_eq!(
+            parse_info("score mate -2"),
+            info(&[("eval", "-29996"), ("mate", "-2")])
+        );
+        assert_eq!(parse_info("score mate x"), info(&[]));
+    }
+
+    #[test]
+    fn parse_info_pv_and_string() {
+        // the pv and string take the rest of the line
+        assert_eq!(
+            parse_info("pv e2e4 depth 3"),
+            info(&[("pv", "e2e4 depth 3")])
+        );
+        assert_eq!(
+            parse_info("depth 5 string score cp 100 pv e2e4"),
+            info(&[("depth", "5")])
+        );
+        assert_eq!(parse_info("string"), info(&[]));
+    }
+}
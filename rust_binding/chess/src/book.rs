@@ -0,0 +1,312 @@
+//! Reader for opening books in the Polyglot `.bin` format
+//!
+//! A Polyglot book is a list of 16 byte entries sorted by position hash. Each entry holds the
+//! [`Board::hash`] of a position, a move that can be played from it, and a weight describing how
+//! often the move should be played.
+use crate::{Board, BoardPos, Move, PieceType};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// size of a book entry in bytes
+const ENTRY_SIZE: usize = 16;
+
+/// A single entry in an opening book
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BookEntry {
+    /// Hash of the position the entry applies to
+    pub key: u64,
+    /// The move in Polyglot's encoding. See [`BookEntry::to_move`] to decode it
+    pub raw_move: u16,
+    /// Relative weight of the move among the entries for the position
+    pub weight: u16,
+    /// Learning data (unused by most books)
+    pub learn: u32,
+}
+
+impl BookEntry {
+    /// Decode an entry from its 16 big endian bytes
+    fn from_bytes(bytes: &[u8]) -> BookEntry {
+        let mut key = [0u8; 8];
+        key.copy_from_slice(&bytes[0..8]);
+        BookEntry {
+            key: u64::from_be_bytes(key),
+            raw_move: u16::from_be_bytes([bytes[8], bytes[9]]),
+            weight: u16::from_be_bytes([bytes[10], bytes[11]]),
+            learn: u32::from_be_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]),
+        }
+    }
+
+    /// Convert the entry's move into a legal move on board, or return None if it isn't legal.
+    ///
+    /// Polyglot encodes castling as the king moving onto its rook (e1h1, e1a1, e8h8, e8a8), which is also
+    /// how castles are represented internally, so castles are matched directly against the legal moves.
+    pub fn to_move(&self, board: &Board) -> Option<Move> {
+        let square = |bits: u16| BoardPos::new((bits & 7) as i32, ((bits >> 3) & 7) as i32);
+        let dst = square(self.raw_move);
+        let src = square(self.raw_move >> 6);
+        let promote = match (self.raw_move >> 12) & 7 {
+            0 => None,
+            1 => Some(PieceType::Knight),
+            2 => Some(PieceType::Bishop),
+            3 => Some(PieceType::Rook),
+            4 => Some(PieceType::Queen),
+            _ => return None,
+        };
+        board
+            .legal_moves()
+            .into_iter()
+            .find(|m| m.src() == src && m.dst() == dst && m.promote() == promote)
+    }
+}
+
+/// A book move for a position, along with its weight
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BookMove {
+    pub chess_move: Move,
+    pub weight: u16,
+}
+
+/// A Polyglot opening book
+#[derive(Clone, Debug, Default)]
+pub struct Book {
+    // entries, sorted by key
+    entries: Vec<BookEntry>,
+}
+
+impl Book {
+    /// Load a book from a Polyglot `.bin` file
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Book> {
+        Ok(Book::from_bytes(&fs::read(path)?))
+    }
+
+    /// Load a book from the contents of a Polyglot `.bin` file. A trailing partial entry is ignored.
+    pub fn from_bytes(data: &[u8]) -> Book {
+        let mut entries = data
+            .chunks_exact(ENTRY_SIZE)
+            .map(BookEntry::from_bytes)
+            .collect::<Vec<BookEntry>>();
+        // books should already be sorted, but lookups rely on it
+        entries.sort_by_key(|entry| entry.key);
+        Book { entries }
+    }
+
+    /// Get the number of entries in the book
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if the book has no entries
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Get the book entries for a position hash
+    pub fn entries(&self, key: u64) -> &[BookEntry] {
+        let start = self.entries.partition_point(|entry| entry.key < key);
+        let end = self.entries.partition_point(|entry| entry.key <= key);
+        &self.entries[start..end]
+    }
+
+    /// Get the legal book moves for a board, along with their weights. Entries that don't describe a legal
+    /// move (such as those from hash collisions) are skipped.
+    pub fn moves(&self, board: &Board) -> Vec<BookMove> {
+        self.entries(board.hash())
+            .iter()
+            .filter_map(|entry| {
+                entry.to_move(board).map(|chess_move| BookMove {
+                    chess_move,
+                    weight: entry.weight,
+                })
+            })
+            .collect()
+    }
+
+    /// Pick a book move for a board with probability proportional to its weight, or return None if the
+    /// position isn't in the book. `roll` should be a random number, and is reduced modulo the total weight
+    /// of the moves. Moves with zero weight are never picked.
+    pub fn weighted_move(&self, board: &Board, roll: u32) -> Option<Move> {
+        let moves = self.moves(board);
+        let total = moves.iter().map(|m| m.weight as u32).sum::<u32>();
+        if total == 0 {
+            return None;
+        }
+        let mut roll = roll % total;
+        for m in moves {
+            if roll < m.weight as u32 {
+                return Some(m.chess_move);
+            }
+            roll -= m.weight as u32;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Once;
+
+    const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    static INIT: Once = Once::new();
+
+    fn board(fen: &str) -> Board {
+        INIT.call_once(crate::init);
+        Board::new(fen)
+    }
+
+    // polyglot move encoding of (source square, destination square, promotion piece)
+    fn raw_move(src: u16, dst: u16, promote: u16) -> u16 {
+        promote << 12 | src << 6 | dst
+    }
+
+    // encode polyglot book entries for (position, move, weight)
+    fn book_bytes(entries: &[(&str, u16, u16)]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for (fen, raw_move, weight) in entries.iter() {
+            data.extend_from_slice(&board(fen).hash().to_be_bytes());
+            data.extend_from_slice(&raw_move.to_be_bytes());
+            data.extend_from_slice(&weight.to_be_bytes());
+            data.extend_from_slice(&[0; 4]);
+        }
+        data
+    }
+
+    fn entry(raw_move: u16) -> BookEntry {
+        BookEntry {
+            key: 0,
+            raw_move,
+            weight: 1,
+            learn: 0,
+        }
+    }
+
+    #[test]
+    fn to_move_castling_test() {
+        // castles are encoded as the king moving onto its rook
+        let white = board("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        let kingside = entry(raw_move(4, 7, 0)).to_move(&white).unwrap();
+        assert!(kingside.castle());
+        assert_eq!(kingside, Move::from_str("e1g1", &white).unwrap());
+        let queenside = entry(raw_move(4, 0, 0)).to_move(&white).unwrap();
+        assert!(queenside.castle());
+        assert_eq!(queenside, Move::from_str("e1c1", &white).unwrap());
+
+        let black = board("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1");
+        let kingside = entry(raw_move(60, 63, 0)).to_move(&black).unwrap();
+        assert!(kingside.castle());
+        assert_eq!(kingside, Move::from_str("e8g8", &black).unwrap());
+        let queenside = entry(raw_move(60, 56, 0)).to_move(&black).unwrap();
+        assert!(queenside.castle());
+        assert_eq!(queenside, Move::from_str("e8c8", &black).unwrap());
+
+        // without castling rights, the king can't move onto its rook
+        let no_rights = board("r3k2r/8/8/8/8/8/8/R3K2R w - - 0 1");
+        assert_eq!(entry(raw_move(4, 7, 0)).to_move(&no_rights), None);
+    }
+
+    #[test]
+    fn to_move_promotion_test() {
+        let board = board("3r4/4P3/8/8/8/8/k7/4K3 w - - 0 1");
+        let to_move = |raw_move| entry(raw_move).to_move(&board).map(|m| m.to_string());
+        assert_eq!(to_move(raw_move(52, 60, 4)), Some("e7e8q".to_string()));
+        assert_eq!(to_move(raw_move(52, 60, 3)), Some("e7e8r".to_string()));
+        assert_eq!(to_move(raw_move(52, 60, 2)), Some("e7e8b".to_string()));
+        assert_eq!(to_move(raw_move(52, 59, 1)), Some("e7d8n".to_string()));
+        // a pawn reaching the last rank has to promote, and there's no promotion piece 5
+        assert_eq!(to_move(raw_move(52, 60, 0)), None);
+        assert_eq!(to_move(raw_move(52, 60, 5)), None);
+        // illegal moves aren't decoded
+        assert_eq!(to_move(raw_move(52, 44, 0)), None);
+    }
+
+    #[test]
+    fn entries_test() {
+        let after_e4 = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1";
+        let book = Book::from_bytes(&book_bytes(&[
+            (after_e4, raw_move(51, 35, 0), 4),
+            (START_FEN, raw_move(12, 28, 0), 3),
+            (START_FEN, raw_move(11, 27, 0), 2),
+            (after_e4, raw_move(50, 34, 0), 1),
+            (START_FEN, raw_move(6, 21, 0), 1),
+        ]));
+        assert_eq!(book.len(), 5);
+        assert!(!book.is_empty());
+
+        // entries for a key are contiguous, whatever order the file had them in
+        let start_key = board(START_FEN).hash();
+        let start_entries = book.entries(start_key);
+        assert_eq!(start_entries.len(), 3);
+        assert!(start_entries.iter().all(|entry| entry.key == start_key));
+        assert_eq!(
+            start_entries.iter().map(|entry| entry.weight).sum::<u16>(),
+            6
+        );
+        let e4_key = board(after_e4).hash();
+        assert_eq!(book.entries(e4_key).len(), 2);
+        assert!(book.entries(e4_key).iter().all(|entry| entry.key == e4_key));
+
+        // keys outside the book, including before the first and after the last entry
+        assert_eq!(book.entries(0), &[]);
+        assert_eq!(book.entries(u64::MAX), &[]);
+        assert_eq!(book.entries(start_key.min(e4_key) + 1).len(), 0);
+
+        // a trailing partial entry is ignored
+        let mut data = book_bytes(&[(START_FEN, raw_move(12, 28, 0), 1)]);
+        data.extend_from_slice(&[1, 2, 3]);
+        assert_eq!(Book::from_bytes(&data).len(), 1);
+        assert!(Book::from_bytes(&[]).is_empty());
+    }
+
+    #[test]
+    fn weighted_move_test() {
+        let book = Book::from_bytes(&book_bytes(&[
+            // e2e4, d2d4, and g1f3, with c2c4 never played
+            (START_FEN, raw_move(10, 26, 0), 0),
+            (START_FEN, raw_move(12, 28, 0), 2),
+            (START_FEN, raw_move(11, 27, 0), 0),
+            (START_FEN, raw_move(6, 21, 0), 3),
+        ]));
+        let start = board(START_FEN);
+        assert_eq!(book.moves(&start).len(), 4);
+
+        let mut counts = std::collections::HashMap::new();
+        for roll in 0..500 {
+            let m = book.weighted_move(&start, roll).unwrap();
+            *counts.entry(m.to_string()).or_insert(0) += 1;
+        }
+        // rolls are split in proportion to the weights, and zero weight moves are never picked
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts["e2e4"], 200);
+        assert_eq!(counts["g1f3"], 300);
+        // rolls wrap around the total weight
+        assert_eq!(book.weighted_move(&start, 5), book.weighted_move(&start, 0));
+
+        // a position with only zero weight moves has no book move, like one outside the book
+        let zero_weights = Book::from_bytes(&book_bytes(&[(START_FEN, raw_move(12, 28, 0), 0)]));
+        assert_eq!(zero_weights.moves(&start).len(), 1);
+        assert_eq!(zero_weights.weighted_move(&start, 0), None);
+        assert_eq!(
+            book.weighted_move(&board("8/8/4k3/8/8/4K3/8/8 w - - 0 1"), 0),
+            None
+        );
+
+        // entries from hash collisions that aren't legal moves are skipped
+        let castling = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        let book = Book::from_bytes(&book_bytes(&[
+            (castling, raw_move(4, 7, 0), 3),
+            (castling, raw_move(4, 36, 0), 5),
+        ]));
+        let castling = board(castling);
+        let castle = Move::from_str("e1g1", &castling).unwrap();
+        assert_eq!(
+            book.moves(&castling),
+            vec![BookMove {
+                chess_move: castle,
+                weight: 3
+            }]
+        );
+        assert_eq!(book.weighted_move(&castling, 7), Some(castle));
+    }
+}
@@ -112,6 +112,7 @@ pub const BOARD_FLAGS_W_CASTLE_KING: u32 = 256;
 pub const BOARD_FLAGS_W_CASTLE_QUEEN: u32 = 512;
 pub const BOARD_FLAGS_B_CASTLE_KING: u32 = 1024;
 pub const BOARD_FLAGS_B_CASTLE_QUEEN: u32 = 2048;
+pub const BOARD_FLAGS_CHESS960: u32 = 4096;
 pub const MOVE_END: u64 = 0xffffffffffffffff;
 pub type __u_char = ::std::os::raw::c_uchar;
 pub type __u_short = ::std::os::raw::c_ushort;
@@ -228,13 +229,15 @@ pub type __chess_util_board_pos = u8;
 pub struct __chess_util_board {
     pub players: [__chess_util_bitboard; 2usize],
     pub pieces: [__chess_util_bitboard; 6usize],
-    pub flags: u16,
+    pub flags: u32,
+    pub castle_files: [u8; 4usize],
+    pub hash: u64,
 }
 #[test]
 fn bindgen_test_layout___chess_util_board() {
     assert_eq!(
         ::std::mem::size_of::<__chess_util_board>(),
-        72usize,
+        80usize,
         concat!("Size of: ", stringify!(__chess_util_board))
     );
     assert_eq!(
@@ -272,6 +275,26 @@ fn bindgen_test_layout___chess_util_board() {
             stringify!(flags)
         )
     );
+    assert_eq!(
+        unsafe { &(*(::std::ptr::null::<__chess_util_board>())).castle_files as *const _ as usize },
+        68usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(__chess_util_board),
+            "::",
+            stringify!(castle_files)
+        )
+    );
+    assert_eq!(
+        unsafe { &(*(::std::ptr::null::<__chess_util_board>())).hash as *const _ as usize },
+        72usize,
+        concat!(
+            "Offset of field: ",
+            stringify!(__chess_util_board),
+            "::",
+            stringify!(hash)
+        )
+    );
 }
 pub type __chess_util_move = u64;
 #[repr(C)]
@@ -541,10 +564,27 @@ extern "C" {
     #[doc = " check that board is in a consistent state"]
     pub fn board_invariants(board: *const board);
 }
+extern "C" {
+    #[doc = " return 1 if board is in a consistent state (as checked by board_invariants),"]
+    #[doc = " 0 otherwise"]
+    pub fn board_is_valid(board: *const board) -> ::std::os::raw::c_int;
+}
 extern "C" {
     #[doc = " initialize a board from a board in FEN notation"]
+    #[doc = " castling rights may be given in standard notation (KQkq), Shredder-FEN"]
+    #[doc = " (rook files, eg HAha), or X-FEN (KQkq referring to the outermost rooks)."]
+    #[doc = " Shredder-FEN, or X-FEN with the king or rooks off their standard squares,"]
+    #[doc = " marks the board as a chess960 board"]
     pub fn board_from_fen_str(board: *mut board, fen_string: *const ::std::os::raw::c_char);
 }
+extern "C" {
+    #[doc = " check if a FEN string is wellformed and describes a valid position"]
+    #[doc = " (consistent according to board_invariants, no pawns on the first or last"]
+    #[doc = " rank, and the player not to move isn't in check)"]
+    #[doc = " EPD strings (FEN without the halfmove clock and turn counter, optionally"]
+    #[doc = " followed by operations) are also accepted"]
+    pub fn board_fen_str_is_valid(fen_string: *const ::std::os::raw::c_char) -> ::std::os::raw::c_int;
+}
 extern "C" {
     #[doc = " express a board in FEN notation (minus halfmove + turn counters)"]
     #[doc = " res_str must have 87 bytes allocated"]
@@ -584,6 +624,30 @@ extern "C" {
         side: ::std::os::raw::c_int,
     ) -> ::std::os::raw::c_int;
 }
+extern "C" {
+    #[doc = " get the file of the rook player castles with on side (side should be either"]
+    #[doc = " KING or QUEEN)"]
+    pub fn board_castle_rook_file(
+        board: *const board,
+        player: ::std::os::raw::c_int,
+        side: ::std::os::raw::c_int,
+    ) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    #[doc = " check if the board uses chess960 castling notation"]
+    pub fn board_is_chess960(board: *const board) -> ::std::os::raw::c_int;
+}
+extern "C" {
+    #[doc = " get the zobrist hash of the board"]
+    #[doc = " the hash uses the same keys as polyglot opening books, so it can be used to"]
+    #[doc = " look up positions in them. The hash is updated incrementally when moves are"]
+    #[doc = " made and unmade."]
+    pub fn board_hash(board: *const board) -> u64;
+}
+extern "C" {
+    #[doc = " compute the zobrist hash of the board from scratch"]
+    pub fn board_compute_hash(board: *const board) -> u64;
+}
 extern "C" {
     #[doc = " print a board on stdout using ony ascii characters"]
     pub fn board_print(board: *const board);
@@ -630,6 +694,10 @@ extern "C" {
 #[doc = " bits 33-35 : type of captured piece"]
 #[doc = " bits 36-41 : square capture piece was on (may be different from move"]
 #[doc = " destination due to en passant)"]
+#[doc = " bit  42    : set if the move is a castle"]
+#[doc = ""]
+#[doc = " castles are stored as the king moving onto the square of the rook it castles"]
+#[doc = " with"]
 pub type move_ = __chess_util_move;
 extern "C" {
     #[doc = " get the source square of a move (where a piece is being moved from )"]
@@ -678,6 +746,8 @@ extern "C" {
     #[doc = " a7a8n (promoting to knight)"]
     #[doc = " a7a8b (promoting to bishop)"]
     #[doc = " a7a8r (promoting to rook)"]
+    #[doc = " castles are written as the king's movement (e1g1) on standard boards, and as"]
+    #[doc = " the king moving onto its rook (e1h1) on chess960 boards"]
     #[doc = ""]
     #[doc = " res_str must have 6 bytes allocated"]
     pub fn move_to_str(move_: move_, res_str: *mut ::std::os::raw::c_char);
@@ -725,6 +795,15 @@ extern "C" {
     #[doc = " move_gen_next_move again"]
     pub fn move_gen_make_next_move(generator: *mut move_gen) -> move_;
 }
+extern "C" {
+    #[doc = " get the next pseudo-legal move from the move generator. pseudo-legal moves"]
+    #[doc = " follow how pieces move, but may leave the player to move in check (castles"]
+    #[doc = " are only generated when legal)"]
+    #[doc = " if no more moves are available, the method returns MOVE_END"]
+    #[doc = " move_gen_is_checkmate and move_gen_is_stalemate don't detect checkmate or"]
+    #[doc = " stalemate after pseudo-legal move generation"]
+    pub fn move_gen_next_pseudo_legal_move(generator: *mut move_gen) -> move_;
+}
 extern "C" {
     #[doc = " make the given move on the given board"]
     #[doc = " mutates board"]
@@ -0,0 +1,460 @@
+//! Asynchronous client for the codekata server's protocol version 2
+//!
+//! Unlike [`crate::connect_to_server`], which speaks protocol version 1 and plays one game at a
+//! time, [`Client`] can be sent `go` requests for many games at once, and reports every message
+//! from the server as an [`Event`]. It needs to be used from inside a tokio runtime, and is only
+//! built with the `client` feature.
+pub use crate::ClientError;
+use crate::{init, Board, Move};
+use futures_util::{SinkExt, StreamExt};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio_tungstenite::connect_async;
+use tungstenite::Message;
+use url::Url;
+
+/// A request from the server to make a move in a game (sent as `go`)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GoRequest {
+    /// The game to move in
+    pub game_id: i32,
+    /// The type of game (`chess` or `chess960`)
+    pub game_type: String,
+    /// Time (in ms) the client has left for the whole game
+    pub time_remaining: i64,
+    /// Additional time (in ms) the client is given for this move
+    pub time_for_move: i64,
+    /// The game's current state. For chess games this is the position in FEN
+    pub state: String,
+    /// If the client's opponent has offered a draw
+    pub draw_offer: bool,
+}
+
+impl GoRequest {
+    /// Parse the arguments of a `go` command, or return None if they are malformed
+    fn parse(args: &str) -> Option<GoRequest> {
+        let mut parts = args.splitn(5, ", ");
+        let game_id = parts.next()?.parse().ok()?;
+        let game_type = parts.next()?.to_string();
+        let time_remaining = parts.next()?.parse().ok()?;
+        let time_for_move = parts.next()?.parse().ok()?;
+        let state = parts.next()?;
+        let (state, draw_offer) = match state.strip_suffix(", draw_offer") {
+            Some(state) => (state, true),
+            None => (state, false),
+        };
+        Some(GoRequest {
+            game_id,
+            game_type,
+            time_remaining,
+            time_for_move,
+            state: state.to_string(),
+            draw_offer,
+        })
+    }
+
+    /// Get the board for the request's game state
+    pub fn board(&self) -> Option<Board> {
+        Board::from_fen(&self.state)
+    }
+}
+
+/// The result of a finished game
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameResult {
+    /// The game was won by the given user
+    Win(i32),
+    /// The game ended in a tie
+    Tie,
+}
+
+/// A player in a game, as sent in a `game` message
+#[derive(Clone, Debug, PartialEq)]
+pub struct GamePlayer {
+    pub user_id: i32,
+    /// The player's score in the game (0 until it has ended)
+    pub score: f64,
+    /// Time (in ms) the player has left for the whole game
+    pub time_ms: i64,
+    /// The player's current rating in the game type
+    pub rating: i64,
+}
+
+/// The state of an observed game (sent as `game`)
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameInfo {
+    pub id: i32,
+    /// The type of game (`chess` or `chess960`)
+    pub game_type: String,
+    /// The user who created the game
+    pub owner: i32,
+    pub started: bool,
+    pub finished: bool,
+    /// The game's result, or None if it hasn't ended
+    pub result: Option<GameResult>,
+    /// Time (in ms) each player has for the whole game
+    pub time_ms: i64,
+    /// Additional time (in ms) each player is given per move
+    pub time_per_move_ms: i64,
+    /// Time (in ms) the current player has left for their move, or None if the game isn't being played
+    pub current_player_time_for_move: Option<i64>,
+    pub players: Vec<GamePlayer>,
+    /// The player who has offered a draw, if any
+    pub draw_offer: Option<i32>,
+    /// The game's state, or None if it hasn't started. For chess games this is the position in FEN and the moves
+    /// made, and ended games give their winner and how they ended first
+    pub state: Option<String>,
+}
+
+impl GameInfo {
+    /// Parse the arguments of a `game` command, or return None if they are malformed
+    fn parse(args: &str) -> Option<GameInfo> {
+        let mut parts = args.splitn(10, ", ");
+        let id = parts.next()?.parse().ok()?;
+        let game_type = parts.next()?.to_string();
+        let owner = parts.next()?.parse().ok()?;
+        let started = parts.next()?.parse().ok()?;
+        let finished = parts.next()?.parse().ok()?;
+        let result = match parts.next()? {
+            "-" => None,
+            "tie" => Some(GameResult::Tie),
+            winner => Some(GameResult::Win(winner.parse().ok()?)),
+        };
+        let time_ms = parts.next()?.parse().ok()?;
+        let time_per_move_ms = parts.next()?.parse().ok()?;
+        let current_player_time_for_move = match parts.next()? {
+            "-" => None,
+            time => Some(time.parse().ok()?),
+        };
+        // the players list is followed by the draw offer and state, which may contain commas
+        let rest = parts.next()?;
+        let (players_str, rest) = match rest.strip_prefix("[], ") {
+            Some(rest) => ("", rest),
+            None => rest.strip_prefix('[')?.split_once("]], ")?,
+        };
+        let mut players = Vec::new();
+        for player in players_str.split("], ").filter(|p| !p.is_empty()) {
+            let mut fields = player
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .split(", ");
+            players.push(GamePlayer {
+                user_id: fields.next()?.parse().ok()?,
+                score: fields.next()?.parse().ok()?,
+                time_ms: fields.next()?.parse().ok()?,
+                rating: fields.next()?.parse().ok()?,
+            });
+        }
+        let (draw_offer, state) = rest.split_once(", ")?;
+        let draw_offer = match draw_offer {
+            "-" => None,
+            uid => Some(uid.parse().ok()?),
+        };
+        let state = match state {
+            "-" => None,
+            state => Some(state.to_string()),
+        };
+        Some(GameInfo {
+            id,
+            game_type,
+            owner,
+            started,
+            finished,
+            result,
+            time_ms,
+            time_per_move_ms,
+            current_player_time_for_move,
+            players,
+            draw_offer,
+            state,
+        })
+    }
+}
+
+/// A message received from the server
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// The server wants a move to be made in a game
+    Go(GoRequest),
+    /// A command was processed successfully
+    Okay,
+    /// A command failed, with the server's error message
+    Error(String),
+    /// The state of an observed game
+    Game(GameInfo),
+    /// Any other message, as sent by the server
+    Other(String),
+}
+
+impl Event {
+    /// Parse a message from the server
+    fn parse(text: &str) -> Event {
+        let (cmd, args) = text.split_once(' ').unwrap_or((text, ""));
+        match cmd {
+            "okay" => Event::Okay,
+            "error" => Event::Error(args.to_string()),
+            "go" => match GoRequest::parse(args) {
+                Some(go) => Event::Go(go),
+                None => Event::Other(text.to_string()),
+            },
+            "game" => match GameInfo::parse(args) {
+                Some(game) => Event::Game(game),
+                None => Event::Other(text.to_string()),
+            },
+            _ => Event::Other(text.to_string()),
+        }
+    }
+}
+
+/// A handle for sending commands to the server. Senders can be cloned and moved into other tasks, so moves
+/// for several games can be searched for at once.
+#[derive(Clone, Debug)]
+pub struct ClientSender(mpsc::UnboundedSender<Message>);
+
+impl ClientSender {
+    /// Send a raw command to the server
+    pub fn send(&self, cmd: &str) -> Result<(), ClientError> {
+        self.0
+            .send(Message::Text(cmd.to_string()))
+            .map_err(|_| ClientError::Closed)
+    }
+
+    /// Make a move in a game, in response to a `go` request
+    pub fn play(&self, game_id: i32, m: Move) -> Result<(), ClientError> {
+        self.send(&format!("play {}, {}", game_id, m))
+    }
+
+    /// Report information about the last move made in a game, such as the `eval` it was given or the search
+    /// `depth`, `nodes` and `pv`
+    pub fn info(&self, game_id: i32, info: &HashMap<String, String>) -> Result<(), ClientError> {
+        let mut info_str = format!("info {}, ", game_id);
+        for (key, value) in info {
+            info_str += &format!("{} {}`", key, value);
+        }
+        self.send(&info_str)
+    }
+
+    /// Resign from a game
+    pub fn resign(&self, game_id: i32) -> Result<(), ClientError> {
+        self.send(&format!("resign {}", game_id))
+    }
+
+    /// Offer a draw in a game (or accept the opponent's offer)
+    pub fn offer_draw(&self, game_id: i32) -> Result<(), ClientError> {
+        self.send(&format!("offer_draw {}", game_id))
+    }
+}
+
+/// A connection to a codekata server using protocol version 2
+#[derive(Debug)]
+pub struct Client {
+    sender: ClientSender,
+    events: mpsc::UnboundedReceiver<Result<Event, ClientError>>,
+}
+
+impl Client {
+    /// Connect to a codekata server at host and port, switch to protocol version 2, and log in with apikey and
+    /// set the user's name
+    pub async fn connect(
+        host: &str,
+        port: &str,
+        apikey: &str,
+        name: &str,
+    ) -> Result<Client, ClientError> {
+        init();
+
+        let url = Url::parse(&format!("ws://{}:{}/", host, port))?;
+        let (socket, _) = connect_async(url).await?;
+        let (mut write, mut read) = socket.split();
+
+        // forward commands from senders to the server
+        let (tx, mut rx) = mpsc::unbounded_channel::<Message>();
+        tokio::spawn(async move {
+            while let Some(msg) = rx.recv().await {
+                if write.send(msg).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        // parse messages from the server into events
+        let (events_tx, events) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Some(msg) = read.next().await {
+                let event = match msg {
+                    Ok(Message::Text(text)) => Ok(Event::parse(&text)),
+                    Ok(Message::Close(_)) => Err(ClientError::Closed),
+                    Ok(_) => continue,
+                    Err(e) => Err(ClientError::from(e)),
+                };
+                let done = event.is_err();
+                if events_tx.send(event).is_err() || done {
+                    return;
+                }
+            }
+            let _ = events_tx.send(Err(ClientError::Closed));
+        });
+
+        let sender = ClientSender(tx);
+        // the version has to be set before logging in, since games waiting for a move are sent on login
+        sender.send("version 2")?;
+        sender.send(&format!("apikey {}", apikey))?;
+        sender.send(&format!("name {}", name))?;
+
+        Ok(Client { sender, events })
+    }
+
+    /// Get a handle for sending commands to the server
+    pub fn sender(&self) -> ClientSender {
+        self.sender.clone()
+    }
+
+    /// Wait for the next message from the server. Returns an error once the connection is closed.
+    pub async fn next_event(&mut self) -> Result<Event, ClientError> {
+        self.events.recv().await.unwrap_or(Err(ClientError::Closed))
+    }
+
+    /// Play games until the connection to the server fails, and return the error that ended it. func is called (on a
+    /// blocking thread, so it may search for as long as it likes) to pick a move whenever one is requested, and moves
+    /// for different games are picked concurrently. Errors from the server (such as a rejected move) and positions
+    /// that can't be loaded are logged, and play continues. Other events are ignored.
+    pub async fn play<F>(mut self, func: F) -> Result<(), ClientError>
+    where
+        F: Fn(&mut Board, &GoRequest) -> Move + Send + Sync + 'static,
+    {
+        let func = Arc::new(func);
+        loop {
+            match self.next_event().await? {
+                Event::Go(go) => {
+                    let mut board = match go.board() {
+                        Some(board) => board,
+                        None => {
+                            log::warn!("{}", ClientError::InvalidPosition(go.state.clone()));
+                            continue;
+                        }
+                    };
+                    let func = func.clone();
+                    let sender = self.sender();
+                    tokio::task::spawn_blocking(move || {
+                        let m = func(&mut board, &go);
+                        sender.play(go.game_id, m)
+                    });
+                }
+                Event::Error(msg) => log::warn!("{}", ClientError::Server(msg)),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn go_request_parse() {
+        assert_eq!(
+            GoRequest::parse("1, chess, 99000, 500, 8/8/4k3/8/8/4K3/8/4R3 w - - 0 1"),
+            Some(GoRequest {
+                game_id: 1,
+                game_type: "chess".to_string(),
+                time_remaining: 99000,
+                time_for_move: 500,
+                state: "8/8/4k3/8/8/4K3/8/4R3 w - - 0 1".to_string(),
+                draw_offer: false,
+            })
+        );
+        assert_eq!(
+            GoRequest::parse("2, chess960, 1000, 0, 8/8/4k3/8/8/4K3/8/4R3 b - - 0 1, draw_offer")
+                .map(|go| (go.state, go.draw_offer)),
+            Some(("8/8/4k3/8/8/4K3/8/4R3 b - - 0 1".to_string(), true))
+        );
+        assert_eq!(GoRequest::parse("x, chess, 1000, 0, -"), None);
+        assert_eq!(GoRequest::parse("1, chess, 1000"), None);
+    }
+
+    #[test]
+    fn event_parse() {
+        assert_eq!(Event::parse("okay"), Event::Okay);
+        assert_eq!(
+            Event::parse("error no such game"),
+            Event::Error("no such game".to_string())
+        );
+        assert!(matches!(
+            Event::parse("go 3, chess, 1000, 0, 8/8/4k3/8/8/4K3/8/4R3 w - - 0 1"),
+            Event::Go(GoRequest { game_id: 3, .. })
+        ));
+        assert_eq!(
+            Event::parse("go 3, chess"),
+            Event::Other("go 3, chess".to_string())
+        );
+        assert_eq!(
+            Event::parse("new_game 4"),
+            Event::Other("new_game 4".to_string())
+        );
+    }
+
+    #[test]
+    fn event_parse_game() {
+        assert_eq!(
+            Event::parse(
+                "game 2, chess, 1, true, true, 1, 100000, 0, 0, [[1, 1, 99500, 1662], [2, 0, 98000, 1338]], -, \
+                 __ENDED_GAME, 1, adjudication, chess, 8/8/4k3/8/8/4K3/8/4R3 b - - 0 1,[]"
+            ),
+            Event::Game(GameInfo {
+                id: 2,
+                game_type: "chess".to_string(),
+                owner: 1,
+                started: true,
+                finished: true,
+                result: Some(GameResult::Win(1)),
+                time_ms: 100000,
+                time_per_move_ms: 0,
+                current_player_time_for_move: Some(0),
+                players: vec![
+                    GamePlayer {
+                        user_id: 1,
+                        score: 1.0,
+                        time_ms: 99500,
+                        rating: 1662,
+                    },
+                    GamePlayer {
+                        user_id: 2,
+                        score: 0.0,
+                        time_ms: 98000,
+                        rating: 1338,
+                    },
+                ],
+                draw_offer: None,
+                state: Some(
+                    "__ENDED_GAME, 1, adjudication, chess, 8/8/4k3/8/8/4K3/8/4R3 b - - 0 1,[]".to_string()
+                ),
+            })
+        );
+        match Event::parse("game 5, chess960, 3, false, false, -, 500, 200, -, [], -, -") {
+            Event::Game(game) => {
+                assert_eq!(game.result, None);
+                assert_eq!(game.current_player_time_for_move, None);
+                assert_eq!(game.players, vec![]);
+                assert_eq!(game.draw_offer, None);
+                assert_eq!(game.state, None);
+            }
+            event => panic!("expected a game, got {:?}", event),
+        }
+        match Event::parse(
+            "game 6, chess, 3, true, false, tie, 500, 200, 100, [[3, 0.5, 400, 1500]], 3, -",
+        ) {
+            Event::Game(game) => {
+                assert_eq!(game.result, Some(GameResult::Tie));
+                assert_eq!(game.players[0].score, 0.5);
+                assert_eq!(game.draw_offer, Some(3));
+            }
+            event => panic!("expected a game, got {:?}", event),
+        }
+        assert_eq!(
+            Event::parse("game 7, chess, 1, true"),
+            Event::Other("game 7, chess, 1, true".to_string())
+        );
+    }
+}
@@ -0,0 +1,89 @@
+//! A baseline chess engine, enabled with the `engine` feature
+//!
+//! [`Searcher`] runs an iterative deepening alpha-beta search with quiescence search and a
+//! [`TranspositionTable`], stopping when its [`TimeManager`] says so. Position evaluation is
+//! pluggable through the [`Evaluator`] trait, and move ordering through [`MoveOrderer`], so bots can
+//! share the search and only differ in what they know about chess.
+//!
+//! ```no_run
+//! use chess::engine::{MaterialEvaluator, Searcher, TimeManager};
+//!
+//! chess::init();
+//! let mut board = chess::Board::new("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+//! let mut searcher = Searcher::new(MaterialEvaluator);
+//! let result = searcher.search(&mut board, &TimeManager::new(60000, 1000), 64);
+//! println!("best move: {:?}, score: {}", result.best_move, result.score);
+//! ```
+use crate::{Bitboard, Board, Move, PieceType, Player};
+
+mod order;
+mod search;
+mod time;
+mod tt;
+
+pub use order::{DefaultOrderer, MoveOrderer};
+pub use search::{SearchResult, Searcher};
+pub use time::TimeManager;
+pub use tt::{Bound, TranspositionTable, TtEntry};
+
+/// Score of a checkmate (in centipawns). A player that will be mated in n plies scores `-(MATE - n)`.
+pub const MATE: i32 = 30000;
+/// Scores above this (or below its negation) are mate scores
+pub const MATE_BOUND: i32 = MATE - 1000;
+/// A score larger than any evaluation
+pub const INFINITY: i32 = MATE + 1;
+
+/// Evaluates positions for the search
+pub trait Evaluator {
+    /// Score a position in centipawns from the perspective of the player to move (positive is good for that
+    /// player). Scores must be smaller in magnitude than [`MATE_BOUND`].
+    fn evaluate(&self, board: &Board) -> i32;
+}
+
+/// Value of each piece type in centipawns, indexed by `PieceType as usize`
+pub const PIECE_VALUES: [i32; 6] = [0, 100, 300, 500, 300, 900];
+
+// the four center squares
+const CENTER: u64 = 0x1818000000;
+// bonus for each piece on a center square
+const CENTER_BONUS: i32 = 20;
+
+/// Evaluates positions by material and control of the center, like the sample programs
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MaterialEvaluator;
+
+impl MaterialEvaluator {
+    // score a position from white's perspective
+    fn evaluate_white(board: &Board) -> i32 {
+        let mut score = 0;
+        for piece in [
+            PieceType::Pawn,
+            PieceType::Knight,
+            PieceType::Bishop,
+            PieceType::Rook,
+            PieceType::Queen,
+        ]
+        .iter()
+        {
+            let count = |player: Player| board.piece_bb(player, *piece).count() as i32;
+            score += PIECE_VALUES[*piece as usize] * (count(Player::White) - count(Player::Black));
+        }
+        let center =
+            |player: Player| (board.player_bb(player) & Bitboard::new(CENTER)).count() as i32;
+        score + CENTER_BONUS * (center(Player::White) - center(Player::Black))
+    }
+}
+
+impl Evaluator for MaterialEvaluator {
+    fn evaluate(&self, board: &Board) -> i32 {
+        match board.player_to_move() {
+            Player::White => MaterialEvaluator::evaluate_white(board),
+            Player::Black => -MaterialEvaluator::evaluate_white(board),
+        }
+    }
+}
+
+/// Check if a move is a capture or promotion (the moves searched in quiescence search)
+pub fn is_tactical(m: Move) -> bool {
+    m.capture_square().is_some() || m.promote().is_some()
+}
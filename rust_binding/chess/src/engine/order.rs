@@ -0,0 +1,110 @@
+use super::PIECE_VALUES;
+use crate::{Board, Move};
+use std::cmp::Reverse;
+
+// number of killer moves remembered for each ply
+const KILLERS_PER_PLY: usize = 2;
+
+// ordering scores for each kind of move. Captures are ordered among themselves by MVV-LVA
+const TT_MOVE_SCORE: i32 = 1_000_000;
+const CAPTURE_SCORE: i32 = 100_000;
+const PROMOTION_SCORE: i32 = 90_000;
+const KILLER_SCORE: i32 = 80_000;
+
+/// Decides which order moves are searched in. Good ordering (searching the best move first) lets alpha-beta
+/// prune more of the tree.
+pub trait MoveOrderer {
+    /// Sort moves from most to least promising. `tt_move` is the best move stored in the transposition table
+    /// for the position (if any), and `ply` is the distance from the root of the search.
+    fn order(&mut self, board: &Board, moves: &mut Vec<Move>, tt_move: Option<Move>, ply: usize);
+
+    /// Called when a move causes a beta cutoff, so the orderer can try it earlier in similar positions
+    fn cutoff(&mut self, _board: &Board, _m: Move, _depth: u32, _ply: usize) {}
+
+    /// Called before each new search
+    fn clear(&mut self) {}
+}
+
+/// Orders the transposition table move first, then captures (most valuable victim, least valuable attacker
+/// first), promotions, killer moves (quiet moves that caused cutoffs at the same ply), and other moves by how
+/// often they have caused cutoffs.
+#[derive(Clone, Debug, Default)]
+pub struct DefaultOrderer {
+    killers: Vec<[Option<Move>; KILLERS_PER_PLY]>,
+    // cutoff scores for quiet moves, indexed by source and destination square
+    history: Vec<i32>,
+}
+
+impl DefaultOrderer {
+    pub fn new() -> DefaultOrderer {
+        DefaultOrderer {
+            killers: Vec::new(),
+            history: vec![0; 64 * 64],
+        }
+    }
+
+    fn history_index(m: Move) -> usize {
+        m.src().0 as usize * 64 + m.dst().0 as usize
+    }
+
+    fn score(&self, board: &Board, m: Move, tt_move: Option<Move>, ply: usize) -> i32 {
+        if Some(m) == tt_move {
+            return TT_MOVE_SCORE;
+        }
+        if let Some(victim) = m.capture_piece().filter(|_| m.capture_square().is_some()) {
+            let attacker = board
+                .piece_on_square(m.src())
+                .map_or(0, |p| PIECE_VALUES[p as usize]);
+            return CAPTURE_SCORE + 10 * PIECE_VALUES[victim as usize] - attacker / 10;
+        }
+        if m.promote().is_some() {
+            return PROMOTION_SCORE;
+        }
+        if let Some(killers) = self.killers.get(ply) {
+            if killers.contains(&Some(m)) {
+                return KILLER_SCORE;
+            }
+        }
+        self.history
+            .get(DefaultOrderer::history_index(m))
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
+impl MoveOrderer for DefaultOrderer {
+    fn order(&mut self, board: &Board, moves: &mut Vec<Move>, tt_move: Option<Move>, ply: usize) {
+        let mut scored = moves
+            .iter()
+            .map(|m| (self.score(board, *m, tt_move, ply), *m))
+            .collect::<Vec<(i32, Move)>>();
+        scored.sort_by_key(|(score, _)| Reverse(*score));
+        moves.clear();
+        moves.extend(scored.into_iter().map(|(_, m)| m));
+    }
+
+    fn cutoff(&mut self, _board: &Board, m: Move, depth: u32, ply: usize) {
+        if m.capture_square().is_some() || m.promote().is_some() {
+            return;
+        }
+        if self.killers.len() <= ply {
+            self.killers.resize(ply + 1, [None; KILLERS_PER_PLY]);
+        }
+        let killers = &mut self.killers[ply];
+        if killers[0] != Some(m) {
+            killers[1] = killers[0];
+            killers[0] = Some(m);
+        }
+        if self.history.is_empty() {
+            self.history = vec![0; 64 * 64];
+        }
+        let entry = &mut self.history[DefaultOrderer::history_index(m)];
+        // keep history scores below the killer move score
+        *entry = (*entry + (depth * depth) as i32).min(KILLER_SCORE - 1);
+    }
+
+    fn clear(&mut self) {
+        self.killers.clear();
+        self.history = vec![0; 64 * 64];
+    }
+}
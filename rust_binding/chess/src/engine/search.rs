@@ -0,0 +1,434 @@
+use super::{
+    is_tactical, Bound, DefaultOrderer, Evaluator, MoveOrderer, TimeManager, TranspositionTable,
+    INFINITY, MATE, MATE_BOUND,
+};
+use crate::{Board, Move};
+
+// default number of transposition table entries
+const DEFAULT_TT_ENTRIES: usize = 1 << 20;
+// how many nodes are searched between checks of the time manager (a power of two)
+const TIME_CHECK_NODES: u64 = 2048;
+
+/// The result of a search
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchResult {
+    /// The best move found, or None if there are no legal moves
+    pub best_move: Option<Move>,
+    /// Score of the best move in centipawns, from the perspective of the player to move
+    pub score: i32,
+    /// Depth of the last completed iteration
+    pub depth: u32,
+    /// Number of positions searched
+    pub nodes: u64,
+    /// The expected line of play, starting with the best move
+    pub pv: Vec<Move>,
+}
+
+impl SearchResult {
+    /// If the score is a forced mate, get the number of moves until mate (negative if the player to move is
+    /// getting mated)
+    pub fn mate_in(&self) -> Option<i32> {
+        if self.score > MATE_BOUND {
+            Some((MATE - self.score + 1) / 2)
+        } else if self.score < -MATE_BOUND {
+            Some(-(MATE + self.score) / 2)
+        } else {
+            None
+        }
+    }
+}
+
+/// Iterative deepening alpha-beta search with quiescence search and a transposition table
+pub struct Searcher<E: Evaluator, O: MoveOrderer = DefaultOrderer> {
+    evaluator: E,
+    orderer: O,
+    tt: TranspositionTable,
+    // hashes of the positions played before the root, and of the positions on the current search path
+    history: Vec<u64>,
+    nodes: u64,
+    stopped: bool,
+}
+
+impl<E: Evaluator> Searcher<E, DefaultOrderer> {
+    /// Create a searcher using the default move ordering
+    pub fn new(evaluator: E) -> Searcher<E, DefaultOrderer> {
+        Searcher::with_orderer(evaluator, DefaultOrderer::new())
+    }
+}
+
+impl<E: Evaluator, O: MoveOrderer> Searcher<E, O> {
+    /// Create a searcher with a custom move orderer
+    pub fn with_orderer(evaluator: E, orderer: O) -> Searcher<E, O> {
+        Searcher {
+            evaluator,
+            orderer,
+            tt: TranspositionTable::new(DEFAULT_TT_ENTRIES),
+            history: Vec::new(),
+            nodes: 0,
+            stopped: false,
+        }
+    }
+
+    /// Replace the transposition table with one with room for the given number of entries
+    pub fn set_table_size(&mut self, entries: usize) {
+        self.tt = TranspositionTable::new(entries);
+    }
+
+    /// Get the transposition table. It is kept between searches.
+    pub fn table(&mut self) -> &mut TranspositionTable {
+        &mut self.tt
+    }
+
+    /// Set the hashes of the positions that occurred in the game before the position being searched, so the
+    /// search can recognize repetitions
+    pub fn set_game_history(&mut self, hashes: &[u64]) {
+        self.history = hashes.to_vec();
+    }
+
+    /// Search a board until the time manager runs out or max_depth is reached, and return the best move found.
+    /// The board is left unchanged.
+    pub fn search(
+        &mut self,
+        board: &mut Board,
+        time: &TimeManager,
+        max_depth: u32,
+    ) -> SearchResult {
+        self.search_with_info(board, time, max_depth, |_| {})
+    }
+
+    /// Search a board like [`Searcher::search`], calling on_iteration with the result of each completed iteration
+    pub fn search_with_info<F>(
+        &mut self,
+        board: &mut Board,
+        time: &TimeManager,
+        max_depth: u32,
+        mut on_iteration: F,
+    ) -> SearchResult
+    where
+        F: FnMut(&SearchResult),
+    {
+        self.nodes = 0;
+        self.stopped = false;
+        self.orderer.clear();
+        let history_len = self.history.len();
+
+        let moves = board.legal_moves();
+        let mut result = SearchResult {
+            best_move: moves.first().copied(),
+            score: 0,
+            depth: 0,
+            nodes: 0,
+            pv: Vec::new(),
+        };
+        if moves.is_empty() {
+            result.score = if board.in_check(board.player_to_move()) {
+                -MATE
+            } else {
+                0
+            };
+            return result;
+        }
+
+        for depth in 1..=max_depth.max(1) {
+            if depth > 1 && !time.can_start_iteration() {
+                break;
+            }
+            let score = self.alpha_beta(board, time, depth, 0, -INFINITY, INFINITY);
+            self.history.truncate(history_len);
+            if self.stopped {
+                break;
+            }
+            let pv = self.principal_variation(board, depth);
+            result = SearchResult {
+                best_move: pv.first().copied().or(result.best_move),
+                score,
+                depth,
+                nodes: self.nodes,
+                pv,
+            };
+            on_iteration(&result);
+            // no need to search deeper once a forced mate is found
+            if score.abs() > MATE_BOUND {
+                break;
+            }
+        }
+        result.nodes = self.nodes;
+        result
+    }
+
+    // check if the position has occurred before in the game or on the search path
+    fn is_repetition(&self, hash: u64) -> bool {
+        self.history.contains(&hash)
+    }
+
+    // count a node, and check if the search is out of time
+    fn visit_node(&mut self, time: &TimeManager) {
+        self.nodes += 1;
+        if self.nodes & (TIME_CHECK_NODES - 1) == 0 && time.must_stop() {
+            self.stopped = true;
+        }
+    }
+
+    fn alpha_beta(
+        &mut self,
+        board: &mut Board,
+        time: &TimeManager,
+        depth: u32,
+        ply: usize,
+        mut alpha: i32,
+        beta: i32,
+    ) -> i32 {
+        self.visit_node(time);
+        if self.stopped {
+            return 0;
+        }
+        let hash = board.hash();
+        if ply > 0 && self.is_repetition(hash) {
+            return 0;
+        }
+
+        let in_check = board.in_check(board.player_to_move());
+        // extend the search when in check, so mates at the horizon aren't missed
+        let depth = if in_check { depth + 1 } else { depth };
+        if depth == 0 {
+            return self.quiescence(board, time, ply, alpha, beta);
+        }
+
+        let tt_entry = self.tt.get(hash, ply);
+        if let Some(entry) = tt_entry {
+            if ply > 0 && entry.depth >= depth {
+                let cutoff = match entry.bound {
+                    Bound::Exact => true,
+                    Bound::Lower => entry.score >= beta,
+                    Bound::Upper => entry.score <= alpha,
+                };
+                if cutoff {
+                    return entry.score;
+                }
+            }
+        }
+
+        let mut moves = board.legal_moves();
+        if moves.is_empty() {
+            return if in_check { -MATE + ply as i32 } else { 0 };
+        }
+        self.orderer.order(
+            board,
+            &mut moves,
+            tt_entry.and_then(|entry| entry.best_move),
+            ply,
+        );
+
+        let original_alpha = alpha;
+        let mut best_score = -INFINITY;
+        let mut best_move = None;
+        self.history.push(hash);
+        for m in moves {
+            board.make_move(m);
+            let score = -self.alpha_beta(board, time, depth - 1, ply + 1, -beta, -alpha);
+            board.unmake_move(m);
+            if self.stopped {
+                self.history.pop();
+                return 0;
+            }
+            if score > best_score {
+                best_score = score;
+                best_move = Some(m);
+            }
+            if score > alpha {
+                alpha = score;
+            }
+            if alpha >= beta {
+                self.orderer.cutoff(board, m, depth, ply);
+                break;
+            }
+        }
+        self.history.pop();
+
+        let bound = if best_score >= beta {
+            Bound::Lower
+        } else if best_score > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        self.tt
+            .store(hash, depth, best_score, bound, best_move, ply);
+        best_score
+    }
+
+    // search captures and promotions until the position is quiet, so the evaluation isn't taken in the middle of
+    // an exchange
+    fn quiescence(
+        &mut self,
+        board: &mut Board,
+        time: &TimeManager,
+        ply: usize,
+        mut alpha: i32,
+        beta: i32,
+    ) -> i32 {
+        self.visit_node(time);
+        if self.stopped {
+            return 0;
+        }
+
+        let in_check = board.in_check(board.player_to_move());
+        let mut moves = board.legal_moves();
+        if moves.is_empty() {
+            return if in_check { -MATE + ply as i32 } else { 0 };
+        }
+
+        // when not in check, the player to move can choose not to capture (stand pat)
+        let mut best_score = -INFINITY;
+        if !in_check {
+            best_score = self.evaluator.evaluate(board);
+            if best_score >= beta {
+                return best_score;
+            }
+            alpha = alpha.max(best_score);
+            moves.retain(|m| is_tactical(*m));
+        }
+        self.orderer.order(board, &mut moves, None, ply);
+
+        for m in moves {
+            board.make_move(m);
+            let score = -self.quiescence(board, time, ply + 1, -beta, -alpha);
+            board.unmake_move(m);
+            if self.stopped {
+                return 0;
+            }
+            if score > best_score {
+                best_score = score;
+            }
+            if score > alpha {
+                alpha = score;
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+        best_score
+    }
+
+    // follow best moves through the transposition table to get the expected line of play
+    fn principal_variation(&self, board: &mut Board, max_len: u32) -> Vec<Move> {
+        let mut pv = Vec::new();
+        let mut seen = Vec::new();
+        while pv.len() < max_len as usize {
+            let hash = board.hash();
+            if seen.contains(&hash) {
+                break;
+            }
+            seen.push(hash);
+            let m = match self.tt.get(hash, 0).and_then(|entry| entry.best_move) {
+                Some(m) if board.is_legal(&m) => m,
+                _ => break,
+            };
+            board.make_move(m);
+            pv.push(m);
+        }
+        for m in pv.iter().rev() {
+            board.unmake_move(*m);
+        }
+        pv
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::engine::MaterialEvaluator;
+    use std::sync::Once;
+
+    static INIT: Once = Once::new();
+
+    fn board(fen: &str) -> Board {
+        INIT.call_once(crate::init);
+        Board::new(fen)
+    }
+
+    fn search(board: &mut Board, depth: u32) -> SearchResult {
+        Searcher::new(MaterialEvaluator).search(board, &TimeManager::unlimited(), depth)
+    }
+
+    #[test]
+    fn finds_mate_in_one() {
+        let mut board = board("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        let result = search(&mut board, 4);
+        assert_eq!(format!("{}", result.best_move.unwrap()), "a1a8");
+        assert_eq!(result.score, MATE - 1);
+        assert_eq!(result.mate_in(), Some(1));
+    }
+
+    #[test]
+    fn finds_mate_in_two() {
+        // Rb7, then Ra8 mates wherever the king goes
+        let mut attacker = board("7k/8/8/8/8/8/R7/1R4K1 w - - 0 1");
+        let result = search(&mut attacker, 6);
+        assert_eq!(result.score, MATE - 3);
+        assert_eq!(result.mate_in(), Some(2));
+        assert_eq!(result.pv.len(), 3);
+
+        // the same line, seen by the player getting mated
+        let mut defender = board("7k/1R6/8/8/8/8/R7/6K1 b - - 0 1");
+        let result = search(&mut defender, 6);
+        assert_eq!(result.score, -(MATE - 2));
+        assert_eq!(result.mate_in(), Some(-1));
+    }
+
+    #[test]
+    fn mated_and_stalemated_positions() {
+        let mut mated = board("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1");
+        let result = search(&mut mated, 4);
+        assert_eq!(result.best_move, None);
+        assert_eq!(result.score, -MATE);
+        assert_eq!(result.mate_in(), Some(0));
+
+        let mut stalemated = board("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1");
+        let result = search(&mut stalemated, 4);
+        assert_eq!(result.best_move, None);
+        assert_eq!(result.score, 0);
+        assert_eq!(result.mate_in(), None);
+    }
+
+    #[test]
+    fn repetitions_are_draws() {
+        let fen = "7k/8/8/8/8/8/8/KQ6 w - - 0 1";
+        let mut board = board(fen);
+        assert!(search(&mut board, 3).score > 800);
+
+        // if every move repeats a position from the game, the best white can do is a draw
+        let mut history = Vec::new();
+        for m in board.legal_moves() {
+            board.make_move(m);
+            history.push(board.hash());
+            board.unmake_move(m);
+        }
+        let mut searcher = Searcher::new(MaterialEvaluator);
+        searcher.set_game_history(&history);
+        let result = searcher.search(&mut board, &TimeManager::unlimited(), 3);
+        assert_eq!(result.score, 0);
+        // the board and the game history are left unchanged
+        assert_eq!(board.hash(), Board::new(fen).hash());
+        assert_eq!(searcher.history, history);
+    }
+
+    #[test]
+    fn mate_in() {
+        let result = |score| SearchResult {
+            best_move: None,
+            score,
+            depth: 1,
+            nodes: 0,
+            pv: Vec::new(),
+        };
+        assert_eq!(result(MATE - 1).mate_in(), Some(1));
+        assert_eq!(result(MATE - 2).mate_in(), Some(1));
+        assert_eq!(result(MATE - 5).mate_in(), Some(3));
+        assert_eq!(result(-(MATE - 2)).mate_in(), Some(-1));
+        assert_eq!(result(-(MATE - 4)).mate_in(), Some(-2));
+        assert_eq!(result(MATE_BOUND).mate_in(), None);
+        assert_eq!(result(-MATE_BOUND).mate_in(), None);
+        assert_eq!(result(150).mate_in(), None);
+    }
+}
@@ -0,0 +1,139 @@
+#[cfg(feature = "client")]
+use crate::client::GoRequest;
+use std::time::{Duration, Instant};
+
+// number of moves the remaining time is assumed to be spread over
+const MOVES_TO_GO: u64 = 30;
+// time kept in reserve for network latency
+const SAFETY_MARGIN_MS: u64 = 50;
+
+/// Decides how long a search may run for.
+///
+/// The time manager has a soft limit, after which no new iteration of the search is started, and a hard
+/// limit, at which the search is stopped immediately.
+#[derive(Clone, Copy, Debug)]
+pub struct TimeManager {
+    start: Instant,
+    soft: Option<Duration>,
+    hard: Option<Duration>,
+}
+
+impl TimeManager {
+    /// Budget time for a move given the time left for the game and the extra time given for this move (both in
+    /// ms, as sent in the `go` command)
+    pub fn new(time_remaining: i64, time_for_move: i64) -> TimeManager {
+        let remaining = time_remaining.max(0) as u64;
+        let for_move = time_for_move.max(0) as u64;
+        // never plan to use more than the time for this move plus a quarter of the time left
+        let max = (remaining / 4 + for_move).saturating_sub(SAFETY_MARGIN_MS);
+        let soft = (remaining / MOVES_TO_GO + for_move * 3 / 4).min(max);
+        let hard = (soft * 3).min(max);
+        TimeManager {
+            start: Instant::now(),
+            soft: Some(Duration::from_millis(soft)),
+            hard: Some(Duration::from_millis(hard)),
+        }
+    }
+
+    /// Budget time for a move requested by the server
+    #[cfg(feature = "client")]
+    pub fn from_go(go: &GoRequest) -> TimeManager {
+        TimeManager::new(go.time_remaining, go.time_for_move)
+    }
+
+    /// Search for exactly the given time
+    pub fn fixed(time: Duration) -> TimeManager {
+        TimeManager {
+            start: Instant::now(),
+            soft: Some(time),
+            hard: Some(time),
+        }
+    }
+
+    /// Never stop the search because of time (it is limited only by depth)
+    pub fn unlimited() -> TimeManager {
+        TimeManager {
+            start: Instant::now(),
+            soft: None,
+            hard: None,
+        }
+    }
+
+    /// Get the time since the time manager was created
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Check if there is time to start another iteration of the search
+    pub fn can_start_iteration(&self) -> bool {
+        match self.soft {
+            Some(soft) => self.elapsed() < soft,
+            None => true,
+        }
+    }
+
+    /// Check if the search must stop now
+    pub fn must_stop(&self) -> bool {
+        match self.hard {
+            Some(hard) => self.elapsed() >= hard,
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn limits(time: TimeManager) -> (u128, u128) {
+        (
+            time.soft.unwrap().as_millis(),
+            time.hard.unwrap().as_millis(),
+        )
+    }
+
+    #[test]
+    fn budgets_from_remaining_time() {
+        // a 30th of the time left, and at most 3 times that
+        assert_eq!(limits(TimeManager::new(60000, 0)), (2000, 6000));
+        // most of the time for the move is used
+        assert_eq!(limits(TimeManager::new(60000, 1000)), (2750, 8250));
+    }
+
+    #[test]
+    fn never_uses_too_much_time() {
+        // the hard limit is at most a quarter of the time left plus the time for the move, less the safety margin
+        assert_eq!(limits(TimeManager::new(1000, 0)), (33, 99));
+        assert_eq!(limits(TimeManager::new(2000, 0)), (66, 198));
+        assert_eq!(limits(TimeManager::new(0, 1000)), (750, 950));
+        assert_eq!(limits(TimeManager::new(100, 100)), (75, 75));
+        for (remaining, for_move) in [(200, 0), (0, 40), (-500, 0), (-500, -500)].iter() {
+            assert_eq!(limits(TimeManager::new(*remaining, *for_move)), (0, 0));
+        }
+    }
+
+    #[test]
+    fn fixed_and_unlimited() {
+        let time = TimeManager::fixed(Duration::from_millis(0));
+        assert!(!time.can_start_iteration());
+        assert!(time.must_stop());
+
+        let time = TimeManager::unlimited();
+        assert!(time.can_start_iteration());
+        assert!(!time.must_stop());
+    }
+
+    #[cfg(feature = "client")]
+    #[test]
+    fn from_go() {
+        let go = GoRequest {
+            game_id: 1,
+            game_type: "chess".to_string(),
+            time_remaining: 60000,
+            time_for_move: 1000,
+            state: "-".to_string(),
+            draw_offer: false,
+        };
+        assert_eq!(limits(TimeManager::from_go(&go)), (2750, 8250));
+    }
+}
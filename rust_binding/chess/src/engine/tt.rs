@@ -0,0 +1,162 @@
+use super::MATE_BOUND;
+use crate::Move;
+
+/// How a stored score relates to the position's true score
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bound {
+    /// The score is exact
+    Exact,
+    /// The true score is at least the stored score (the search failed high)
+    Lower,
+    /// The true score is at most the stored score (the search failed low)
+    Upper,
+}
+
+/// A search result stored in the transposition table
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TtEntry {
+    /// Hash of the position
+    pub key: u64,
+    /// Depth the position was searched to
+    pub depth: u32,
+    /// Score of the position. Mate scores are stored relative to the position, not the root of the search
+    pub score: i32,
+    pub bound: Bound,
+    /// Best move found in the position
+    pub best_move: Option<Move>,
+}
+
+/// A fixed size hash table of search results, indexed by [`crate::Board::hash`]
+#[derive(Clone, Debug)]
+pub struct TranspositionTable {
+    entries: Vec<Option<TtEntry>>,
+}
+
+impl TranspositionTable {
+    /// Create a table with room for at least `entries` entries (rounded up to a power of two)
+    pub fn new(entries: usize) -> TranspositionTable {
+        TranspositionTable {
+            entries: vec![None; entries.max(1).next_power_of_two()],
+        }
+    }
+
+    fn index(&self, key: u64) -> usize {
+        (key as usize) & (self.entries.len() - 1)
+    }
+
+    /// Look up the entry for a position, adjusting mate scores to be relative to the root
+    pub fn get(&self, key: u64, ply: usize) -> Option<TtEntry> {
+        self.entries[self.index(key)]
+            .filter(|entry| entry.key == key)
+            .map(|entry| TtEntry {
+                score: score_from_tt(entry.score, ply),
+                ..entry
+            })
+    }
+
+    /// Store the result of searching a position. Entries from deeper searches of the same position are kept.
+    pub fn store(
+        &mut self,
+        key: u64,
+        depth: u32,
+        score: i32,
+        bound: Bound,
+        best_move: Option<Move>,
+        ply: usize,
+    ) {
+        let index = self.index(key);
+        if let Some(existing) = self.entries[index] {
+            if existing.key == key && existing.depth > depth && bound != Bound::Exact {
+                return;
+            }
+        }
+        self.entries[index] = Some(TtEntry {
+            key,
+            depth,
+            score: score_to_tt(score, ply),
+            bound,
+            best_move,
+        });
+    }
+
+    /// Remove all entries
+    pub fn clear(&mut self) {
+        for entry in self.entries.iter_mut() {
+            *entry = None;
+        }
+    }
+}
+
+// mate scores are stored as distance to mate from the stored position, and read back as distance from the root
+fn score_to_tt(score: i32, ply: usize) -> i32 {
+    if score > MATE_BOUND {
+        score + ply as i32
+    } else if score < -MATE_BOUND {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: i32, ply: usize) -> i32 {
+    if score > MATE_BOUND {
+        score - ply as i32
+    } else if score < -MATE_BOUND {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::engine::MATE;
+
+    #[test]
+    fn store_and_get() {
+        let mut tt = TranspositionTable::new(1000);
+        assert_eq!(tt.get(5, 0), None);
+        tt.store(5, 3, 120, Bound::Exact, None, 2);
+        let entry = tt.get(5, 4).unwrap();
+        assert_eq!((entry.key, entry.depth, entry.score), (5, 3, 120));
+        assert_eq!(entry.bound, Bound::Exact);
+        // keys that share an index don't match
+        assert_eq!(tt.get(5 + 1024, 0), None);
+        tt.clear();
+        assert_eq!(tt.get(5, 0), None);
+    }
+
+    #[test]
+    fn mate_scores_are_relative_to_the_root() {
+        let mut tt = TranspositionTable::new(16);
+        // mate in 5 plies from the root, found 3 plies into the search (so 2 plies from the stored position)
+        tt.store(1, 4, MATE - 5, Bound::Exact, None, 3);
+        assert_eq!(tt.entries[1].unwrap().score, MATE - 2);
+        assert_eq!(tt.get(1, 3).unwrap().score, MATE - 5);
+        // reaching the same position sooner gives a sooner mate
+        assert_eq!(tt.get(1, 1).unwrap().score, MATE - 3);
+
+        tt.store(2, 4, -(MATE - 6), Bound::Upper, None, 4);
+        assert_eq!(tt.entries[2].unwrap().score, -(MATE - 2));
+        assert_eq!(tt.get(2, 7).unwrap().score, -(MATE - 9));
+
+        // other scores aren't adjusted
+        tt.store(3, 4, MATE_BOUND, Bound::Lower, None, 4);
+        assert_eq!(tt.get(3, 0).unwrap().score, MATE_BOUND);
+    }
+
+    #[test]
+    fn deeper_entries_are_kept() {
+        let mut tt = TranspositionTable::new(16);
+        tt.store(1, 6, 50, Bound::Lower, None, 0);
+        tt.store(1, 2, 80, Bound::Upper, None, 0);
+        assert_eq!(tt.get(1, 0).unwrap().depth, 6);
+        // exact scores always replace the entry
+        tt.store(1, 2, 80, Bound::Exact, None, 0);
+        assert_eq!(tt.get(1, 0).unwrap().depth, 2);
+        // as do entries for other positions
+        tt.store(17, 1, 0, Bound::Upper, None, 0);
+        assert_eq!(tt.get(1, 0), None);
+    }
+}
@@ -1,14 +1,23 @@
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
+use std::cmp;
 use std::collections::HashMap;
 use std::ffi;
 use std::fmt;
 use std::fmt::{Display, Debug};
 use std::ops::{BitAnd, BitOr, BitXor, Not, Shl, Shr};
-use tungstenite::{connect, Message};
+use std::thread;
+use std::time::Duration;
+use tungstenite::client::AutoStream;
+use tungstenite::{connect, Message, WebSocket};
 use url::Url;
 
 mod clib;
+pub mod book;
+#[cfg(feature = "client")]
+pub mod client;
+#[cfg(feature = "engine")]
+pub mod engine;
 
 /// A position on a chessboard
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
@@ -239,6 +248,95 @@ impl PieceType {
             panic!("int must be 0 - 5")
         }
     }
+
+    /// Get the letter used for the piece in standard algebraic notation (empty for pawns)
+    fn san_letter(self) -> &'static str {
+        match self {
+            PieceType::King => "K",
+            PieceType::Pawn => "",
+            PieceType::Knight => "N",
+            PieceType::Rook => "R",
+            PieceType::Bishop => "B",
+            PieceType::Queen => "Q",
+        }
+    }
+
+    /// Convert a piece letter in standard algebraic notation to a piece type
+    fn from_san_letter(letter: char) -> Option<PieceType> {
+        match letter {
+            'K' => Some(PieceType::King),
+            'N' => Some(PieceType::Knight),
+            'R' => Some(PieceType::Rook),
+            'B' => Some(PieceType::Bishop),
+            'Q' => Some(PieceType::Queen),
+            _ => None,
+        }
+    }
+}
+
+/// A move in standard algebraic notation, parsed without a board
+enum SanMove {
+    Castle {
+        kingside: bool,
+    },
+    Piece {
+        piece: PieceType,
+        dst: BoardPos,
+        promote: Option<PieceType>,
+        // source file and rank given to disambiguate the move
+        src_x: Option<i32>,
+        src_y: Option<i32>,
+    },
+}
+
+impl SanMove {
+    fn parse(san: &str) -> Option<SanMove> {
+        // check, mate, and annotation suffixes don't affect the move
+        let san = san.trim_end_matches(&['+', '#', '!', '?'][..]);
+        match san {
+            "O-O" | "0-0" => return Some(SanMove::Castle { kingside: true }),
+            "O-O-O" | "0-0-0" => return Some(SanMove::Castle { kingside: false }),
+            _ => {}
+        }
+
+        let mut chars = san.chars().filter(|c| *c != 'x').collect::<Vec<char>>();
+        // promotions are written e8=Q, or sometimes e8Q
+        let mut promote = None;
+        if chars.len() > 2 {
+            if let Some(piece) = PieceType::from_san_letter(chars[chars.len() - 1]) {
+                promote = Some(piece);
+                chars.pop();
+                if chars.last() == Some(&'=') {
+                    chars.pop();
+                }
+            }
+        }
+        let piece = match chars.first().and_then(|c| PieceType::from_san_letter(*c)) {
+            Some(piece) => {
+                chars.remove(0);
+                piece
+            }
+            None => PieceType::Pawn,
+        };
+        if chars.len() < 2 {
+            return None;
+        }
+        let (file, rank) = (chars[chars.len() - 2], chars[chars.len() - 1]);
+        if !('a'..='h').contains(&file) || !('1'..='8').contains(&rank) {
+            return None;
+        }
+        let dst = BoardPos::new(file as i32 - 'a' as i32, rank as i32 - '1' as i32);
+        // anything before the destination disambiguates the source square
+        let (mut src_x, mut src_y) = (None, None);
+        for c in &chars[..chars.len() - 2] {
+            match c {
+                'a'..='h' => src_x = Some(*c as i32 - 'a' as i32),
+                '1'..='8' => src_y = Some(*c as i32 - '1' as i32),
+                _ => return None,
+            }
+        }
+        Some(SanMove::Piece { piece, dst, promote, src_x, src_y })
+    }
 }
 
 /// A move that can be made on a [`Board`]
@@ -312,11 +410,102 @@ impl Move {
     pub fn is_legal(self, board: &mut Board) -> bool {
         unsafe { clib::move_is_legal(self.0, &mut board.0) != 0 }
     }
+
+    /// Construct a move from a string in standard algebraic notation (SAN), or return None if the string doesn't describe exactly one legal move on board
+    pub fn from_san(san: &str, board: &Board) -> Option<Move> {
+        let candidates = board.legal_moves();
+        match SanMove::parse(san)? {
+            // castles are stored as the king moving onto its rook
+            SanMove::Castle { kingside } => candidates
+                .into_iter()
+                .find(|m| m.castle() && (m.dst().x() > m.src().x()) == kingside),
+            SanMove::Piece { piece, dst, promote, src_x, src_y } => {
+                let mut matches = candidates.into_iter().filter(|m| {
+                    !m.castle()
+                        && m.dst() == dst
+                        && m.promote() == promote
+                        && board.piece_on_square(m.src()) == Some(piece)
+                        && src_x.iter().all(|x| m.src().x() == *x)
+                        && src_y.iter().all(|y| m.src().y() == *y)
+                });
+                match (matches.next(), matches.next()) {
+                    (Some(m), None) => Some(m),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    /// Check if a string is syntactically valid standard algebraic notation (SAN), whether or not it's legal on any board
+    pub fn san_is_wellformed(san: &str) -> bool {
+        SanMove::parse(san).is_some()
+    }
+
+    /// Convert a legal move on board to standard algebraic notation (SAN)
+    pub fn to_san(self, board: &Board) -> String {
+        let mut board = Board(board.0);
+        let mut san = if self.castle() {
+            // castles are stored as the king moving onto its rook
+            if self.dst().x() > self.src().x() {
+                "O-O".to_string()
+            } else {
+                "O-O-O".to_string()
+            }
+        } else {
+            let piece = board.piece_on_square(self.src()).unwrap();
+            let src = self.src().to_string();
+            let mut san = piece.san_letter().to_string();
+            if piece == PieceType::Pawn {
+                if self.capture_square().is_some() {
+                    san.push_str(&src[0..1]);
+                }
+            } else {
+                // disambiguate from other pieces of the same type that can move to the same square
+                let others = board
+                    .legal_moves()
+                    .into_iter()
+                    .filter(|m| {
+                        m.dst() == self.dst()
+                            && m.src() != self.src()
+                            && !m.castle()
+                            && board.piece_on_square(m.src()) == Some(piece)
+                    })
+                    .map(|m| m.src())
+                    .collect::<Vec<BoardPos>>();
+                if !others.is_empty() {
+                    if others.iter().all(|o| o.x() != self.src().x()) {
+                        san.push_str(&src[0..1]);
+                    } else if others.iter().all(|o| o.y() != self.src().y()) {
+                        san.push_str(&src[1..2]);
+                    } else {
+                        san.push_str(&src);
+                    }
+                }
+            }
+            if self.capture_square().is_some() {
+                san.push('x');
+            }
+            san.push_str(&self.dst().to_string());
+            if let Some(promote) = self.promote() {
+                san.push('=');
+                san.push_str(promote.san_letter());
+            }
+            san
+        };
+
+        board.make_move(self);
+        if board.is_checkmate() {
+            san.push('#');
+        } else if board.in_check(board.player_to_move()) {
+            san.push('+');
+        }
+        san
+    }
 }
 
 impl Display for Move {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut res: [i8; 5] = [0; 5];
+        let mut res: [i8; 6] = [0; 6];
         unsafe { clib::move_to_str(self.0, res.as_mut_ptr()) };
         let c_str = unsafe { ffi::CStr::from_ptr(res.as_ptr()) };
         write!(f, "{}", c_str.to_str().unwrap())
@@ -350,6 +539,16 @@ impl Board {
         board
     }
 
+    /// Create a board from FEN or EPD, or return None if it isn't a valid position
+    pub fn from_fen(fen: &str) -> Option<Board> {
+        let fen_cstr = ffi::CString::new(fen).ok()?;
+        if unsafe { clib::board_fen_str_is_valid(fen_cstr.as_ptr()) } != 0 {
+            Some(Board::new(fen))
+        } else {
+            None
+        }
+    }
+
     /// Get the piece type on the given square
     pub fn piece_on_square(&self, square: BoardPos) -> Option<PieceType> {
         FromPrimitive::from_i32(unsafe { clib::board_piece_on_square(&self.0, square.0) })
@@ -385,6 +584,28 @@ impl Board {
         (unsafe { clib::board_can_castle(&self.0, player as i32, side as i32) }) != 0
     }
 
+    /// Get the file of the rook player castles with on side, or None if player has lost castling rights on side
+    pub fn castle_rook_file(&self, player: Player, side: PieceType) -> Option<i32> {
+        if self.has_castling_rights(player, side) {
+            Some(unsafe { clib::board_castle_rook_file(&self.0, player as i32, side as i32) })
+        } else {
+            None
+        }
+    }
+
+    /// Check if the board uses chess960 castling rules and notation
+    pub fn is_chess960(&self) -> bool {
+        (unsafe { clib::board_is_chess960(&self.0) }) != 0
+    }
+
+    /// Get the zobrist hash of the board
+    ///
+    /// The hash uses the keys from the polyglot opening book format, and is updated incrementally
+    /// by [`Board::make_move`] and [`Board::unmake_move`].
+    pub fn hash(&self) -> u64 {
+        unsafe { clib::board_hash(&self.0) }
+    }
+
     /// Print the board on stdout
     pub fn print(&self) {
         unsafe { clib::board_print(&self.0) }
@@ -418,7 +639,7 @@ impl Board {
     }
 
     /// Get the flags stored in the board
-    pub fn flags(&self) -> u16 {
+    pub fn flags(&self) -> u32 {
         self.0.flags
     }
 
@@ -431,6 +652,33 @@ impl Board {
     pub fn is_checkmate(&self) -> bool {
         unsafe { clib::board_is_checkmate(&self.0) != 0 }
     }
+
+    /// Get all the legal moves for the player to move
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let mut board = Board(self.0);
+        let mut moves = Vec::new();
+        let mut gen = MoveGenerator::new(&mut board);
+        while let Some(m) = gen.next(&mut board) {
+            moves.push(m);
+        }
+        moves
+    }
+
+    /// Get all the pseudo-legal moves for the player to move. These are the legal moves, plus moves that leave the player in check.
+    pub fn pseudo_legal_moves(&self) -> Vec<Move> {
+        let mut board = Board(self.0);
+        let mut moves = Vec::new();
+        let mut gen = MoveGenerator::new(&mut board);
+        while let Some(m) = gen.next_pseudo_legal(&mut board) {
+            moves.push(m);
+        }
+        moves
+    }
+
+    /// Check if a move is legal for the player to move
+    pub fn is_legal(&self, m: &Move) -> bool {
+        self.legal_moves().contains(m)
+    }
 }
 
 impl Display for Board {
@@ -470,6 +718,18 @@ impl MoveGenerator {
         }
     }
 
+    /// Get the next pseudo-legal move (which may leave the player to move in check) out of the generator.
+    /// After generating pseudo-legal moves, is_checkmate and is_stalemate can't be used.
+    pub fn next_pseudo_legal(&mut self, board: &mut Board) -> Option<Move> {
+        assert_eq!((&mut board.0 as *mut clib::board), self.0.board);
+        let m = unsafe { clib::move_gen_next_pseudo_legal_move(&mut self.0) };
+        if m == clib::MOVE_END {
+            None
+        } else {
+            Some(Move(m))
+        }
+    }
+
     /// Get the next move out of the generator and apply it to board
     pub fn make_next(&mut self, board: &mut Board) -> Option<Move> {
         assert_eq!((&mut board.0 as *mut clib::board), self.0.board);
@@ -497,54 +757,347 @@ pub fn init() {
     unsafe { clib::move_gen_pregenerate() };
 }
 
-/// Connect to a codekata server at host and port, send apikey and name, and call func whenever a move is requested
-pub fn connect_to_server<F>(host: &str, port: &str, apikey: &str, name: &str, func: F)
+/// An error that ended the connection to the server
+#[derive(Debug)]
+pub enum ClientError {
+    /// The server's url couldn't be parsed
+    Url(url::ParseError),
+    /// The websocket connection failed
+    WebSocket(Box<tungstenite::Error>),
+    /// The connection to the server was closed
+    Closed,
+    /// The server sent a position that couldn't be loaded
+    InvalidPosition(String),
+    /// The server rejected a command, with its error message
+    Server(String),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Url(e) => write!(f, "invalid server url: {}", e),
+            ClientError::WebSocket(e) => write!(f, "websocket error: {}", e),
+            ClientError::Closed => write!(f, "connection to server closed"),
+            ClientError::InvalidPosition(state) => {
+                write!(f, "invalid position from server: {}", state)
+            }
+            ClientError::Server(msg) => write!(f, "error from server: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<url::ParseError> for ClientError {
+    fn from(e: url::ParseError) -> ClientError {
+        ClientError::Url(e)
+    }
+}
+
+impl From<tungstenite::Error> for ClientError {
+    fn from(e: tungstenite::Error) -> ClientError {
+        ClientError::WebSocket(Box::new(e))
+    }
+}
+
+// delay before reconnecting to the server, doubled after each failed connection attempt
+const RECONNECT_INITIAL_DELAY: Duration = Duration::from_millis(500);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(30);
+// number of failed connection attempts in a row before giving up
+const RECONNECT_MAX_ATTEMPTS: u32 = 20;
+
+/// Connect to a codekata server at host and port, send apikey and name, and call func whenever a move is requested.
+///
+/// If the connection drops, the client reconnects (backing off exponentially between failed attempts) and logs in
+/// again. The server resends any moves it is waiting for on login, so games in progress resume. The original
+/// protocol doesn't acknowledge a login, so an error from the server before the first position is taken as a rejected
+/// login. This only returns if the login is rejected, the server sends a position that can't be loaded, the server
+/// can't be reached after repeated attempts, or the url is invalid. Lost connections and errors reported by the
+/// server during play are logged with the `log` crate.
+pub fn connect_to_server<F>(host: &str, port: &str, apikey: &str, name: &str, func: F) -> Result<(), ClientError>
 where
     F: Fn(&mut Board) -> (Move, HashMap<String, String>),
 {
     init();
 
-    let url = Url::parse(&*format!("ws://{}:{}/", host, port)).unwrap();
+    let url = Url::parse(&format!("ws://{}:{}/", host, port))?;
+
+    let mut failed_attempts = 0;
+    let mut delay = RECONNECT_INITIAL_DELAY;
+    loop {
+        match connect(url.clone()) {
+            Ok((mut socket, _)) => {
+                // the server is reachable, so back off from the start again if the connection drops
+                failed_attempts = 0;
+                delay = RECONNECT_INITIAL_DELAY;
+                match play_on_socket(&mut socket, apikey, name, &func) {
+                    // reconnecting won't fix a rejected login or a position that can't be loaded
+                    Err(e @ ClientError::Server(_)) | Err(e @ ClientError::InvalidPosition(_)) => return Err(e),
+                    Err(e) => log::warn!("lost connection to server: {}", e),
+                    Ok(()) => {}
+                }
+            }
+            Err(e) => {
+                failed_attempts += 1;
+                if failed_attempts >= RECONNECT_MAX_ATTEMPTS {
+                    return Err(e.into());
+                }
+                log::warn!("can't connect to server: {}", e);
+            }
+        }
 
-    // connect to server
-    let (mut socket, _) = connect(url).expect("error connecting to server");
+        log::info!("reconnecting in {}ms", delay.as_millis());
+        thread::sleep(delay);
+        delay = cmp::min(delay * 2, RECONNECT_MAX_DELAY);
+    }
+}
 
+/// Log in on a connection to the server, and play moves on it until it fails. An error from the server before the
+/// first position is returned as a rejected login ([`ClientError::Server`]), and later errors are logged.
+fn play_on_socket<F>(socket: &mut WebSocket<AutoStream>, apikey: &str, name: &str, func: &F) -> Result<(), ClientError>
+where
+    F: Fn(&mut Board) -> (Move, HashMap<String, String>),
+{
     // send name and apikey
-    socket
-        .write_message(Message::Text(format!("apikey {}", apikey)))
-        .expect("error sending apikey command");
-    socket
-        .write_message(Message::Text(format!("name {}", name)))
-        .expect("error sending name command");
+    socket.write_message(Message::Text(format!("apikey {}", apikey)))?;
+    socket.write_message(Message::Text(format!("name {}", name)))?;
 
     // wait for position or error command
+    let mut got_position = false;
     loop {
-        let msg = socket
-            .read_message()
-            .expect("error reading msg from server");
-        if msg.is_text() {
-            let text = msg.into_text().unwrap();
-
-            if text.starts_with("position") {
-                let pos_str = &text[9..];
-                let mut board = Board::new(pos_str);
+        let msg = socket.read_message()?;
+        if let Message::Text(text) = msg {
+            if let Some(error) = text.strip_prefix("error ") {
+                if !got_position {
+                    return Err(ClientError::Server(error.to_string()));
+                }
+                log::warn!("error from server: {}", error);
+                continue;
+            }
+            if let Some(pos_str) = text.strip_prefix("position ") {
+                got_position = true;
+                let mut board = Board::from_fen(pos_str)
+                    .ok_or_else(|| ClientError::InvalidPosition(pos_str.to_string()))?;
 
                 // call func and send move and debug info
                 let (move_to_make, debug) = func(&mut board);
-                socket
-                    .write_message(Message::Text(format!("move {}", move_to_make)))
-                    .expect("error sending move command");
+                socket.write_message(Message::Text(format!("move {}", move_to_make)))?;
 
                 let mut debug_str = String::from("info ");
                 for (key, value) in &debug {
                     debug_str += &*format!("{} {}`", key, value);
                 }
-                socket
-                    .write_message(Message::Text(debug_str))
-                    .expect("error sending info command");
-            } else if text.starts_with("error") {
-                println!("error from server: {}", text);
+                socket.write_message(Message::Text(debug_str))?;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Once;
+
+    const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    static INIT: Once = Once::new();
+
+    fn board(fen: &str) -> Board {
+        INIT.call_once(crate::init);
+        Board::new(fen)
+    }
+
+    fn san_moves(fen: &str, moves: &[&str]) -> Vec<String> {
+        let mut board = board(fen);
+        moves
+            .iter()
+            .map(|move_str| {
+                let chess_move = Move::from_str(move_str, &board).unwrap();
+                assert!(chess_move.is_legal(&mut board));
+                let san = chess_move.to_san(&board);
+                board.make_move(chess_move);
+                san
+            })
+            .collect()
+    }
+
+    #[test]
+    fn to_san_test() {
+        assert_eq!(
+            san_moves(
+                START_FEN,
+                &["e2e4", "e7e5", "g1f3", "b8c6", "f1b5", "a7a6", "b5c6", "d7c6", "e1g1"]
+            ),
+            vec!["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Bxc6", "dxc6", "O-O"]
+        );
+        // fool's mate
+        assert_eq!(
+            san_moves(START_FEN, &["f2f3", "e7e5", "g2g4", "d8h4"]),
+            vec!["f3", "e5", "g4", "Qh4#"]
+        );
+        // disambiguation by file, rank, and square, promotion, and check
+        assert_eq!(
+            san_moves("2k5/8/8/8/8/8/4K3/R6R w - - 0 1", &["a1d1"]),
+            vec!["Rad1"]
+        );
+        assert_eq!(
+            san_moves("7k/8/8/R7/8/8/8/R3K3 w - - 0 1", &["a1a3"]),
+            vec!["R1a3"]
+        );
+        assert_eq!(
+            san_moves("7k/8/8/8/8/1Q3Q2/8/1Q2K3 w - - 0 1", &["b3d3"]),
+            vec!["Qb3d3"]
+        );
+        assert_eq!(
+            san_moves("8/4P3/8/8/8/8/k7/4K3 w - - 0 1", &["e7e8q"]),
+            vec!["e8=Q"]
+        );
+        assert_eq!(
+            san_moves("7k/8/8/8/8/8/8/R3K3 w Q - 0 1", &["e1c1", "h8g7", "c1b1"]),
+            vec!["O-O-O", "Kg7", "Kb1"]
+        );
+        assert_eq!(
+            san_moves("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1", &["a1a8"]),
+            vec!["Ra8+"]
+        );
+    }
+
+    #[test]
+    fn from_san_test() {
+        let from_san =
+            |fen: &str, san: &str| Move::from_san(san, &board(fen)).map(|m| m.to_string());
+        assert_eq!(from_san(START_FEN, "e4"), Some("e2e4".to_string()));
+        assert_eq!(from_san(START_FEN, "Nf3!?"), Some("g1f3".to_string()));
+        assert_eq!(from_san(START_FEN, "e5"), None);
+        assert_eq!(from_san(START_FEN, "Ke2"), None);
+        assert_eq!(from_san(START_FEN, "Nf"), None);
+        // ambiguous and disambiguated moves
+        let rooks = "2k5/8/8/8/8/8/4K3/R6R w - - 0 1";
+        assert_eq!(from_san(rooks, "Rd1"), None);
+        assert_eq!(from_san(rooks, "Rad1"), Some("a1d1".to_string()));
+        assert_eq!(from_san(rooks, "Rhxd1+"), Some("h1d1".to_string()));
+        let queens = "7k/8/8/8/8/1Q3Q2/8/1Q2K3 w - - 0 1";
+        assert_eq!(from_san(queens, "Qbd3"), None);
+        assert_eq!(from_san(queens, "Qb3d3"), Some("b3d3".to_string()));
+        // pawn captures, en passant, and promotions
+        assert_eq!(
+            from_san("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "exd6"),
+            Some("e5d6".to_string())
+        );
+        let promote = "3r4/4P3/8/8/8/8/k7/4K3 w - - 0 1";
+        assert_eq!(from_san(promote, "e8=Q"), Some("e7e8q".to_string()));
+        assert_eq!(from_san(promote, "exd8N"), Some("e7d8n".to_string()));
+        assert_eq!(from_san(promote, "e8"), None);
+        // castles
+        let castles = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        assert_eq!(from_san(castles, "O-O"), Some("e1g1".to_string()));
+        assert_eq!(from_san(castles, "0-0-0"), Some("e1c1".to_string()));
+        assert_eq!(
+            from_san("1r4kr/8/8/8/8/8/8/1R4KR w HBhb - 0 1", "O-O-O"),
+            Some("g1b1".to_string())
+        );
+    }
+
+    #[test]
+    fn legal_moves_test() {
+        // positions and move counts from the perft_tests directory
+        let positions = [
+            (START_FEN, 20),
+            ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", 48),
+            ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 14),
+            ("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1", 6),
+            ("r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1", 6),
+            ("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", 44),
+            ("r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10", 46),
+            ("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9", 21),
+            ("1nbbnrkr/p1p1ppp1/3p4/1p3P1p/3Pq2P/8/PPP1P1P1/QNBBNRKR w HFhf - 0 9", 28),
+        ];
+        for (fen, count) in positions.iter() {
+            let board = board(fen);
+            let legal = board.legal_moves();
+            assert_eq!(legal.len(), *count, "{}", fen);
+            let pseudo_legal = board.pseudo_legal_moves();
+            for m in legal.iter() {
+                assert!(board.is_legal(m), "{} {}", fen, m);
+                assert!(pseudo_legal.contains(m), "{} {}", fen, m);
+            }
+        }
+    }
+
+    #[test]
+    fn pseudo_legal_moves_test() {
+        // the knight is pinned to the king, so only king moves are legal
+        let mut board = board("4k3/4r3/8/8/8/8/4N3/4K3 w - - 0 1");
+        let legal = board.legal_moves();
+        assert_eq!(legal.len(), 4);
+        let pseudo_legal = board.pseudo_legal_moves();
+        assert_eq!(pseudo_legal.len(), 10);
+        assert!(legal.iter().all(|m| pseudo_legal.contains(m)));
+
+        let pinned = Move::from_str("e2c3", &board).unwrap();
+        assert!(pseudo_legal.contains(&pinned));
+        assert!(!board.is_legal(&pinned));
+        assert!(!pinned.is_legal(&mut board));
+
+        // the generator gives the same moves one at a time
+        let mut generated = Vec::new();
+        let mut gen = MoveGenerator::new(&mut board);
+        while let Some(m) = gen.next_pseudo_legal(&mut board) {
+            generated.push(m);
+        }
+        assert_eq!(generated, pseudo_legal);
+    }
+
+    #[test]
+    fn san_is_wellformed_test() {
+        // legal or not, these are valid SAN
+        for san in ["e4", "e5", "Nc3", "Qb3d3", "exd8=N", "e8Q", "O-O-O", "Ra8+", "Nf3!?"] {
+            assert!(Move::san_is_wellformed(san), "{}", san);
+        }
+        for san in ["", "Nf", "j4e5", "Nz9", "Kee", "O-O-O-O"] {
+            assert!(!Move::san_is_wellformed(san), "{}", san);
+        }
+    }
+
+    #[test]
+    fn board_hash_test() {
+        // positions and hashes from the polyglot book format specification
+        let lines: [&[(&[&str], u64)]; 2] = [
+            &[
+                (&["e2e4"], 0x823c9b50fd114196),
+                (&["d7d5"], 0x0756b94461c50fb0),
+                (&["e4e5"], 0x662fafb965db29d4),
+                (&["f7f5"], 0x22a48b5a8e47ff78),
+                (&["e1e2"], 0x652a607ca3f242c1),
+                (&["e8f7"], 0x00fdd303c946bdd9),
+            ],
+            &[
+                (
+                    &["a2a4", "b7b5", "h2h4", "b5b4", "c2c4"],
+                    0x3c8123ea7b067637,
+                ),
+                (&["b4c3", "a1a3"], 0x5c3f9b829b279560),
+            ],
+        ];
+        for line in lines.iter() {
+            let mut board = board(START_FEN);
+            assert_eq!(board.hash(), 0x463b96181691fc9c);
+            let mut made = Vec::new();
+            for (moves, hash) in line.iter() {
+                for move_str in moves.iter() {
+                    let m = Move::from_str(move_str, &board).unwrap();
+                    made.push((m, board.hash()));
+                    board.make_move(m);
+                }
+                assert_eq!(board.hash(), *hash);
+                assert_eq!(Board::new(&board.to_string()).hash(), *hash);
+            }
+            // unmaking moves restores the previous hashes
+            while let Some((m, hash)) = made.pop() {
+                board.unmake_move(m);
+                assert_eq!(board.hash(), hash);
             }
+            assert_eq!(board.hash(), 0x463b96181691fc9c);
         }
     }
 }
@@ -0,0 +1 @@
+ALTER TABLE games DROP COLUMN options
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE games ADD COLUMN options TEXT NOT NULL DEFAULT ''
\ No newline at end of file
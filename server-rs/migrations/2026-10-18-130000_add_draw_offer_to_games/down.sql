@@ -0,0 +1 @@
+ALTER TABLE games DROP COLUMN draw_offer
\ No newline at end of file
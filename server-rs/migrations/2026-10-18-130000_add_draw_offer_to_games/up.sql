@@ -0,0 +1 @@
+ALTER TABLE games ADD COLUMN draw_offer INTEGER
\ No newline at end of file
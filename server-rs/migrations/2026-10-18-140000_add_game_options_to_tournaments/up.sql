@@ -0,0 +1 @@
+ALTER TABLE tournaments ADD COLUMN game_options TEXT NOT NULL DEFAULT ''
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE game_players DROP COLUMN moves_made
\ No newline at end of file
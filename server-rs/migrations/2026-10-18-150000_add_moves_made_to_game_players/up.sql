@@ -0,0 +1 @@
+ALTER TABLE game_players ADD COLUMN moves_made INTEGER NOT NULL DEFAULT 0
\ No newline at end of file
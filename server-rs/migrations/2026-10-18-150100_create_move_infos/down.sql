@@ -0,0 +1 @@
+DROP TABLE move_infos
\ No newline at end of file
@@ -0,0 +1,11 @@
+CREATE TABLE move_infos (
+    id SERIAL PRIMARY KEY,
+    game_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    move_number INTEGER NOT NULL,
+    eval DOUBLE PRECISION,
+    depth INTEGER,
+    node_count BIGINT,
+    pv TEXT,
+    UNIQUE (game_id, user_id, move_number)
+)
\ No newline at end of file
@@ -0,0 +1 @@
+DROP TABLE game_moves
\ No newline at end of file
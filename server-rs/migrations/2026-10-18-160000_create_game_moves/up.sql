@@ -0,0 +1,12 @@
+CREATE TABLE game_moves (
+    id SERIAL PRIMARY KEY,
+    game_id INTEGER NOT NULL,
+    ply INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    play TEXT NOT NULL,
+    time_spent_ms BIGINT NOT NULL,
+    clock_ms BIGINT NOT NULL,
+    played_at_ms BIGINT NOT NULL,
+    eval DOUBLE PRECISION,
+    UNIQUE (game_id, ply)
+)
\ No newline at end of file
@@ -0,0 +1,2 @@
+DROP TABLE rating_changes;
+DROP TABLE ratings
\ No newline at end of file
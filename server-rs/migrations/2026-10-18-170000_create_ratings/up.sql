@@ -0,0 +1,20 @@
+CREATE TABLE ratings (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    game_type TEXT NOT NULL,
+    rating DOUBLE PRECISION NOT NULL,
+    deviation DOUBLE PRECISION NOT NULL,
+    volatility DOUBLE PRECISION NOT NULL,
+    UNIQUE (user_id, game_type)
+);
+
+CREATE TABLE rating_changes (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    game_type TEXT NOT NULL,
+    game_id INTEGER NOT NULL,
+    rating DOUBLE PRECISION NOT NULL,
+    deviation DOUBLE PRECISION NOT NULL,
+    volatility DOUBLE PRECISION NOT NULL,
+    changed_at_ms BIGINT NOT NULL
+)
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE games ADD COLUMN rated BOOLEAN NOT NULL DEFAULT TRUE
\ No newline at end of file
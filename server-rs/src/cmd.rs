@@ -2,9 +2,12 @@ use crate::apikey::ApiKey;
 use crate::db::GameTimeMs;
 use crate::error::Error;
 use crate::games::GameState;
-use crate::models::{GameId, TournamentId, TournamentPlayer, UserId};
+use crate::models::{
+    GameId, MoveInfo, RatingChange, TournamentId, TournamentPlayer, UserId, UserStats,
+};
+use crate::rating::Glicko;
 use lazy_static;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 use std::fmt;
 use std::fmt::{Display, Formatter};
@@ -65,7 +68,8 @@ pub enum ServerCommand {
         winner: GameState,
         time_dur: GameTimeMs,
         current_player_time_for_move: Option<i64>,
-        players: Vec<(UserId, Option<f64>, i64)>,
+        players: Vec<(UserId, Option<f64>, i64, f64)>,
+        draw_offer: Option<UserId>,
         state: Option<String>,
     },
     /// Report a new tournament's id
@@ -79,7 +83,7 @@ pub enum ServerCommand {
         started: bool,
         finished: bool,
         winner: GameState,
-        players: Vec<TournamentPlayer>,
+        players: Vec<(TournamentPlayer, f64)>,
         games: String,
     },
     /// Send a game to the client to make a move on
@@ -89,9 +93,34 @@ pub enum ServerCommand {
         time_ms: i64,
         time_for_turn_ms: i64,
         state: Option<String>,
+        draw_offer: bool,
     },
     /// Send a game to the client to make a move on (legacy)
     Position { state: Option<String> },
+    /// Report a game's moves in PGN
+    Pgn { id: GameId, pgn: String },
+    /// Return the id of a game created from PGN
+    ImportPgn(GameId),
+    /// Report info about a move in a game (such as a client's evaluation of the position)
+    MoveInfo(MoveInfo),
+    /// Report a user's rating in a game type, and how it changed over time
+    Rating {
+        user_id: UserId,
+        game_type: String,
+        rating: Glicko,
+        changes: Vec<RatingChange>,
+    },
+    /// Report a page of the users with the highest ratings in a game type, with their ranks and game totals
+    Leaderboard {
+        game_type: String,
+        offset: u32,
+        stats: Vec<UserStats>,
+    },
+    /// Report a user's game totals in each game type they've played
+    UserStats {
+        user_id: UserId,
+        stats: Vec<UserStats>,
+    },
 }
 
 /// A command sent to the server from the client
@@ -131,6 +160,7 @@ pub enum ClientCommand<'a> {
         game_type: &'a str,
         total_time: i64,
         time_per_move: i64,
+        options: &'a str,
     },
     /// Observe a game with the given id
     ObserveGame(GameId),
@@ -142,6 +172,37 @@ pub enum ClientCommand<'a> {
     LeaveGame(GameId),
     /// Start a game with the given id
     StartGame(GameId),
+    /// Resign from a game with the given id
+    Resign(GameId),
+    /// Offer a draw in a game with the given id
+    OfferDraw(GameId),
+    /// Accept the draw offered in a game with the given id
+    AcceptDraw(GameId),
+    /// Decline the draw offered in a game with the given id
+    DeclineDraw(GameId),
+    /// Get a game's moves in PGN (ServerCommand::Pgn response)
+    Pgn(GameId),
+    /// Create a game from PGN (ServerCommand::ImportPgn response)
+    ImportPgn {
+        white: UserId,
+        black: UserId,
+        total_time: i64,
+        time_per_move: i64,
+        pgn: &'a str,
+    },
+    /// Get a user's rating in a game type (ServerCommand::Rating response)
+    Rating {
+        user_id: UserId,
+        game_type: &'a str,
+    },
+    /// Get a page of the highest rated users in a game type (ServerCommand::Leaderboard response)
+    Leaderboard {
+        game_type: &'a str,
+        offset: u32,
+        limit: u32,
+    },
+    /// Get a user's game totals (ServerCommand::UserStats response)
+    UserStats(UserId),
     /// Create a new tournament
     NewTournament {
         tourney_type: &'a str,
@@ -149,6 +210,7 @@ pub enum ClientCommand<'a> {
         total_time: i64,
         time_per_move: i64,
         options: &'a str,
+        game_options: &'a str,
     },
     /// Become a player in a tournament
     JoinTournament(TournamentId),
@@ -167,6 +229,13 @@ pub enum ClientCommand<'a> {
     },
     /// Make a move in a game (legacy)
     Move(&'a str),
+    /// Report info about the current user's last move in a game
+    Info {
+        id: GameId,
+        info: &'a str,
+    },
+    /// Report info about the current user's last move (legacy)
+    LegacyInfo(&'a str),
 }
 
 impl ServerCommand {
@@ -177,6 +246,26 @@ impl ServerCommand {
             &GameState::Tie => write!(f, "tie"),
         }
     }
+
+    /// Write a user's rating and game totals
+    fn write_stats(f: &mut fmt::Formatter<'_>, stats: &UserStats) -> fmt::Result {
+        write!(
+            f,
+            "{:.2}, {:.2}, {}, {}, {}, {}, {}, ",
+            stats.rating,
+            stats.deviation,
+            stats.games,
+            stats.wins,
+            stats.losses,
+            stats.ties,
+            stats.score
+        )?;
+        match stats.avg_move_time_ms {
+            Some(time) => write!(f, "{:.0}, ", time)?,
+            None => write!(f, "-, ")?,
+        };
+        write!(f, "{}", stats.time_forfeits)
+    }
 }
 
 impl fmt::Display for ServerCommand {
@@ -209,6 +298,7 @@ impl fmt::Display for ServerCommand {
                 ref state,
                 ref time_dur,
                 current_player_time_for_move,
+                draw_offer,
             } => {
                 write!(
                     f,
@@ -229,16 +319,22 @@ impl fmt::Display for ServerCommand {
                 for (i, player) in players.iter().enumerate() {
                     write!(
                         f,
-                        "[{}, {}, {}]",
+                        "[{}, {}, {}, {:.0}]",
                         (*player).0,
                         (*player).1.unwrap_or(0.0),
-                        (*player).2
+                        (*player).2,
+                        (*player).3
                     )?;
                     if i < players.len() - 1 {
                         write!(f, ", ")?;
                     }
                 }
-                write!(f, "], {}", *state.as_ref().unwrap_or(&dash_str))
+                write!(f, "], ")?;
+                match draw_offer {
+                    Some(uid) => write!(f, "{}", uid)?,
+                    None => write!(f, "-")?,
+                };
+                write!(f, ", {}", *state.as_ref().unwrap_or(&dash_str))
             }
             &NewTournament(id) => write!(f, "new_tournament {}", id),
             &Tournament {
@@ -259,11 +355,11 @@ impl fmt::Display for ServerCommand {
                 )?;
                 ServerCommand::write_game_state(f, winner)?;
                 write!(f, ", [")?;
-                for (i, player) in players.iter().enumerate() {
+                for (i, (player, rating)) in players.iter().enumerate() {
                     write!(
                         f,
-                        "[{}, {}, {}, {}]",
-                        player.user_id, player.win, player.loss, player.tie
+                        "[{}, {}, {}, {}, {:.0}]",
+                        player.user_id, player.win, player.loss, player.tie, rating
                     )?;
                     if i < players.len() - 1 {
                         write!(f, ", ")?;
@@ -277,18 +373,98 @@ impl fmt::Display for ServerCommand {
                 time_ms,
                 time_for_turn_ms,
                 ref state,
-            } => write!(
-                f,
-                "go {}, {}, {}, {}, {}",
-                id,
-                *game_type,
-                time_ms,
-                time_for_turn_ms,
-                *state.as_ref().unwrap_or(&dash_str)
-            ),
+                draw_offer,
+            } => {
+                write!(
+                    f,
+                    "go {}, {}, {}, {}, {}",
+                    id,
+                    *game_type,
+                    time_ms,
+                    time_for_turn_ms,
+                    *state.as_ref().unwrap_or(&dash_str)
+                )?;
+                if draw_offer {
+                    write!(f, ", draw_offer")?;
+                }
+                Ok(())
+            }
             &Position { ref state } => {
                 write!(f, "position {}", *state.as_ref().unwrap_or(&dash_str))
             }
+            &Pgn { id, ref pgn } => write!(f, "pgn {}, {}", id, *pgn),
+            &ImportPgn(id) => write!(f, "import_pgn {}", id),
+            &MoveInfo(ref info) => {
+                write!(
+                    f,
+                    "move_info {}, {}, {}, ",
+                    info.game_id, info.user_id, info.move_number
+                )?;
+                match info.eval {
+                    Some(eval) => write!(f, "{}, ", eval)?,
+                    None => write!(f, "-, ")?,
+                };
+                match info.depth {
+                    Some(depth) => write!(f, "{}, ", depth)?,
+                    None => write!(f, "-, ")?,
+                };
+                match info.node_count {
+                    Some(nodes) => write!(f, "{}, ", nodes)?,
+                    None => write!(f, "-, ")?,
+                };
+                write!(f, "{}", *info.pv.as_ref().unwrap_or(&dash_str))
+            }
+            &Rating {
+                user_id,
+                ref game_type,
+                ref rating,
+                ref changes,
+            } => {
+                write!(
+                    f,
+                    "rating {}, {}, {:.2}, {:.2}, {:.6}, [",
+                    user_id, *game_type, rating.rating, rating.deviation, rating.volatility
+                )?;
+                for (i, change) in changes.iter().enumerate() {
+                    write!(
+                        f,
+                        "[{}, {:.2}, {:.2}, {}]",
+                        change.game_id, change.rating, change.deviation, change.changed_at_ms
+                    )?;
+                    if i < changes.len() - 1 {
+                        write!(f, ", ")?;
+                    }
+                }
+                write!(f, "]")
+            }
+            &Leaderboard {
+                ref game_type,
+                offset,
+                ref stats,
+            } => {
+                write!(f, "leaderboard {}, [", *game_type)?;
+                for (i, entry) in stats.iter().enumerate() {
+                    write!(f, "[{}, {}, ", offset as usize + i + 1, entry.user_id)?;
+                    ServerCommand::write_stats(f, entry)?;
+                    write!(f, "]")?;
+                    if i < stats.len() - 1 {
+                        write!(f, ", ")?;
+                    }
+                }
+                write!(f, "]")
+            }
+            &UserStats { user_id, ref stats } => {
+                write!(f, "user_stats {}, [", user_id)?;
+                for (i, entry) in stats.iter().enumerate() {
+                    write!(f, "[{}, ", entry.game_type)?;
+                    ServerCommand::write_stats(f, entry)?;
+                    write!(f, "]")?;
+                    if i < stats.len() - 1 {
+                        write!(f, ", ")?;
+                    }
+                }
+                write!(f, "]")
+            }
         }
     }
 }
@@ -308,7 +484,13 @@ fn parse_cmd(msg: &str) -> (&str, Vec<&str>) {
     }
 
     if cmd_end_index < msg.len() {
-        for el in msg[cmd_end_index..].split(',') {
+        // the last argument of commands that take text keeps its commas
+        let max_args = if TEXT_ARG_CMDS.contains(cmd) {
+            NUM_ARGS[cmd] + NUM_OPTIONAL_ARGS.get(cmd).unwrap_or(&0)
+        } else {
+            usize::MAX
+        };
+        for el in msg[cmd_end_index..].splitn(max_args, ',') {
             args.push(el.trim());
         }
     }
@@ -335,6 +517,15 @@ lazy_static! {
         m.insert("join_game", 1);
         m.insert("leave_game", 1);
         m.insert("start_game", 1);
+        m.insert("resign", 1);
+        m.insert("offer_draw", 1);
+        m.insert("accept_draw", 1);
+        m.insert("decline_draw", 1);
+        m.insert("pgn", 1);
+        m.insert("import_pgn", 5);
+        m.insert("rating", 2);
+        m.insert("leaderboard", 3);
+        m.insert("user_stats", 1);
         m.insert("new_tournament", 5);
         m.insert("join_tournament", 1);
         m.insert("leave_tournament", 1);
@@ -344,8 +535,24 @@ lazy_static! {
         m.insert("version", 1);
         m.insert("play", 2);
         m.insert("move", 1);
+        m.insert("info", 0);
+        m
+    };
+    // number of optional arguments that may follow the expected arguments for each command
+    static ref NUM_OPTIONAL_ARGS: HashMap<&'static str, usize> = {
+        let mut m = HashMap::new();
+        m.insert("new_game", 1);
+        m.insert("new_tournament", 1);
+        m.insert("info", 2);
         m
     };
+    // commands whose last argument is text that may contain commas
+    static ref TEXT_ARG_CMDS: HashSet<&'static str> = {
+        let mut s = HashSet::new();
+        s.insert("import_pgn");
+        s.insert("info");
+        s
+    };
 }
 
 fn parse_val<F: FromStr>(str: &str) -> Result<F, Error> {
@@ -372,7 +579,8 @@ impl ClientCommand<'_> {
         match expected_args {
             None => return Err(Error::InvalidCommand(cmd.to_string())),
             Some(expected) => {
-                if args.len() != *expected {
+                let optional = NUM_OPTIONAL_ARGS.get(cmd).unwrap_or(&0);
+                if args.len() < *expected || args.len() > *expected + *optional {
                     return Err(Error::InvalidNumberOfArguments {
                         cmd: cmd.to_string(),
                         expected: *expected,
@@ -404,23 +612,52 @@ impl ClientCommand<'_> {
                 game_type: args[0],
                 total_time: parse_val(args[1])?,
                 time_per_move: parse_val(args[2])?,
+                options: args.get(3).unwrap_or(&""),
             }),
             "observe_game" => Ok(ObserveGame(parse_val(args[0])?)),
             "stop_observe_game" => Ok(StopObserveGame(parse_val(args[0])?)),
             "join_game" => Ok(JoinGame(parse_val(args[0])?)),
             "leave_game" => Ok(LeaveGame(parse_val(args[0])?)),
             "start_game" => Ok(StartGame(parse_val(args[0])?)),
+            "resign" => Ok(Resign(parse_val(args[0])?)),
+            "offer_draw" => Ok(OfferDraw(parse_val(args[0])?)),
+            "accept_draw" => Ok(AcceptDraw(parse_val(args[0])?)),
+            "decline_draw" => Ok(DeclineDraw(parse_val(args[0])?)),
+            "pgn" => Ok(Pgn(parse_val(args[0])?)),
+            "import_pgn" => Ok(ImportPgn {
+                white: parse_val(args[0])?,
+                black: parse_val(args[1])?,
+                total_time: parse_val(args[2])?,
+                time_per_move: parse_val(args[3])?,
+                pgn: args[4],
+            }),
+            "rating" => Ok(Rating {
+                user_id: parse_val(args[0])?,
+                game_type: args[1],
+            }),
+            "leaderboard" => Ok(Leaderboard {
+                game_type: args[0],
+                offset: parse_val(args[1])?,
+                limit: parse_val(args[2])?,
+            }),
+            "user_stats" => Ok(UserStats(parse_val(args[0])?)),
             "play" => Ok(Play {
                 id: parse_val(args[0])?,
                 play: args[1],
             }),
             "move" => Ok(Move(args[0])),
+            // info is given with a game id in version 2 (`info <id>, <info>`), and without in legacy clients
+            "info" => match (args.len(), args.first().map(|id| parse_val::<GameId>(id))) {
+                (2, Some(Ok(id))) => Ok(Info { id, info: args[1] }),
+                _ => Ok(LegacyInfo(msg[cmd.len()..].trim())),
+            },
             "new_tournament" => Ok(NewTournament {
                 tourney_type: args[0],
                 game_type: args[1],
                 total_time: parse_val(args[2])?,
                 time_per_move: parse_val(args[3])?,
                 options: args[4],
+                game_options: args.get(5).unwrap_or(&""),
             }),
             "join_tournament" => Ok(JoinTournament(parse_val(args[0])?)),
             "leave_tournament" => Ok(LeaveTournament(parse_val(args[0])?)),
@@ -470,11 +707,36 @@ mod tests {
                 winner: GameState::Tie,
                 time_dur: GameTimeMs { sudden_death_ms: 200, per_move_ms: 100 },
                 current_player_time_for_move: Some(150),
-                players: vec![(3, Some(0.5), 1), (4, Some(4.5), 2), (5, None, 3)],
+                players: vec![
+                    (3, Some(0.5), 1, 1500.0),
+                    (4, Some(4.5), 2, 1662.31),
+                    (5, None, 3, 1337.4)
+                ],
+                draw_offer: None,
+                state: Some("STATE".to_string()),
+            }
+            .to_string(),
+            "game 1, some_game, 2, true, true, tie, 200, 100, 150, [[3, 0.5, 1, 1500], [4, 4.5, 2, 1662], [5, 0, 3, 1337]], -, STATE"
+        );
+        assert_eq!(
+            ServerCommand::Game {
+                id: 1,
+                game_type: "some_game".to_string(),
+                owner: 2,
+                started: true,
+                finished: false,
+                winner: GameState::InProgress,
+                time_dur: GameTimeMs {
+                    sudden_death_ms: 200,
+                    per_move_ms: 100
+                },
+                current_player_time_for_move: None,
+                players: vec![(3, None, 1, 1500.0), (4, None, 2, 1500.0)],
+                draw_offer: Some(4),
                 state: Some("STATE".to_string()),
             }
             .to_string(),
-            "game 1, some_game, 2, true, true, tie, 200, 100, 150, [[3, 0.5, 1], [4, 4.5, 2], [5, 0, 3]], STATE"
+            "game 1, some_game, 2, true, false, -, 200, 100, -, [[3, 0, 1, 1500], [4, 0, 2, 1500]], 4, STATE"
         );
         assert_eq!(
             ServerCommand::Go {
@@ -482,11 +744,24 @@ mod tests {
                 game_type: "some_game".to_string(),
                 time_ms: 1234,
                 time_for_turn_ms: 321,
-                state: Some("STATE".to_string())
+                state: Some("STATE".to_string()),
+                draw_offer: false,
             }
             .to_string(),
             "go 1, some_game, 1234, 321, STATE"
         );
+        assert_eq!(
+            ServerCommand::Go {
+                id: 1,
+                game_type: "some_game".to_string(),
+                time_ms: 1234,
+                time_for_turn_ms: 321,
+                state: Some("STATE".to_string()),
+                draw_offer: true,
+            }
+            .to_string(),
+            "go 1, some_game, 1234, 321, STATE, draw_offer"
+        );
         assert_eq!(
             ServerCommand::Position {
                 state: Some("STATE".to_string())
@@ -494,6 +769,15 @@ mod tests {
             .to_string(),
             "position STATE"
         );
+        assert_eq!(
+            ServerCommand::Pgn {
+                id: 1,
+                pgn: "PGN".to_string()
+            }
+            .to_string(),
+            "pgn 1, PGN"
+        );
+        assert_eq!(ServerCommand::ImportPgn(1).to_string(), "import_pgn 1");
         assert_eq!(
             ServerCommand::NewTournament(1).to_string(),
             "new_tournament 1"
@@ -508,27 +792,107 @@ mod tests {
                 finished: true,
                 winner: GameState::Tie,
                 players: vec![
-                    TournamentPlayer {
-                        user_id: 3,
-                        id: 0,
-                        tournament_id: 1,
-                        win: 4,
-                        loss: 5,
-                        tie: 6
-                    },
-                    TournamentPlayer {
-                        user_id: 7,
-                        id: 0,
-                        tournament_id: 1,
-                        win: 8,
-                        loss: 9,
-                        tie: 10
-                    }
+                    (
+                        TournamentPlayer {
+                            user_id: 3,
+                            id: 0,
+                            tournament_id: 1,
+                            win: 4,
+                            loss: 5,
+                            tie: 6
+                        },
+                        1500.0
+                    ),
+                    (
+                        TournamentPlayer {
+                            user_id: 7,
+                            id: 0,
+                            tournament_id: 1,
+                            win: 8,
+                            loss: 9,
+                            tie: 10
+                        },
+                        1420.6
+                    )
                 ],
                 games: "GAMES".to_string()
             }
             .to_string(),
-            "tournament 1, type, 2, game, true, true, tie, [[3, 4, 5, 6], [7, 8, 9, 10]], GAMES"
+            "tournament 1, type, 2, game, true, true, tie, [[3, 4, 5, 6, 1500], [7, 8, 9, 10, 1421]], GAMES"
+        );
+        assert_eq!(
+            ServerCommand::MoveInfo(MoveInfo {
+                id: 1,
+                game_id: 2,
+                user_id: 3,
+                move_number: 4,
+                eval: Some(-0.5),
+                depth: Some(12),
+                node_count: None,
+                pv: Some("e2e4 e7e5".to_string()),
+            })
+            .to_string(),
+            "move_info 2, 3, 4, -0.5, 12, -, e2e4 e7e5"
+        );
+        assert_eq!(
+            ServerCommand::Rating {
+                user_id: 1,
+                game_type: "chess".to_string(),
+                rating: Glicko {
+                    rating: 1662.3114,
+                    deviation: 290.3194,
+                    volatility: 0.05999,
+                },
+                changes: vec![RatingChange {
+                    id: 1,
+                    user_id: 1,
+                    game_type: "chess".to_string(),
+                    game_id: 2,
+                    rating: 1662.3114,
+                    deviation: 290.3194,
+                    volatility: 0.05999,
+                    changed_at_ms: 1000,
+                }],
+            }
+            .to_string(),
+            "rating 1, chess, 1662.31, 290.32, 0.059990, [[2, 1662.31, 290.32, 1000]]"
+        );
+        let stats = UserStats {
+            user_id: 3,
+            game_type: "chess".to_string(),
+            games: 5,
+            wins: 2,
+            losses: 1,
+            ties: 2,
+            score: 3.0,
+            avg_move_time_ms: Some(1234.4),
+            time_forfeits: 1,
+            rating: 1662.3114,
+            deviation: 290.3194,
+        };
+        assert_eq!(
+            ServerCommand::Leaderboard {
+                game_type: "chess".to_string(),
+                offset: 10,
+                stats: vec![
+                    stats.clone(),
+                    UserStats {
+                        user_id: 4,
+                        avg_move_time_ms: None,
+                        ..stats.clone()
+                    }
+                ],
+            }
+            .to_string(),
+            "leaderboard chess, [[11, 3, 1662.31, 290.32, 5, 2, 1, 2, 3, 1234, 1], [12, 4, 1662.31, 290.32, 5, 2, 1, 2, 3, -, 1]]"
+        );
+        assert_eq!(
+            ServerCommand::UserStats {
+                user_id: 3,
+                stats: vec![stats],
+            }
+            .to_string(),
+            "user_stats 3, [[chess, 1662.31, 290.32, 5, 2, 1, 2, 3, 1234, 1]]"
         );
     }
 
@@ -611,7 +975,25 @@ mod tests {
             Ok(ClientCommand::NewGame {
                 game_type: "chess",
                 total_time: 1000,
-                time_per_move: 500
+                time_per_move: 500,
+                options: ""
+            })
+        );
+        assert_eq!(
+            ClientCommand::deserialize("new_game chess, 1000, 500, draw_rule=none"),
+            Ok(ClientCommand::NewGame {
+                game_type: "chess",
+                total_time: 1000,
+                time_per_move: 500,
+                options: "draw_rule=none"
+            })
+        );
+        assert_eq!(
+            ClientCommand::deserialize("new_game chess, 1000, 500, a, b"),
+            Err(Error::InvalidNumberOfArguments {
+                expected: 3,
+                actual: 5,
+                cmd: "new_game".to_string()
             })
         );
         assert_eq!(
@@ -638,6 +1020,69 @@ mod tests {
             ClientCommand::deserialize("leave_game 5"),
             Ok(ClientCommand::LeaveGame(5))
         );
+        assert_eq!(
+            ClientCommand::deserialize("resign 6"),
+            Ok(ClientCommand::Resign(6))
+        );
+        assert_eq!(
+            ClientCommand::deserialize("offer_draw 7"),
+            Ok(ClientCommand::OfferDraw(7))
+        );
+        assert_eq!(
+            ClientCommand::deserialize("accept_draw 8"),
+            Ok(ClientCommand::AcceptDraw(8))
+        );
+        assert_eq!(
+            ClientCommand::deserialize("decline_draw 9"),
+            Ok(ClientCommand::DeclineDraw(9))
+        );
+        assert_eq!(
+            ClientCommand::deserialize("pgn 10"),
+            Ok(ClientCommand::Pgn(10))
+        );
+        assert_eq!(
+            ClientCommand::deserialize(
+                "import_pgn 1, 2, 1000, 0, [White \"B, A\"]\n\n1. e4 {[%clk 0:00:01]} *"
+            ),
+            Ok(ClientCommand::ImportPgn {
+                white: 1,
+                black: 2,
+                total_time: 1000,
+                time_per_move: 0,
+                pgn: "[White \"B, A\"]\n\n1. e4 {[%clk 0:00:01]} *"
+            })
+        );
+        assert_eq!(
+            ClientCommand::deserialize("import_pgn 1, 2, 1000"),
+            Err(Error::InvalidNumberOfArguments {
+                cmd: "import_pgn".to_string(),
+                expected: 5,
+                actual: 3
+            })
+        );
+        assert_eq!(
+            ClientCommand::deserialize("rating 3, chess"),
+            Ok(ClientCommand::Rating {
+                user_id: 3,
+                game_type: "chess"
+            })
+        );
+        assert_eq!(
+            ClientCommand::deserialize("leaderboard chess, 0, 20"),
+            Ok(ClientCommand::Leaderboard {
+                game_type: "chess",
+                offset: 0,
+                limit: 20
+            })
+        );
+        assert_eq!(
+            ClientCommand::deserialize("leaderboard chess, -1, 20"),
+            Err(Error::InvalidNumberId)
+        );
+        assert_eq!(
+            ClientCommand::deserialize("user_stats 3"),
+            Ok(ClientCommand::UserStats(3))
+        );
         assert_eq!(
             ClientCommand::deserialize("play 1, e2e4"),
             Ok(ClientCommand::Play {
@@ -649,6 +1094,25 @@ mod tests {
             ClientCommand::deserialize("move e2e4"),
             Ok(ClientCommand::Move("e2e4"))
         );
+        assert_eq!(
+            ClientCommand::deserialize("info 3, eval 25`depth 12`pv e2e4 e7e5, c7c5`"),
+            Ok(ClientCommand::Info {
+                id: 3,
+                info: "eval 25`depth 12`pv e2e4 e7e5, c7c5`"
+            })
+        );
+        assert_eq!(
+            ClientCommand::deserialize("info eval 25`depth 12`"),
+            Ok(ClientCommand::LegacyInfo("eval 25`depth 12`"))
+        );
+        assert_eq!(
+            ClientCommand::deserialize("info "),
+            Ok(ClientCommand::LegacyInfo(""))
+        );
+        assert_eq!(
+            ClientCommand::deserialize("info eval 25`pv e2e4, e7e5"),
+            Ok(ClientCommand::LegacyInfo("eval 25`pv e2e4, e7e5"))
+        );
 
         assert_eq!(
             ClientCommand::deserialize("new_tournament type, game, 100, 200, 2"),
@@ -657,7 +1121,19 @@ mod tests {
                 game_type: "game",
                 total_time: 100,
                 time_per_move: 200,
-                options: "2"
+                options: "2",
+                game_options: ""
+            })
+        );
+        assert_eq!(
+            ClientCommand::deserialize("new_tournament type, game, 100, 200, 2, draw_rule=none"),
+            Ok(ClientCommand::NewTournament {
+                tourney_type: "type",
+                game_type: "game",
+                total_time: 100,
+                time_per_move: 200,
+                options: "2",
+                game_options: "draw_rule=none"
             })
         );
         assert_eq!(
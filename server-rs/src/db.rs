@@ -1,22 +1,29 @@
 use crate::apikey::ApiKey;
-use crate::diesel::prelude::*;
 use crate::error::Error;
-use crate::games::ended_game::EndedGameInstance;
-use crate::games::{Fmt, GameInstance, GameState, GameTurn, GameTypeMap};
+use crate::games::ended_game::{EndedGame, EndedGameInstance, ENDED_GAME_PREFIX};
+use crate::games::{Fmt, GameInstance, GameState, GameTurn, GameType, GameTypeMap};
 use crate::models::{
-    DBGame, DBTournament, GameId, GamePlayer, GamePlayerId, NewDBGame, NewDBTournament,
-    NewGamePlayer, NewTournamentPlayer, NewUser, TournamentId, TournamentPlayer, User, UserId,
+    DBGame, DBTournament, GameId, GameMove, GamePlayer, GamePlayerId, MoveInfo, NewDBGame,
+    NewDBTournament, NewGameMove, NewGamePlayer, NewMoveInfo, NewRating, NewRatingChange,
+    NewTournamentPlayer, NewUser, RatingChange, TournamentId, TournamentPlayer, User, UserId,
+    UserStats,
 };
-use crate::schema::{game_players, games, tournament_players, tournaments, users};
+use crate::pgn::PgnGame;
+use crate::rating::Glicko;
+use crate::storage::{Storage, StoragePool};
 use crate::tournament::{TournamentCfg, TournamentTypeInstance, TournamentTypeMap};
 use bcrypt;
-use diesel::pg::PgConnection;
-use diesel::r2d2::{ConnectionManager, Pool, PoolError, PooledConnection};
 use futures_channel::mpsc;
 use rand::random;
+use std::cell::Cell;
 use std::cmp::max;
+use std::collections::HashMap;
+use std::str::FromStr;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+/// Most users returned in one page of the leaderboard
+pub const MAX_LEADERBOARD_LIMIT: u32 = 100;
+
 impl User {
     pub fn check_password(&self, password: &str) -> bool {
         match self.password_hash.as_deref() {
@@ -29,6 +36,65 @@ impl User {
     }
 }
 
+impl NewMoveInfo {
+    /// Parse info about a move in the form `key value`key value...`. The eval, depth, nodes, and pv keys are
+    /// recorded, and other keys are ignored.
+    pub fn parse(
+        game_id: GameId,
+        user_id: UserId,
+        move_number: i32,
+        info: &str,
+    ) -> Result<NewMoveInfo, String> {
+        fn number<T: FromStr>(key: &str, value: &str) -> Result<Option<T>, String> {
+            match value.parse() {
+                Ok(num) => Ok(Some(num)),
+                Err(_) => Err(format!("{} should be a number, not {}", key, value)),
+            }
+        }
+
+        let mut res = NewMoveInfo {
+            game_id,
+            user_id,
+            move_number,
+            eval: None,
+            depth: None,
+            node_count: None,
+            pv: None,
+        };
+        for entry in info.split('`') {
+            let entry = entry.trim();
+            if entry.is_empty() {
+                continue;
+            }
+            let (key, value) = match entry.split_once(char::is_whitespace) {
+                Some((key, value)) => (key, value.trim()),
+                None => (entry, ""),
+            };
+            match key {
+                "eval" => {
+                    res.eval = number::<f64>(key, value)?;
+                    if !res.eval.unwrap().is_finite() {
+                        return Err(format!("eval should be a number, not {}", value));
+                    }
+                }
+                "depth" => res.depth = number(key, value)?,
+                "nodes" => res.node_count = number(key, value)?,
+                "pv" => res.pv = Some(value.to_string()),
+                _ => {}
+            }
+        }
+        Ok(res)
+    }
+
+    /// Check if none of the recorded keys were given
+    pub fn is_empty(&self) -> bool {
+        self.eval.is_none()
+            && self.depth.is_none()
+            && self.node_count.is_none()
+            && self.pv.is_none()
+    }
+}
+
 /// Time control configuration for a game
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub struct GameTimeCfg {
@@ -67,10 +133,15 @@ pub struct Game {
     pub owner_id: UserId,
     pub tournament_id: Option<TournamentId>,
     pub game_type: String,
+    pub options: String,
     pub instance: Option<Box<dyn GameInstance>>,
     pub time: GameTimeCfg,
     pub current_move_start: Option<SystemTime>,
     pub turn_id: Option<i64>,
+    /// player who has offered a draw that hasn't been accepted, declined, or expired
+    pub draw_offer: Option<UserId>,
+    /// whether the game's result changes the players' ratings
+    pub rated: bool,
 }
 
 pub type GameAndPlayers = (Game, Vec<GamePlayer>);
@@ -79,7 +150,11 @@ pub type TournamentAndPlayers = (Tournament, Vec<TournamentPlayer>);
 impl Game {
     pub fn from_dbgame(game: DBGame, type_map: &GameTypeMap, players: &[GamePlayerId]) -> Game {
         let instance = if let Some(ref state) = game.state {
-            type_map[&*game.game_type].deserialize(state, players)
+            if state.starts_with(ENDED_GAME_PREFIX) {
+                EndedGame().deserialize(state, players, &game.options)
+            } else {
+                type_map[&*game.game_type].deserialize(state, players, &game.options)
+            }
         } else {
             None
         };
@@ -89,6 +164,7 @@ impl Game {
             owner_id: game.owner_id,
             tournament_id: game.tournament_id,
             game_type: game.game_type,
+            options: game.options,
             instance,
             time: GameTimeCfg {
                 per_move: Duration::from_millis(game.dur_per_move_ms as u64),
@@ -98,6 +174,8 @@ impl Game {
                 .current_move_start_ms
                 .map(|ms| UNIX_EPOCH + Duration::from_millis(ms as u64)),
             turn_id: game.turn_id,
+            draw_offer: game.draw_offer,
+            rated: game.rated,
         }
     }
 
@@ -140,6 +218,9 @@ impl Game {
                     .as_millis() as i64
             }),
             turn_id: self.turn_id,
+            options: self.options.clone(),
+            draw_offer: self.draw_offer,
+            rated: self.rated,
         }
     }
 
@@ -192,6 +273,7 @@ impl Tournament {
         let cfg = TournamentCfg {
             game_type: tourney.game_type,
             time_cfg: GameTimeCfg::from_ms(tourney.dur_per_move_ms, tourney.dur_sudden_death_ms),
+            game_options: tourney.game_options,
         };
         let instance = type_map[&*tourney.tournament_type].new(&*tourney.options, &cfg)?;
         Ok(Tournament {
@@ -229,19 +311,13 @@ impl Tournament {
             dur_sudden_death_ms: times.sudden_death_ms,
             started: self.started,
             options,
+            game_options: self.cfg.game_options.clone(),
             finished,
             winner,
         })
     }
 }
 
-pub type PgPool = Pool<ConnectionManager<PgConnection>>;
-
-pub fn init_db_pool(db_url: &str) -> Result<PgPool, PoolError> {
-    let manage = ConnectionManager::<PgConnection>::new(db_url);
-    Pool::builder().build(manage)
-}
-
 /// A message that a player's time in a game may have expired
 #[derive()]
 pub struct PlayerTimeExpiry {
@@ -252,22 +328,24 @@ pub struct PlayerTimeExpiry {
     pub user_id: UserId,
 }
 
-/// A database connection wrapper, which associates the database with functions to manipulate it
+/// A storage connection wrapper, which associates the storage with functions to manipulate it
 pub struct DBWrapper<'a, 'b, 'c> {
-    pool: &'c PgPool,
-    db: PooledConnection<ConnectionManager<PgConnection>>,
+    pool: &'c dyn StoragePool,
+    db: Box<dyn Storage + 'c>,
     game_type_map: &'a GameTypeMap,
     tournament_type_map: &'a TournamentTypeMap,
     game_update_callback: Box<dyn Fn(&Game, &[GamePlayer], &DBWrapper<'a, 'b, 'c>) + 'b>,
     tournament_update_callback:
         Box<dyn Fn(&Tournament, &[TournamentPlayer], &DBWrapper<'a, 'b, 'c>) + 'b>,
     time_expiry_channel: mpsc::UnboundedSender<PlayerTimeExpiry>,
+    // set while callbacks are held back, e.g. inside a transaction that may still be rolled back
+    callbacks_held: Cell<bool>,
 }
 
 impl DBWrapper<'_, '_, '_> {
-    /// Wrap an existing pg connection
-    pub fn from_pg_pool<'a, 'b, 'c>(
-        pool: &'c PgPool,
+    /// Wrap a connection from a storage pool
+    pub fn from_pool<'a, 'b, 'c>(
+        pool: &'c dyn StoragePool,
         game_type_map: &'a GameTypeMap,
         tournament_type_map: &'a TournamentTypeMap,
         game_update_callback: impl Fn(&Game, &[GamePlayer], &DBWrapper<'a, 'b, 'c>) + 'b,
@@ -283,6 +361,7 @@ impl DBWrapper<'_, '_, '_> {
             game_update_callback: Box::new(game_update_callback),
             tournament_update_callback: Box::new(tournament_update_callback),
             time_expiry_channel,
+            callbacks_held: Cell::new(false),
         })
     }
 
@@ -297,18 +376,29 @@ impl DBWrapper<'_, '_, '_> {
             game_update_callback: Box::new(|_, _, _| {}),
             tournament_update_callback: Box::new(|_, _, _| {}),
             time_expiry_channel: self.time_expiry_channel.clone(),
+            callbacks_held: Cell::new(false),
         })
     }
 
+    /// Run the game update callback, unless callbacks are held back
+    fn game_updated(&self, game: &Game, players: &[GamePlayer]) {
+        if !self.callbacks_held.get() {
+            (self.game_update_callback)(game, players, self);
+        }
+    }
+
+    /// Run the tournament update callback, unless callbacks are held back
+    fn tournament_updated(&self, tournament: &Tournament, players: &[TournamentPlayer]) {
+        if !self.callbacks_held.get() {
+            (self.tournament_update_callback)(tournament, players, self);
+        }
+    }
+
     // ---- Users ----
 
     /// Lookup a user with the given id
     pub fn find_user(&self, id: UserId) -> Result<User, Error> {
-        match users::dsl::users
-            .find(id)
-            .first::<User>(&self.db)
-            .optional()?
-        {
+        match self.db.find_user(id)? {
             Some(user) => Ok(user),
             None => Err(Error::NoSuchUser),
         }
@@ -317,11 +407,7 @@ impl DBWrapper<'_, '_, '_> {
     /// Lookup user by api key
     pub fn find_user_by_apikey(&self, key: &ApiKey) -> Result<User, Error> {
         let hashed = key.hash();
-        match users::dsl::users
-            .filter(users::dsl::api_key_hash.eq(hashed.to_string()))
-            .first::<User>(&self.db)
-            .optional()?
-        {
+        match self.db.find_user_by_apikey_hash(&hashed.to_string())? {
             Some(user) => Ok(user),
             None => Err(Error::InvalidApiKey),
         }
@@ -329,11 +415,7 @@ impl DBWrapper<'_, '_, '_> {
 
     /// Lookup user by email
     fn find_user_by_email(&self, email: &str) -> Result<User, Error> {
-        match users::dsl::users
-            .filter(users::dsl::email.eq(email))
-            .first::<User>(&self.db)
-            .optional()?
-        {
+        match self.db.find_user_by_email(email)? {
             Some(user) => Ok(user),
             None => Err(Error::NoSuchUser),
         }
@@ -348,13 +430,6 @@ impl DBWrapper<'_, '_, '_> {
         }
     }
 
-    /// Insert a new user into the db
-    fn insert_user(&self, user: NewUser) -> Result<User, Error> {
-        Ok(diesel::insert_into(users::table)
-            .values(&user)
-            .get_result::<User>(&self.db)?)
-    }
-
     /// Create a new user with given info
     pub fn new_user(&self, name: &str, email: &str, pass: &str) -> Result<User, Error> {
         // check for existing user
@@ -369,7 +444,7 @@ impl DBWrapper<'_, '_, '_> {
                     password_hash: Some(&*hashed_pass),
                     api_key_hash: &*ApiKey::new().hash().to_string(),
                 };
-                self.insert_user(user)
+                self.db.insert_user(&user)
             }
             Err(err) => Err(err),
         }
@@ -384,28 +459,29 @@ impl DBWrapper<'_, '_, '_> {
             password_hash: None,
             api_key_hash: &*ApiKey::new().hash().to_string(),
         };
-        self.insert_user(user)
+        self.db.insert_user(&user)
     }
 
     /// Update a user already in the db
     pub fn save_user(&self, user: &User) -> Result<(), Error> {
-        diesel::update(users::dsl::users.find(user.id))
-            .set(user)
-            .execute(&self.db)?;
-        Ok(())
+        self.db.save_user(user)
     }
 
     // ---- Games ----
-    /// Create a new game with the given type
+    /// Create a new game with the given type and options
     pub fn new_game(
         &self,
         game_type: &str,
+        options: &str,
         owner: UserId,
         time_cfg: GameTimeCfg,
         tournament_id: Option<TournamentId>,
     ) -> Result<DBGame, Error> {
-        if !self.game_type_map.contains_key(game_type) {
-            return Err(Error::NoSuchGameType(game_type.to_string()));
+        match self.game_type_map.get(game_type) {
+            None => return Err(Error::NoSuchGameType(game_type.to_string())),
+            Some(game_impl) => game_impl
+                .check_options(options)
+                .map_err(Error::InvalidGameOptions)?,
         }
         let game = NewDBGame {
             game_type,
@@ -419,19 +495,16 @@ impl DBWrapper<'_, '_, '_> {
             dur_sudden_death_ms: time_cfg.to_ms().sudden_death_ms,
             current_move_start_ms: None,
             turn_id: None,
+            options,
+            draw_offer: None,
+            rated: true,
         };
-        Ok(diesel::insert_into(games::table)
-            .values(&game)
-            .get_result::<DBGame>(&self.db)?)
+        self.db.insert_game(&game)
     }
 
     /// Load a DBGame from the database
     fn find_dbgame(&self, id: GameId) -> Result<DBGame, Error> {
-        match games::dsl::games
-            .find(id)
-            .first::<DBGame>(&self.db)
-            .optional()?
-        {
+        match self.db.find_game(id)? {
             Some(game) => Ok(game),
             None => Err(Error::NoSuchGame),
         }
@@ -444,11 +517,7 @@ impl DBWrapper<'_, '_, '_> {
 
     /// Load all players in a game
     pub fn find_game_players(&self, game_id: GameId) -> Result<Vec<GamePlayer>, Error> {
-        use game_players::dsl;
-        Ok(dsl::game_players
-            .filter(dsl::game_id.eq(game_id))
-            .order(dsl::id.asc())
-            .load::<GamePlayer>(&self.db)?)
+        self.db.find_game_players(game_id)
     }
 
     /// Convert a DBGame -> Game + GamePlayers
@@ -463,12 +532,7 @@ impl DBWrapper<'_, '_, '_> {
     }
 
     fn find_game_player(&self, game_id: GameId, user_id: UserId) -> Result<GamePlayer, Error> {
-        use game_players::dsl;
-        match dsl::game_players
-            .filter(dsl::game_id.eq(game_id).and(dsl::user_id.eq(user_id)))
-            .first::<GamePlayer>(&self.db)
-            .optional()?
-        {
+        match self.db.find_game_player(game_id, user_id)? {
             Some(player) => Ok(player),
             None => Err(Error::NotInGame),
         }
@@ -499,58 +563,45 @@ impl DBWrapper<'_, '_, '_> {
             score: None,
             waiting_for_move: false,
             time_ms: game.time.to_ms().sudden_death_ms,
+            moves_made: 0,
         };
-        let new_player = diesel::insert_into(game_players::table)
-            .values(&player)
-            .get_result::<GamePlayer>(&self.db)?;
+        let new_player = self.db.insert_game_player(&player)?;
 
         players.push(new_player);
-        (self.game_update_callback)(&game, &players, self);
+        self.game_updated(&game, &players);
         Ok(players.pop().unwrap())
     }
 
     /// Remove a user as a player in a game
     pub fn leave_game(&self, game_id: GameId, user_id: UserId) -> Result<(), Error> {
-        use game_players::dsl;
         let player = self.find_game_player(game_id, user_id)?;
         let (game, mut players) = self.find_game(game_id)?;
         if let Some(_) = game.instance {
             return Err(Error::GameAlreadyStarted);
         }
 
-        diesel::delete(dsl::game_players.filter(dsl::id.eq(player.id))).execute(&self.db)?;
+        self.db.delete_game_player(player.id)?;
 
         if let Some(index) = players.iter().position(|p| p.user_id == user_id) {
             players.remove(index);
         }
-        (self.game_update_callback)(&game, &players, self);
+        self.game_updated(&game, &players);
         Ok(())
     }
 
     /// Update a DBGame in the database
     fn save_dbgame(&self, game: &DBGame) -> Result<(), Error> {
-        diesel::update(games::dsl::games.find(game.id))
-            .set(game)
-            .execute(&self.db)?;
-        Ok(())
+        self.db.save_game(game)
     }
 
     /// Save a GamePlayer in the database
     pub fn save_game_player(&self, game_player: &GamePlayer) -> Result<(), Error> {
-        diesel::update(game_players::dsl::game_players.find(game_player.id))
-            .set(game_player)
-            .execute(&self.db)?;
-        Ok(())
+        self.db.save_game_player(game_player)
     }
 
     /// Find all games a user is in that are waiting for that user to play
     pub fn find_waiting_games_for_user(&self, user_id: UserId) -> Result<Vec<GameId>, Error> {
-        use game_players::dsl;
-        Ok(dsl::game_players
-            .filter(dsl::user_id.eq(user_id).and(dsl::waiting_for_move.eq(true)))
-            .order(dsl::id.asc())
-            .select(dsl::game_id)
-            .load::<GameId>(&self.db)?)
+        self.db.find_waiting_games_for_user(user_id)
     }
 
     /// Find the oldest game a user is in that is waiting for that user to play
@@ -558,13 +609,11 @@ impl DBWrapper<'_, '_, '_> {
         &self,
         user_id: UserId,
     ) -> Result<Option<GameId>, Error> {
-        use game_players::dsl;
-        Ok(dsl::game_players
-            .filter(dsl::user_id.eq(user_id).and(dsl::waiting_for_move.eq(true)))
-            .order(dsl::id.asc())
-            .select(dsl::game_id)
-            .first::<GameId>(&self.db)
-            .optional()?)
+        Ok(self
+            .db
+            .find_waiting_games_for_user(user_id)?
+            .first()
+            .cloned())
     }
 
     /// Update the waiting_for_move field on each game player (doesn't save game players)
@@ -609,7 +658,7 @@ impl DBWrapper<'_, '_, '_> {
                 self.save_game_player(player)?;
             }
         }
-        (self.game_update_callback)(game, players, self);
+        self.game_updated(game, players);
         Ok(())
     }
 
@@ -638,24 +687,65 @@ impl DBWrapper<'_, '_, '_> {
                 }
 
                 let till_expired = game.time.per_move + remaining;
-                let tx = self.time_expiry_channel.clone();
-                // start thread to wait for when this player's time will have fully expired
-                tokio::spawn((|| async move {
-                    tokio::time::sleep(till_expired).await;
-                    tx.unbounded_send(PlayerTimeExpiry {
+                self.schedule_expiry(
+                    PlayerTimeExpiry {
                         turn_id,
                         game_id,
                         user_id,
-                    })
-                    .unwrap_or_else(|e| eprintln!("Couldn't send game expiry information: {}", e));
-                })());
+                    },
+                    till_expired,
+                );
                 // mark when turn began
                 game.current_move_start = Some(SystemTime::now());
             }
         }
     }
 
-    /// Turn a game into a EndedGameInstance
+    /// Send an expiry for a turn once the given time has passed
+    fn schedule_expiry(&self, expiry: PlayerTimeExpiry, till_expired: Duration) {
+        let tx = self.time_expiry_channel.clone();
+        // start thread to wait for when this player's time will have fully expired
+        tokio::spawn((|| async move {
+            tokio::time::sleep(till_expired).await;
+            tx.unbounded_send(expiry)
+                .unwrap_or_else(|e| eprintln!("Couldn't send game expiry information: {}", e));
+        })());
+    }
+
+    /// Restart the expiry timers of games waiting for a move, such as after the server restarts. Turns whose
+    /// time already ran out expire immediately. Returns the number of games whose timers were restarted.
+    pub fn restore_game_timers(&self) -> Result<usize, Error> {
+        let game_ids = self.db.find_timed_games()?;
+
+        let mut restored = 0;
+        for game_id in game_ids {
+            let (game, players) = self.find_game(game_id)?;
+            let user_id = match game.instance.as_ref().map(|inst| inst.turn()) {
+                Some(GameTurn::Turn(user_id)) => user_id,
+                _ => continue,
+            };
+            let remaining = match players.iter().find(|p| p.user_id == user_id) {
+                Some(player) => Duration::from_millis(player.time_ms as u64),
+                None => continue,
+            };
+            let elapsed = game.elapsed_since_current_move().unwrap_or(Duration::ZERO);
+            let till_expired = (game.time.per_move + remaining)
+                .checked_sub(elapsed)
+                .unwrap_or(Duration::ZERO);
+            self.schedule_expiry(
+                PlayerTimeExpiry {
+                    turn_id: game.turn_id.unwrap(),
+                    game_id,
+                    user_id,
+                },
+                till_expired,
+            );
+            restored += 1;
+        }
+        Ok(restored)
+    }
+
+    /// Turn a game into a EndedGameInstance, and update the players' ratings if the game is rated
     pub fn end_game(
         &self,
         game: &mut Game,
@@ -664,10 +754,18 @@ impl DBWrapper<'_, '_, '_> {
         reason: String,
     ) -> Result<(), Error> {
         let inst = game.instance.as_ref().map(|i| &**i);
-        // update time elapsed during turn
         if let Some(inst) = inst {
-            if let GameTurn::Turn(user_id) = inst.turn() {
-                self.adjust_players_time(&game, &mut *players, user_id);
+            match inst.turn() {
+                // update time elapsed during turn
+                GameTurn::Turn(user_id) => self.adjust_players_time(&game, &mut *players, user_id),
+                // keep scores from a game that finished by its own rules
+                GameTurn::Finished => {
+                    if let Some(scores) = inst.scores() {
+                        for player in players.iter_mut() {
+                            player.score = scores.get(&player.user_id).cloned();
+                        }
+                    }
+                }
             }
         }
         // set game state to EndedGameInstance
@@ -677,11 +775,95 @@ impl DBWrapper<'_, '_, '_> {
             winner,
             reason,
         )));
+        // give the game a new turn id so pending move timers don't expire
+        self.start_game_timer(game, &*players);
+        game.draw_offer = None;
+        self.update_ratings(&game, &*players)?;
         self.save_game_and_players(&game, &mut *players)?;
-        self.handle_game_end(&game, &**game.instance.as_ref().unwrap())?;
+        self.handle_game_end(&game, &**game.instance.as_ref().unwrap(), &*players)?;
         Ok(())
     }
 
+    /// Load a game that is in progress and that the given user is playing in
+    fn find_active_game_for_player(
+        &self,
+        game_id: GameId,
+        user_id: UserId,
+    ) -> Result<GameAndPlayers, Error> {
+        let (game, players) = self.find_game(game_id)?;
+        if !players.iter().any(|p| p.user_id == user_id) {
+            return Err(Error::NotInGame);
+        }
+        match game.instance {
+            Some(ref inst) => {
+                if let GameTurn::Finished = inst.turn() {
+                    return Err(Error::GameNotInProgress);
+                }
+            }
+            None => return Err(Error::GameNotInProgress),
+        }
+        Ok((game, players))
+    }
+
+    /// Resign from a game as the given user, making the other player the winner
+    pub fn resign_game(&self, game_id: GameId, user_id: UserId) -> Result<(), Error> {
+        let (mut game, mut players) = self.find_active_game_for_player(game_id, user_id)?;
+
+        // TODO: handle winners for >2 player games
+        let mut winner = None;
+        if players.len() == 2 {
+            for player in players.iter() {
+                if player.user_id != user_id {
+                    winner = Some(player.user_id);
+                    break;
+                }
+            }
+        }
+        self.end_game(&mut game, &mut *players, winner, "Resigned".to_string())
+    }
+
+    /// Offer a draw as the given user. Offers can only be made on the user's turn, and are sent to the opponent with their next move.
+    pub fn offer_draw(&self, game_id: GameId, user_id: UserId) -> Result<(), Error> {
+        let (mut game, mut players) = self.find_active_game_for_player(game_id, user_id)?;
+        match game.draw_offer {
+            // offering a draw in response to an opponent's offer accepts it
+            Some(uid) if uid != user_id => {
+                self.end_game(&mut game, &mut *players, None, "Draw Agreed".to_string())
+            }
+            _ => {
+                match game.instance.as_ref().unwrap().turn() {
+                    GameTurn::Turn(uid) if uid == user_id => {}
+                    _ => return Err(Error::NotTurn),
+                }
+                game.draw_offer = Some(user_id);
+                self.save_game(&game)
+            }
+        }
+    }
+
+    /// Accept a draw offered by the given user's opponent, ending the game as a tie
+    pub fn accept_draw(&self, game_id: GameId, user_id: UserId) -> Result<(), Error> {
+        let (mut game, mut players) = self.find_active_game_for_player(game_id, user_id)?;
+        match game.draw_offer {
+            Some(uid) if uid != user_id => {
+                self.end_game(&mut game, &mut *players, None, "Draw Agreed".to_string())
+            }
+            _ => Err(Error::NoDrawOffer),
+        }
+    }
+
+    /// Decline a draw offered by the given user's opponent
+    pub fn decline_draw(&self, game_id: GameId, user_id: UserId) -> Result<(), Error> {
+        let (mut game, _) = self.find_active_game_for_player(game_id, user_id)?;
+        match game.draw_offer {
+            Some(uid) if uid != user_id => {
+                game.draw_offer = None;
+                self.save_game(&game)
+            }
+            _ => Err(Error::NoDrawOffer),
+        }
+    }
+
     /// Start a game as the given user
     pub fn start_game(&self, game_id: GameId, user_id: UserId) -> Result<(), Error> {
         let (mut game, players) = self.find_game(game_id)?;
@@ -696,7 +878,7 @@ impl DBWrapper<'_, '_, '_> {
             return Err(Error::GameAlreadyStarted);
         }
 
-        let new_instance = self.game_type_map[&*game.game_type].new(&player_ids);
+        let new_instance = self.game_type_map[&*game.game_type].new(&player_ids, &game.options);
 
         match new_instance {
             Some(new_instance) => {
@@ -728,20 +910,28 @@ impl DBWrapper<'_, '_, '_> {
         }
     }
 
-    fn handle_game_end(&self, game: &Game, game_inst: &dyn GameInstance) -> Result<(), Error> {
+    fn handle_game_end(
+        &self,
+        game: &Game,
+        game_inst: &dyn GameInstance,
+        game_players: &[GamePlayer],
+    ) -> Result<(), Error> {
         if let Some(id) = game.tournament_id {
             let mut tournament = self.find_tournament(id)?;
             let mut players = self.find_tournament_players(id)?;
+            let in_game = |player: &TournamentPlayer| {
+                game_players.iter().any(|p| p.user_id == player.user_id)
+            };
 
             match game_inst.end_state() {
                 Some(GameState::Tie) => {
-                    for player in &mut players {
+                    for player in players.iter_mut().filter(|p| in_game(p)) {
                         player.tie += 1
                     }
                 }
                 Some(GameState::Win(winner)) => {
-                    for player in &mut players {
-                        if player.id == winner {
+                    for player in players.iter_mut().filter(|p| in_game(p)) {
+                        if player.user_id == winner {
                             player.win += 1
                         } else {
                             player.loss += 1
@@ -750,14 +940,9 @@ impl DBWrapper<'_, '_, '_> {
                 }
                 _ => {}
             }
-            self.save_tournament_players(&*players)?;
-            tournament.instance.advance(
-                tournament.id,
-                tournament.owner_id,
-                &tournament.cfg,
-                &*players,
-                &self,
-            )?;
+            self.save_tournament(&tournament, &*players)?;
+            self.tournament_updated(&tournament, &*players);
+            self.advance_tournament(&mut tournament, &*players)?;
         }
 
         Ok(())
@@ -766,41 +951,337 @@ impl DBWrapper<'_, '_, '_> {
     /// Make a move in a game as the given user
     pub fn make_move(&self, game_id: GameId, user_id: UserId, play: &str) -> Result<(), Error> {
         let (mut game, mut players) = self.find_game(game_id)?;
-        let move_res = if let Some(ref mut inst) = game.instance {
-            match inst.turn() {
+        match game.instance {
+            Some(ref mut inst) => match inst.turn() {
                 GameTurn::Turn(uid) if uid == user_id => {
                     // apply move
                     inst.make_move(user_id, play)
                         .map_err(|e| Error::InvalidMove(e))?;
-                    // subtract elapsed time from player
-                    self.adjust_players_time(&game, &mut *players, user_id);
-                    // start timer for next move
-                    self.start_game_timer(&mut game, &*players);
-                    self.save_game_and_players(&game, &mut *players)?;
-                    Ok(())
                 }
-                _ => Err(Error::NotTurn),
+                _ => return Err(Error::NotTurn),
+            },
+            None => return Err(Error::NotTurn),
+        }
+        // a move by the opponent of a player who offered a draw declines the offer
+        if matches!(game.draw_offer, Some(uid) if uid != user_id) {
+            game.draw_offer = None;
+        }
+        // subtract elapsed time from player
+        let time_spent = game.elapsed_since_current_move().unwrap_or(Duration::ZERO);
+        self.adjust_players_time(&game, &mut *players, user_id);
+        // count the move, so info reported about it can be matched to it
+        for player in players.iter_mut().filter(|p| p.user_id == user_id) {
+            player.moves_made += 1;
+        }
+        // record the move in the game's history
+        let inst = game.instance.as_ref().unwrap();
+        let player = players.iter().find(|p| p.user_id == user_id).unwrap();
+        self.db.insert_game_move(&NewGameMove {
+            game_id,
+            ply: players.iter().map(|p| p.moves_made).sum(),
+            user_id,
+            play: inst.last_move().unwrap_or_else(|| play.to_string()),
+            time_spent_ms: time_spent.as_millis() as i64,
+            clock_ms: player.time_ms,
+            played_at_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or(Duration::ZERO)
+                .as_millis() as i64,
+            eval: None,
+        })?;
+        // start timer for next move
+        self.start_game_timer(&mut game, &*players);
+
+        let inst = game.instance.as_ref().unwrap();
+        if let GameTurn::Finished = inst.turn() {
+            // if the game ended for a reason that isn't evident from its state (such as a draw by repetition), record the reason
+            if let Some(reason) = inst.end_reason() {
+                let winner = match inst.end_state() {
+                    Some(GameState::Win(uid)) => Some(uid),
+                    _ => None,
+                };
+                return self.end_game(&mut game, &mut *players, winner, reason);
             }
-        } else {
-            Err(Error::NotTurn)
-        };
+            self.update_ratings(&game, &*players)?;
+        }
+        self.save_game_and_players(&game, &mut *players)?;
         // if the game just ended and is in a tournament, adjust scores + advance tournament
-        if let Some(ref inst) = game.instance {
-            if let GameTurn::Finished = inst.turn() {
-                self.handle_game_end(&game, &**inst)?;
+        let inst = game.instance.as_ref().unwrap();
+        if let GameTurn::Finished = inst.turn() {
+            self.handle_game_end(&game, &**inst, &*players)?;
+        }
+        Ok(())
+    }
+
+    /// Create a game from PGN, owned by the given user, with white and black as its players. The owner has to be
+    /// one of the players, or own a tournament both players are in. Imported games are unrated: finished games are
+    /// ended with the PGN's result, and unfinished games continue from their last move.
+    pub fn import_game(
+        &self,
+        owner: UserId,
+        white: UserId,
+        black: UserId,
+        time_cfg: GameTimeCfg,
+        pgn_game: &PgnGame,
+    ) -> Result<GameId, Error> {
+        self.find_user(white)?;
+        self.find_user(black)?;
+        if white == black {
+            return Err(Error::SamePlayers);
+        }
+        if owner != white && owner != black && !self.owns_tournament_with(owner, white, black)? {
+            return Err(Error::CantImportGame);
+        }
+
+        // replay the game before creating it, so invalid games aren't saved
+        let game_impl = self
+            .game_type_map
+            .get(pgn_game.game_type)
+            .ok_or_else(|| Error::NoSuchGameType(pgn_game.game_type.to_string()))?;
+        let mut instance = game_impl
+            .new(&[white, black], &pgn_game.options)
+            .ok_or(Error::InvalidNumberOfPlayers)?;
+        let mut moves_made = HashMap::new();
+        for play in pgn_game.moves.iter() {
+            match instance.turn() {
+                GameTurn::Turn(uid) => {
+                    instance.make_move(uid, play).map_err(Error::InvalidMove)?;
+                    *moves_made.entry(uid).or_insert(0) += 1;
+                }
+                GameTurn::Finished => {
+                    return Err(Error::InvalidPgn(
+                        "moves continue after the game ended".to_string(),
+                    ))
+                }
             }
         }
-        move_res
+        let winner = pgn_game.winner.map(|player| match player {
+            chess::Player::White => white,
+            chess::Player::Black => black,
+        });
+        let finished_by_rules = instance.turn() == GameTurn::Finished;
+        if finished_by_rules && pgn_game.finished {
+            let end_state = match winner {
+                Some(uid) => GameState::Win(uid),
+                None => GameState::Tie,
+            };
+            if instance.end_state() != Some(end_state) {
+                return Err(Error::InvalidPgn(
+                    "the result doesn't match the final position".to_string(),
+                ));
+            }
+        }
+
+        // create the game in a transaction, so it isn't left half imported if a step fails. Callbacks are held back
+        // until it commits, so nothing is published about a game that may be rolled back.
+        let mut game_id = 0;
+        self.callbacks_held.set(true);
+        let result = self.db.transaction(Box::new(|| {
+            let new_game_id = self
+                .new_game(pgn_game.game_type, &pgn_game.options, owner, time_cfg, None)?
+                .id;
+            game_id = new_game_id;
+            self.join_game(new_game_id, white)?;
+            self.join_game(new_game_id, black)?;
+            let (mut game, mut players) = self.find_game(new_game_id)?;
+            for player in players.iter_mut() {
+                let clock = if player.user_id == white {
+                    pgn_game.clocks[0]
+                } else {
+                    pgn_game.clocks[1]
+                };
+                if let Some(time_ms) = clock {
+                    player.time_ms = time_ms;
+                }
+                player.moves_made = moves_made.get(&player.user_id).cloned().unwrap_or(0);
+            }
+            let end_reason = instance.end_reason();
+            game.instance = Some(instance);
+            game.rated = false;
+
+            if finished_by_rules {
+                // record reasons not evident from the game's state, as when a move ends the game
+                match end_reason {
+                    Some(reason) => {
+                        let winner = match game.instance.as_ref().unwrap().end_state() {
+                            Some(GameState::Win(uid)) => Some(uid),
+                            _ => None,
+                        };
+                        self.end_game(&mut game, &mut *players, winner, reason)?
+                    }
+                    None => self.save_game_and_players(&game, &mut *players)?,
+                }
+            } else if pgn_game.finished {
+                let reason = pgn_game
+                    .termination
+                    .clone()
+                    .unwrap_or_else(|| "Imported".to_string());
+                self.end_game(&mut game, &mut *players, winner, reason)?;
+            } else {
+                // start timer for the next move
+                self.start_game_timer(&mut game, &*players);
+                self.save_game_and_players(&game, &mut *players)?;
+            }
+            Ok(())
+        }));
+        self.callbacks_held.set(false);
+        result?;
+        let (game, players) = self.find_game(game_id)?;
+        self.game_updated(&game, &players);
+        Ok(game_id)
+    }
+
+    /// Check if a user owns a tournament that both players are in
+    fn owns_tournament_with(
+        &self,
+        owner: UserId,
+        white: UserId,
+        black: UserId,
+    ) -> Result<bool, Error> {
+        for tourney_player in self.db.find_user_tournament_players(white)? {
+            let tourney = self.find_db_tournament(tourney_player.tournament_id)?;
+            if tourney.owner_id == owner
+                && self.db.find_tournament_player(tourney.id, black)?.is_some()
+            {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Record info a user reports about their last move in a game (such as their engine's evaluation). Info reported
+    /// more than once for the same move is merged. Returns None if the info had none of the recorded keys.
+    pub fn report_move_info(
+        &self,
+        game_id: GameId,
+        user_id: UserId,
+        info: &str,
+    ) -> Result<Option<MoveInfo>, Error> {
+        let player = self.find_game_player(game_id, user_id)?;
+        if player.moves_made == 0 {
+            return Err(Error::InvalidInfo(
+                "you haven't made a move in that game".to_string(),
+            ));
+        }
+        let new_info = NewMoveInfo::parse(game_id, user_id, player.moves_made, info)
+            .map_err(Error::InvalidInfo)?;
+        if new_info.is_empty() {
+            return Ok(None);
+        }
+        // keep the eval with the move in the game's history
+        if let Some(eval) = new_info.eval {
+            self.db.set_last_move_eval(game_id, user_id, eval)?;
+        }
+        Ok(Some(self.db.save_move_info(&new_info)?))
+    }
+
+    /// Load the moves made in a game, in the order they were made. Moves from imported PGN aren't included.
+    pub fn find_game_moves(&self, game_id: GameId) -> Result<Vec<GameMove>, Error> {
+        self.db.find_game_moves(game_id)
+    }
+
+    /// Load all info reported about moves in a game
+    pub fn find_move_infos(&self, game_id: GameId) -> Result<Vec<MoveInfo>, Error> {
+        self.db.find_move_infos(game_id)
+    }
+
+    // ---- Ratings ----
+
+    /// Load a user's rating in a game type. Users who haven't played a rated game have the default rating.
+    pub fn find_rating(&self, user_id: UserId, game_type: &str) -> Result<Glicko, Error> {
+        Ok(match self.db.find_rating(user_id, game_type)? {
+            Some(rating) => Glicko {
+                rating: rating.rating,
+                deviation: rating.deviation,
+                volatility: rating.volatility,
+            },
+            None => Glicko::default(),
+        })
+    }
+
+    /// Load the changes in a user's rating in a game type, in the order they were made
+    pub fn find_rating_changes(
+        &self,
+        user_id: UserId,
+        game_type: &str,
+    ) -> Result<Vec<RatingChange>, Error> {
+        self.db.find_rating_changes(user_id, game_type)
+    }
+
+    /// Update the ratings of the players in a finished game, recording each change. Only rated two player games that
+    /// ended in a win or tie are rated, and each game is rated as its own rating period.
+    fn update_ratings(&self, game: &Game, players: &[GamePlayer]) -> Result<(), Error> {
+        if !game.rated {
+            return Ok(());
+        }
+        let end_state = match game.instance.as_ref().and_then(|inst| inst.end_state()) {
+            Some(state @ GameState::Win(_)) | Some(state @ GameState::Tie) => state,
+            _ => return Ok(()),
+        };
+        if players.len() != 2 {
+            return Ok(());
+        }
+        let old_ratings = players
+            .iter()
+            .map(|p| self.find_rating(p.user_id, &game.game_type))
+            .collect::<Result<Vec<Glicko>, Error>>()?;
+        let changed_at_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or(Duration::ZERO)
+            .as_millis() as i64;
+        for (index, player) in players.iter().enumerate() {
+            let score = match end_state {
+                GameState::Win(uid) if uid == player.user_id => 1.0,
+                GameState::Win(_) => 0.0,
+                _ => 0.5,
+            };
+            let new = old_ratings[index].update(&[(old_ratings[1 - index], score)]);
+            self.db.save_rating(&NewRating {
+                user_id: player.user_id,
+                game_type: game.game_type.clone(),
+                rating: new.rating,
+                deviation: new.deviation,
+                volatility: new.volatility,
+            })?;
+            self.db.insert_rating_change(&NewRatingChange {
+                user_id: player.user_id,
+                game_type: game.game_type.clone(),
+                game_id: game.id,
+                rating: new.rating,
+                deviation: new.deviation,
+                volatility: new.volatility,
+                changed_at_ms,
+            })?;
+        }
+        Ok(())
+    }
+
+    // ---- Statistics ----
+
+    /// Find the totals of a user's finished games in each game type they've played, in order of game type
+    pub fn find_user_stats(&self, user_id: UserId) -> Result<Vec<UserStats>, Error> {
+        self.db.find_user_stats(user_id)
+    }
+
+    /// Find the totals of finished games for the users with the highest ratings in a game type, skipping the first
+    /// `offset` users and returning at most `limit` (which is capped at `MAX_LEADERBOARD_LIMIT`)
+    pub fn find_leaderboard(
+        &self,
+        game_type: &str,
+        offset: u32,
+        limit: u32,
+    ) -> Result<Vec<UserStats>, Error> {
+        self.db.find_leaderboard(
+            game_type,
+            offset as i64,
+            limit.min(MAX_LEADERBOARD_LIMIT) as i64,
+        )
     }
 
     // ----- Tournaments -----
     /// Load a DBTournament
     fn find_db_tournament(&self, id: TournamentId) -> Result<DBTournament, Error> {
-        match tournaments::dsl::tournaments
-            .find(id)
-            .first::<DBTournament>(&self.db)
-            .optional()?
-        {
+        match self.db.find_tournament(id)? {
             Some(t) => Ok(t),
             None => Err(Error::NoSuchTournament),
         }
@@ -816,17 +1297,11 @@ impl DBWrapper<'_, '_, '_> {
 
     /// Save a tournament
     fn save_db_tournament(&self, tourney: &DBTournament) -> Result<(), Error> {
-        diesel::update(tournaments::dsl::tournaments.find(tourney.id))
-            .set(tourney)
-            .execute(&self.db)?;
-        Ok(())
+        self.db.save_tournament(tourney)
     }
 
     pub fn save_tournament_player(&self, player: &TournamentPlayer) -> Result<(), Error> {
-        diesel::update(tournament_players::dsl::tournament_players.find(player.id))
-            .set(player)
-            .execute(&self.db)?;
-        Ok(())
+        self.db.save_tournament_player(player)
     }
 
     pub fn save_tournament_players(&self, players: &[TournamentPlayer]) -> Result<(), Error> {
@@ -851,11 +1326,7 @@ impl DBWrapper<'_, '_, '_> {
         &self,
         id: TournamentId,
     ) -> Result<Vec<TournamentPlayer>, Error> {
-        use tournament_players::dsl;
-        Ok(dsl::tournament_players
-            .filter(dsl::tournament_id.eq(id))
-            .order(dsl::id.asc())
-            .load::<TournamentPlayer>(&self.db)?)
+        self.db.find_tournament_players(id)
     }
 
     /// Load a user in a tournament
@@ -864,16 +1335,7 @@ impl DBWrapper<'_, '_, '_> {
         tourney_id: TournamentId,
         user_id: UserId,
     ) -> Result<TournamentPlayer, Error> {
-        use tournament_players::dsl;
-        match dsl::tournament_players
-            .filter(
-                dsl::tournament_id
-                    .eq(tourney_id)
-                    .and(dsl::user_id.eq(user_id)),
-            )
-            .first::<TournamentPlayer>(&self.db)
-            .optional()?
-        {
+        match self.db.find_tournament_player(tourney_id, user_id)? {
             Some(player) => Ok(player),
             None => Err(Error::NoSuchUser),
         }
@@ -887,11 +1349,18 @@ impl DBWrapper<'_, '_, '_> {
         cfg: &TournamentCfg,
         options: &str,
     ) -> Result<DBTournament, Error> {
-        if !self.tournament_type_map.contains_key(tournament_type) {
-            return Err(Error::NoSuchTournamentType);
+        match self.tournament_type_map.get(tournament_type) {
+            None => return Err(Error::NoSuchTournamentType),
+            // check the options can be parsed
+            Some(tourney_impl) => {
+                tourney_impl.new(options, cfg)?;
+            }
         }
-        if !self.game_type_map.contains_key(&*cfg.game_type) {
-            return Err(Error::NoSuchGameType(cfg.game_type.clone()));
+        match self.game_type_map.get(&*cfg.game_type) {
+            None => return Err(Error::NoSuchGameType(cfg.game_type.clone())),
+            Some(game_impl) => game_impl
+                .check_options(&*cfg.game_options)
+                .map_err(Error::InvalidGameOptions)?,
         }
         let times = cfg.time_cfg.to_ms();
         let tourney = NewDBTournament {
@@ -904,10 +1373,9 @@ impl DBWrapper<'_, '_, '_> {
             finished: false,
             winner: None,
             options,
+            game_options: &*cfg.game_options,
         };
-        Ok(diesel::insert_into(tournaments::table)
-            .values(&tourney)
-            .get_result::<DBTournament>(&self.db)?)
+        self.db.insert_tournament(&tourney)
     }
 
     /// Join a tournament
@@ -925,13 +1393,11 @@ impl DBWrapper<'_, '_, '_> {
             loss: 0,
             tie: 0,
         };
-        diesel::insert_into(tournament_players::table)
-            .values(&new_player)
-            .execute(&self.db)?;
+        self.db.insert_tournament_player(&new_player)?;
 
         let tourney = self.find_tournament(id)?;
         let players = self.find_tournament_players(id)?;
-        (self.tournament_update_callback)(&tourney, &*players, &self);
+        self.tournament_updated(&tourney, &*players);
         Ok(())
     }
 
@@ -943,12 +1409,11 @@ impl DBWrapper<'_, '_, '_> {
         }
 
         let existing = self.find_tournament_player(id, user_id)?;
-        diesel::delete(tournament_players::dsl::tournament_players.find(existing.id))
-            .execute(&self.db)?;
+        self.db.delete_tournament_player(existing.id)?;
 
         let players = self.find_tournament_players(id)?;
         let t = Tournament::from_db_tournament(tourney, self.tournament_type_map)?;
-        (self.tournament_update_callback)(&t, &*players, &self);
+        self.tournament_updated(&t, &*players);
 
         Ok(())
     }
@@ -964,27 +1429,44 @@ impl DBWrapper<'_, '_, '_> {
             return Err(Error::GameAlreadyStarted);
         }
         println!("DEBUG good to start");
+        let players = self.find_tournament_players(id)?;
+        tourney.instance.check_start(&*players)?;
         // mark started + save tournament
         tourney.started = true;
-        let players = self.find_tournament_players(id)?;
         println!("DEBUG start save");
         self.save_tournament(&tourney, &*players)?;
         println!("DEBUG end save");
         println!("DEBUG start callback");
-        (self.tournament_update_callback)(&tourney, &*players, &self);
+        self.tournament_updated(&tourney, &*players);
         println!("DEBUG end callback");
         // trigger game creation + starting
+        self.advance_tournament(&mut tourney, &*players)?;
+        println!("DEBUG end");
+        Ok(())
+    }
+
+    /// Advance a tournament. If advancing changed the tournament's state (such as pairing a new round), save and
+    /// publish the tournament.
+    fn advance_tournament(
+        &self,
+        tourney: &mut Tournament,
+        players: &[TournamentPlayer],
+    ) -> Result<(), Error> {
+        let state = |t: &Tournament| format!("{}", Fmt(|f| t.instance.serialize(&t.cfg, f)));
+        let before = state(tourney);
         tourney
             .instance
-            .advance(tourney.id, tourney.owner_id, &tourney.cfg, &*players, &self)?;
-        println!("DEBUG end");
+            .advance(tourney.id, tourney.owner_id, &tourney.cfg, players, &self)?;
+        if state(tourney) != before {
+            let players = self.find_tournament_players(tourney.id)?;
+            self.save_tournament(tourney, &*players)?;
+            self.tournament_updated(tourney, &*players);
+        }
         Ok(())
     }
 
     /// Find all games in a tournament
     pub fn find_tournament_games(&self, id: TournamentId) -> Result<Vec<DBGame>, Error> {
-        Ok(games::dsl::games
-            .filter(games::dsl::tournament_id.eq(id))
-            .load::<DBGame>(&self.db)?)
+        self.db.find_tournament_games(id)
     }
 }
@@ -31,12 +31,15 @@ pub enum Error {
     NoSuchGame,
     AlreadyInGame,
     GameAlreadyStarted,
+    GameNotInProgress,
+    NoDrawOffer,
     NotTurn,
     DontOwnGame,
     InvalidNumberOfPlayers,
     NotInGame,
     InvalidNumberId,
     NoSuchGameType(String),
+    InvalidGameOptions(String),
     InvalidProtocolVersion,
     InvalidMove(String),
     InvalidProtocolForCommand {
@@ -45,6 +48,12 @@ pub enum Error {
     },
     NoSuchTournament,
     NoSuchTournamentType,
+    InvalidTournamentOptions(String),
+    PgnUnavailable,
+    InvalidPgn(String),
+    CantImportGame,
+    SamePlayers,
+    InvalidInfo(String),
 }
 
 impl PartialEq for Error {
@@ -131,6 +140,14 @@ impl PartialEq for Error {
                 GameAlreadyStarted => true,
                 _ => false,
             },
+            GameNotInProgress => match other {
+                GameNotInProgress => true,
+                _ => false,
+            },
+            NoDrawOffer => match other {
+                NoDrawOffer => true,
+                _ => false,
+            },
             NotTurn => match other {
                 NotTurn => true,
                 _ => false,
@@ -151,6 +168,10 @@ impl PartialEq for Error {
                 NoSuchGameType(other_type) => *game_type == *other_type,
                 _ => false,
             },
+            InvalidGameOptions(error) => match other {
+                InvalidGameOptions(other_error) => *error == *other_error,
+                _ => false,
+            },
             InvalidProtocolVersion => match other {
                 InvalidProtocolVersion => true,
                 _ => false,
@@ -174,6 +195,30 @@ impl PartialEq for Error {
                 NoSuchTournamentType => true,
                 _ => false,
             },
+            InvalidTournamentOptions(error) => match other {
+                InvalidTournamentOptions(other_error) => *error == *other_error,
+                _ => false,
+            },
+            PgnUnavailable => match other {
+                PgnUnavailable => true,
+                _ => false,
+            },
+            InvalidPgn(error) => match other {
+                InvalidPgn(other_error) => *error == *other_error,
+                _ => false,
+            },
+            CantImportGame => match other {
+                CantImportGame => true,
+                _ => false,
+            },
+            SamePlayers => match other {
+                SamePlayers => true,
+                _ => false,
+            },
+            InvalidInfo(error) => match other {
+                InvalidInfo(other_error) => *error == *other_error,
+                _ => false,
+            },
         }
     }
 }
@@ -245,11 +290,14 @@ impl fmt::Display for Error {
             NoSuchGame => write!(f, "no such game"),
             AlreadyInGame => write!(f, "you are already in that game"),
             GameAlreadyStarted => write!(f, "that game has already started"),
+            GameNotInProgress => write!(f, "that game isn't in progress"),
+            NoDrawOffer => write!(f, "your opponent hasn't offered a draw in that game"),
             DontOwnGame => write!(f, "you aren't the owner of that game"),
             InvalidNumberOfPlayers => write!(f, "invalid number of players joined to start game"),
             NotInGame => write!(f, "you aren't a player in that game"),
             InvalidNumberId => write!(f, "malformed id or number"),
             NoSuchGameType(game_type) => write!(f, "unsupported game type: {}", *game_type),
+            InvalidGameOptions(error) => write!(f, "invalid game options: {}", *error),
             InvalidProtocolVersion => write!(f, "invalid protocol version"),
             NotTurn => write!(f, "it is not your turn to move in that game"),
             InvalidMove(error) => write!(f, "invalid move: {}", *error),
@@ -260,6 +308,15 @@ impl fmt::Display for Error {
             ),
             NoSuchTournament => write!(f, "no such tournament"),
             NoSuchTournamentType => write!(f, "no such tournament type"),
+            InvalidTournamentOptions(error) => write!(f, "invalid tournament options: {}", *error),
+            PgnUnavailable => write!(f, "that game can't be exported as pgn"),
+            InvalidPgn(error) => write!(f, "invalid pgn: {}", *error),
+            CantImportGame => write!(
+                f,
+                "you can only import your own games, or games between players in your tournament"
+            ),
+            SamePlayers => write!(f, "white and black must be different players"),
+            InvalidInfo(error) => write!(f, "invalid info: {}", *error),
         }
     }
 }
@@ -1,59 +1,396 @@
-use crate::games::{GameInstance, GameScore, GameState, GameTurn, GameType};
+use crate::games::{parse_options, GameInstance, GameScore, GameState, GameTurn, GameType};
 use crate::models::UserId;
 use chess;
+use rand::{thread_rng, Rng};
 use std::collections::HashMap;
 use std::fmt;
 
-// chess board starting position
-static DEFAULT_BOARD: &'static str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+/// chess board starting position
+pub static DEFAULT_BOARD: &'static str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
 
-#[derive(Debug)]
-pub struct ChessGame();
+// number of chess960 start positions
+const CHESS960_POSITIONS: usize = 960;
 
-impl GameType for ChessGame {
-    fn deserialize(&self, data: &str, players: &[UserId]) -> Option<Box<dyn GameInstance>> {
-        if players.len() != 2 {
-            return None;
+/// Maps a `seed=` option to a chess960 start position index. Uses one round of splitmix64 rather than an rng from
+/// `rand`, so the mapping stays the same across `rand` releases.
+fn chess960_index_from_seed(seed: u64) -> usize {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+    (z % CHESS960_POSITIONS as u64) as usize
+}
+
+// squares (counting only empty squares) the knights are placed on for each chess960 knight index
+const CHESS960_KNIGHTS: [(usize, usize); 10] = [
+    (0, 1),
+    (0, 2),
+    (0, 3),
+    (0, 4),
+    (1, 2),
+    (1, 3),
+    (1, 4),
+    (2, 3),
+    (2, 4),
+    (3, 4),
+];
+
+// bitboard of the light squares on the board
+const LIGHT_SQUARES: u64 = 0x55aa55aa55aa55aa;
+
+/// Which draw rules automatically end a game (besides stalemate)
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DrawRule {
+    /// No automatic draws (the original competition ruleset)
+    None,
+    /// Threefold repetition and the fifty move rule
+    Threefold,
+    /// Fivefold repetition and the seventy-five move rule
+    Fivefold,
+}
+
+impl DrawRule {
+    fn from_str(rule: &str) -> Result<DrawRule, String> {
+        match rule {
+            "none" => Ok(DrawRule::None),
+            "threefold" => Ok(DrawRule::Threefold),
+            "fivefold" => Ok(DrawRule::Fivefold),
+            _ => Err(format!("unknown draw rule: {}", rule)),
         }
+    }
 
-        // serialization format: fen,[move0,move1,move2]
-        let clean_data = data.replace('[', "").replace(']', "");
-        let mut components = clean_data.split(',');
-        if let Some(fen) = components.next() {
-            let mut moves = Vec::new();
-            for move_str in components {
-                if move_str.len() > 0 {
-                    moves.push(move_str.to_string())
-                }
+    /// Number of times a position must occur to end the game, and the name of the rule
+    fn repetition_limit(self) -> Option<(u32, &'static str)> {
+        match self {
+            DrawRule::None => None,
+            DrawRule::Threefold => Some((3, "Threefold Repetition")),
+            DrawRule::Fivefold => Some((5, "Fivefold Repetition")),
+        }
+    }
+
+    /// Number of halfmoves without a capture or pawn move needed to end the game, and the name of the rule
+    fn halfmove_limit(self) -> Option<(u32, &'static str)> {
+        match self {
+            DrawRule::None => None,
+            DrawRule::Threefold => Some((100, "Fifty Move Rule")),
+            DrawRule::Fivefold => Some((150, "Seventy-Five Move Rule")),
+        }
+    }
+}
+
+/// Options for a chess game, given as `draw_rule=<none|threefold|fivefold>`, `insufficient_material=<on|off>`,
+/// `fen=<start position>`, and `book_plies=<number>`
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct ChessOptions {
+    draw_rule: DrawRule,
+    // if games are drawn when neither player can checkmate (independent of the draw rule)
+    insufficient_material: bool,
+    // position to start from (in FEN) instead of the standard start position
+    start: Option<String>,
+    // number of halfmoves to play from the opening book before the game starts
+    book_plies: u32,
+}
+
+impl ChessOptions {
+    fn new() -> ChessOptions {
+        ChessOptions {
+            draw_rule: DrawRule::None,
+            insufficient_material: true,
+            start: None,
+            book_plies: 0,
+        }
+    }
+
+    /// Set one option from an options string
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "draw_rule" => self.draw_rule = DrawRule::from_str(value)?,
+            "insufficient_material" => match value {
+                "on" => self.insufficient_material = true,
+                "off" => self.insufficient_material = false,
+                _ => return Err(format!("invalid insufficient_material value: {}", value)),
+            },
+            // accepts FEN or EPD, stored as FEN
+            "fen" => match normalize_fen(value) {
+                Some(fen) => self.start = Some(fen),
+                None => return Err(format!("invalid start position: {}", value)),
+            },
+            "book_plies" => match value.parse::<u32>() {
+                Ok(plies) => self.book_plies = plies,
+                Err(_) => return Err(format!("invalid number of book plies: {}", value)),
+            },
+            _ => return Err(format!("unknown chess option: {}", key)),
+        }
+        Ok(())
+    }
+
+    fn parse(options: &str) -> Result<ChessOptions, String> {
+        let mut res = ChessOptions::new();
+        for (key, value) in parse_options(options)? {
+            res.set(key, value)?;
+        }
+        Ok(res)
+    }
+}
+
+/// Options for a chess960 game. Takes the chess options, plus the start position as either `index=<0-959>` or `seed=<number>`.
+/// If neither is given, the start position is picked at random.
+#[derive(Debug, PartialEq, Eq, Clone)]
+struct Chess960Options {
+    chess: ChessOptions,
+    index: Option<usize>,
+}
+
+impl Chess960Options {
+    fn parse(options: &str) -> Result<Chess960Options, String> {
+        let mut res = Chess960Options {
+            chess: ChessOptions::new(),
+            index: None,
+        };
+        for (key, value) in parse_options(options)? {
+            match key {
+                "index" => match value.parse::<usize>() {
+                    Ok(index) if index < CHESS960_POSITIONS => res.index = Some(index),
+                    _ => return Err(format!("invalid chess960 index: {}", value)),
+                },
+                "seed" => match value.parse::<u64>() {
+                    Ok(seed) => res.index = Some(chess960_index_from_seed(seed)),
+                    Err(_) => return Err(format!("invalid chess960 seed: {}", value)),
+                },
+                // polyglot books only cover the standard start position
+                "book_plies" => return Err("chess960 games can't use an opening book".to_string()),
+                _ => res.chess.set(key, value)?,
             }
+        }
+        Ok(res)
+    }
+}
 
-            Some(Box::new(ChessGameInstance {
-                board: chess::Board::new(fen),
-                moves,
-                white: players[0],
-                black: players[1],
-            }))
-        } else {
+/// Get the FEN of a chess960 start position, numbered 0-959 as in Scharnagl's numbering scheme (518 is the standard start position).
+/// Castling rights are given in Shredder-FEN.
+pub fn chess960_start_fen(index: usize) -> String {
+    // place a piece on the nth empty square of the back rank
+    fn place(rank: &mut [char; 8], nth: usize, piece: char) {
+        let square = rank.iter_mut().filter(|c| **c == ' ').nth(nth).unwrap();
+        *square = piece;
+    }
+
+    let mut rank = [' '; 8];
+    let mut n = index % CHESS960_POSITIONS;
+    // one bishop on a light square, one on a dark square
+    rank[(n % 4) * 2 + 1] = 'b';
+    n /= 4;
+    rank[(n % 4) * 2] = 'b';
+    n /= 4;
+    place(&mut rank, n % 6, 'q');
+    n /= 6;
+    // place the second knight first so the first knight's square isn't shifted
+    let (first_knight, second_knight) = CHESS960_KNIGHTS[n];
+    place(&mut rank, second_knight, 'n');
+    place(&mut rank, first_knight, 'n');
+    // the king goes between the rooks
+    place(&mut rank, 0, 'r');
+    place(&mut rank, 0, 'k');
+    place(&mut rank, 0, 'r');
+
+    let black = rank.iter().collect::<String>();
+    let rook_files = rank
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| **c == 'r')
+        .map(|(file, _)| (b'a' + file as u8) as char)
+        .rev()
+        .collect::<String>();
+    format!(
+        "{}/pppppppp/8/8/8/8/PPPPPPPP/{} w {}{} - 0 1",
+        black,
+        black.to_uppercase(),
+        rook_files.to_uppercase(),
+        rook_files
+    )
+}
+
+/// Get the halfmove clock from a position in FEN, or 0 if it doesn't have one (such as EPD)
+fn halfmove_clock(fen: &str) -> u32 {
+    fen.split_whitespace()
+        .nth(4)
+        .and_then(|clock| clock.parse().ok())
+        .unwrap_or(0)
+}
+
+/// Validate a position in FEN or EPD, and convert it to the FEN used in game states. Returns None if the position
+/// isn't valid.
+pub fn normalize_fen(fen: &str) -> Option<String> {
+    let board = chess::Board::from_fen(fen)?;
+    // the board doesn't keep the halfmove clock, so add it back in
+    let board_fen = board.to_string();
+    let fields = board_fen.split_whitespace().collect::<Vec<&str>>();
+    Some(format!(
+        "{} {} {} {} {} {}",
+        fields[0],
+        fields[1],
+        fields[2],
+        fields[3],
+        halfmove_clock(fen),
+        fields[5]
+    ))
+}
+
+/// The parts of a serialized chess game
+#[derive(Debug, PartialEq, Eq)]
+pub struct ChessState<'a> {
+    /// position the game started from
+    pub start: &'a str,
+    /// current position
+    pub fen: &'a str,
+    /// moves made since the start position, in long algebraic notation
+    pub moves: Vec<String>,
+}
+
+impl<'a> ChessState<'a> {
+    /// Split a chess game's serialization into its parts
+    pub fn parse(data: &'a str) -> ChessState<'a> {
+        // serialization format: start_fen,fen,[move0,move1,move2], where start_fen is left out if the game started from
+        // the standard position
+        let (fens, moves_str) = data.split_once('[').unwrap_or((data, ""));
+        let fens = fens.trim_end_matches(',');
+        let (start, fen) = fens.split_once(',').unwrap_or((DEFAULT_BOARD, fens));
+        let moves = moves_str
+            .trim_end_matches(']')
+            .split(',')
+            .filter(|move_str| !move_str.is_empty())
+            .map(|move_str| move_str.to_string())
+            .collect::<Vec<String>>();
+        ChessState { start, fen, moves }
+    }
+}
+
+/// Rebuild a game instance from its serialization
+fn deserialize_instance(
+    data: &str,
+    players: &[UserId],
+    options: ChessOptions,
+) -> Option<Box<dyn GameInstance>> {
+    if players.len() != 2 {
+        return None;
+    }
+
+    let ChessState { start, fen, moves } = ChessState::parse(data);
+    let mut instance = ChessGameInstance::new(players, &options, start);
+    // replay moves to rebuild position history. If they don't lead to the stored position, trust the stored position
+    if !instance.replay(&moves) || instance.board != chess::Board::new(fen) {
+        instance = ChessGameInstance::new(players, &options, start);
+        instance.board = chess::Board::new(fen);
+        instance.halfmove_clock = halfmove_clock(fen);
+        instance.positions.clear();
+        instance.record_position();
+        instance.moves = moves;
+    }
+    Some(Box::new(instance))
+}
+
+/// Standard chess. Holds the opening book used for the `book_plies` option, if the server has one.
+#[derive(Debug)]
+pub struct ChessGame(pub Option<chess::book::Book>);
+
+impl ChessGame {
+    /// Get the position to start a game from, playing weighted random moves from the opening book if requested.
+    /// Stops early if the position leaves the book.
+    fn start_position(&self, options: &ChessOptions) -> String {
+        let start = options.start.as_deref().unwrap_or(DEFAULT_BOARD);
+        let book = match &self.0 {
+            Some(book) if options.book_plies > 0 => book,
+            _ => return start.to_string(),
+        };
+        let mut board = chess::Board::new(start);
+        let mut rng = thread_rng();
+        for _ in 0..options.book_plies {
+            match book.weighted_move(&board, rng.gen()) {
+                Some(book_move) => board.make_move(book_move),
+                None => break,
+            }
+        }
+        board.to_string()
+    }
+}
+
+impl GameType for ChessGame {
+    fn deserialize(
+        &self,
+        data: &str,
+        players: &[UserId],
+        options: &str,
+    ) -> Option<Box<dyn GameInstance>> {
+        let options = ChessOptions::parse(options).ok()?;
+        deserialize_instance(data, players, options)
+    }
+
+    fn new(&self, players: &[UserId], options: &str) -> Option<Box<dyn GameInstance>> {
+        if players.len() != 2 {
             None
+        } else {
+            let options = ChessOptions::parse(options).ok()?;
+            let start = self.start_position(&options);
+            Some(Box::new(ChessGameInstance::new(players, &options, &start)))
         }
     }
 
-    fn new(&self, players: &[UserId]) -> Option<Box<dyn GameInstance>> {
+    fn check_options(&self, options: &str) -> Result<(), String> {
+        let options = ChessOptions::parse(options)?;
+        if options.book_plies > 0 && self.0.is_none() {
+            return Err("the server doesn't have an opening book".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Chess960 (Fischer random chess), played from one of 960 shuffled start positions
+#[derive(Debug)]
+pub struct Chess960Game();
+
+impl GameType for Chess960Game {
+    fn deserialize(
+        &self,
+        data: &str,
+        players: &[UserId],
+        options: &str,
+    ) -> Option<Box<dyn GameInstance>> {
+        // the start position is stored in the serialization, so the start options don't matter here
+        let options = Chess960Options::parse(options).ok()?;
+        deserialize_instance(data, players, options.chess)
+    }
+
+    fn new(&self, players: &[UserId], options: &str) -> Option<Box<dyn GameInstance>> {
         if players.len() != 2 {
             None
         } else {
-            Some(Box::new(ChessGameInstance {
-                board: chess::Board::new(DEFAULT_BOARD),
-                moves: Vec::new(),
-                white: players[0],
-                black: players[1],
-            }))
+            let options = Chess960Options::parse(options).ok()?;
+            // a start position given with fen takes priority over index and seed
+            let start = match &options.chess.start {
+                Some(start) => start.clone(),
+                None => chess960_start_fen(
+                    options
+                        .index
+                        .unwrap_or_else(|| thread_rng().gen_range(0..CHESS960_POSITIONS)),
+                ),
+            };
+            Some(Box::new(ChessGameInstance::new(
+                players,
+                &options.chess,
+                &start,
+            )))
         }
     }
+
+    fn check_options(&self, options: &str) -> Result<(), String> {
+        Chess960Options::parse(options).map(|_| ())
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct ChessGameInstance {
+    // position the game started from
+    start: String,
     // current board state
     board: chess::Board,
     // moves made to reach this state
@@ -61,9 +398,125 @@ pub struct ChessGameInstance {
     // players in the game
     white: UserId,
     black: UserId,
+    // draw rules in effect
+    draw_rule: DrawRule,
+    insufficient_material: bool,
+    // number of times each position has occurred (keyed by fen without move counters)
+    positions: HashMap<String, u32>,
+    // number of halfmoves since the last capture or pawn move
+    halfmove_clock: u32,
 }
 
 impl ChessGameInstance {
+    fn new(players: &[UserId], options: &ChessOptions, start: &str) -> ChessGameInstance {
+        let mut instance = ChessGameInstance {
+            start: start.to_string(),
+            board: chess::Board::new(start),
+            moves: Vec::new(),
+            white: players[0],
+            black: players[1],
+            draw_rule: options.draw_rule,
+            insufficient_material: options.insufficient_material,
+            positions: HashMap::new(),
+            halfmove_clock: halfmove_clock(start),
+        };
+        instance.record_position();
+        instance
+    }
+
+    /// Apply a list of moves from the current position, returning false if any of them are illegal
+    fn replay(&mut self, moves: &[String]) -> bool {
+        for move_str in moves {
+            match chess::Move::from_str(move_str, &self.board) {
+                Some(chess_move) if chess_move.is_legal(&mut self.board) => {
+                    self.apply_move(chess_move, move_str)
+                }
+                _ => return false,
+            }
+        }
+        true
+    }
+
+    /// Apply a legal move to the board and update move history
+    fn apply_move(&mut self, chess_move: chess::Move, move_str: &str) {
+        let is_pawn_move =
+            self.board.piece_on_square(chess_move.src()) == Some(chess::PieceType::Pawn);
+        if is_pawn_move || chess_move.capture_square().is_some() {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        self.board.make_move(chess_move);
+        self.moves.push(move_str.to_string());
+        self.record_position();
+    }
+
+    /// Check if the player to move can make an en passant capture
+    fn can_capture_en_passant(&mut self) -> bool {
+        let target = match self.board.en_passant_target() {
+            Some(target) => target,
+            None => return false,
+        };
+        let mut gen = chess::MoveGenerator::new(&mut self.board);
+        while let Some(m) = gen.next(&mut self.board) {
+            if m.dst() == target
+                && self.board.piece_on_square(m.src()) == Some(chess::PieceType::Pawn)
+            {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Count an occurrence of the current position.
+    /// Positions are the same if piece placement, player to move, castling rights, and en passant captures available are the same.
+    fn record_position(&mut self) {
+        let fen = self.board.to_string();
+        let mut fields = fen.split_whitespace().take(4).collect::<Vec<&str>>();
+        if fields.len() == 4 && !self.can_capture_en_passant() {
+            fields[3] = "-";
+        }
+        *self.positions.entry(fields.join(" ")).or_insert(0) += 1;
+    }
+
+    /// Check if neither player has enough material left to checkmate (K vs K, K+B vs K, K+N vs K, or only same colored bishops)
+    fn is_insufficient_material(&self) -> bool {
+        use chess::{PieceType, Player};
+
+        let both = |piece: PieceType| {
+            self.board.piece_bb(Player::White, piece) | self.board.piece_bb(Player::Black, piece)
+        };
+        if (both(PieceType::Pawn) | both(PieceType::Rook) | both(PieceType::Queen)).any_set() {
+            return false;
+        }
+        let knights = both(PieceType::Knight);
+        let bishops = both(PieceType::Bishop);
+        if knights.count() + bishops.count() <= 1 {
+            return true;
+        }
+        // any number of bishops can't mate if they are all on the same color
+        let light_bishops = bishops & chess::Bitboard::new(LIGHT_SQUARES);
+        !knights.any_set() && (light_bishops == bishops || !light_bishops.any_set())
+    }
+
+    /// If the game is drawn by repetition, the fifty/seventy-five move rule, or insufficient material, return the name of the rule
+    fn draw_reason(&self) -> Option<&'static str> {
+        if let Some((limit, reason)) = self.draw_rule.repetition_limit() {
+            if self.positions.values().any(|count| *count >= limit) {
+                return Some(reason);
+            }
+        }
+        if let Some((limit, reason)) = self.draw_rule.halfmove_limit() {
+            if self.halfmove_clock >= limit {
+                return Some(reason);
+            }
+        }
+        if self.insufficient_material && self.is_insufficient_material() {
+            return Some("Insufficient Material");
+        }
+        None
+    }
+
     fn chess_player_to_user(&self, player: chess::Player) -> UserId {
         match player {
             chess::Player::White => self.white,
@@ -89,6 +542,9 @@ impl ChessGameInstance {
 
 impl GameInstance for ChessGameInstance {
     fn serialize(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.start != DEFAULT_BOARD {
+            write!(f, "{},", self.start)?;
+        }
         write!(f, "{},[", self.board.to_string())?;
         for (i, m) in (&self.moves).into_iter().enumerate() {
             write!(f, "{}", m)?;
@@ -96,8 +552,7 @@ impl GameInstance for ChessGameInstance {
                 write!(f, ",")?;
             }
         }
-        write!(f, "]")?;
-        Ok(())
+        write!(f, "]")
     }
 
     fn serialize_current(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -106,7 +561,7 @@ impl GameInstance for ChessGameInstance {
     }
 
     fn turn(&self) -> GameTurn {
-        if self.board.is_stalemate() || self.board.is_checkmate() {
+        if self.board.is_stalemate() || self.board.is_checkmate() || self.draw_reason().is_some() {
             GameTurn::Finished
         } else {
             GameTurn::Turn(self.chess_player_to_user(self.board.player_to_move()))
@@ -122,28 +577,50 @@ impl GameInstance for ChessGameInstance {
         match chess_move {
             Some(chess_move) => {
                 if chess_move.is_legal(&mut self.board) {
-                    self.board.make_move(chess_move);
-                    self.moves.push(move_to_make.to_string());
+                    self.apply_move(chess_move, move_to_make);
                     Ok(())
                 } else {
                     Err(format!("illegal move: {}", move_to_make))
                 }
             }
-            None => Err(format!("malformed move: {}", move_to_make)),
+            // moves can also be given in SAN, but are recorded in long algebraic notation
+            None => match chess::Move::from_san(move_to_make, &self.board) {
+                Some(chess_move) => {
+                    self.apply_move(chess_move, &chess_move.to_string());
+                    Ok(())
+                }
+                None if chess::Move::san_is_wellformed(move_to_make) => {
+                    Err(format!("illegal move: {}", move_to_make))
+                }
+                None => Err(format!("malformed move: {}", move_to_make)),
+            },
         }
     }
 
     fn end_state(&self) -> Option<GameState> {
-        if self.board.is_stalemate() {
-            Some(GameState::Tie)
-        } else if self.board.is_checkmate() {
+        if self.board.is_checkmate() {
             let winner = self.other_chess_player(self.board.player_to_move());
             Some(GameState::Win(self.chess_player_to_user(winner)))
+        } else if self.board.is_stalemate() || self.draw_reason().is_some() {
+            Some(GameState::Tie)
         } else {
             Some(GameState::InProgress)
         }
     }
 
+    fn last_move(&self) -> Option<String> {
+        self.moves.last().cloned()
+    }
+
+    fn end_reason(&self) -> Option<String> {
+        // checkmate and stalemate are evident from the final position, but draws by rule aren't
+        if self.board.is_checkmate() || self.board.is_stalemate() {
+            None
+        } else {
+            self.draw_reason().map(|reason| reason.to_string())
+        }
+    }
+
     fn scores(&self) -> Option<GameScore> {
         let end_state = self.end_state();
         if let Some(end_state) = end_state {
@@ -174,23 +651,24 @@ mod test {
 
     #[test]
     fn chess_create_test() {
-        let game = ChessGame();
+        let game = ChessGame(None);
         let players0 = vec![1];
         let players1 = vec![1, 2];
-        if let Some(_) = game.new(&players0[..]) {
+        if let Some(_) = game.new(&players0[..], "") {
             panic!("number of players should be invalid");
         }
-        if let None = game.new(&players1[..]) {
+        if let None = game.new(&players1[..], "") {
             panic!("number of players should be valid");
         }
     }
 
     #[test]
     fn chess_serialize_test() {
-        let game = ChessGame();
+        let game = ChessGame(None);
         let instance = game.deserialize(
             "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c6 0 2,[e2e4,c7c5]",
             &vec![1, 2],
+            "",
         );
         if let Some(mut instance) = instance {
             assert_eq!(instance.end_state(), Some(GameState::InProgress));
@@ -227,8 +705,368 @@ mod test {
                 format!("{}", Fmt(|f| instance.serialize_current(f))),
                 "rnbqkbnr/pp1ppppp/8/2p1P3/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 2"
             );
+
+            // moves in SAN are recorded in long algebraic notation
+            assert_eq!(
+                instance.make_move(2, "Nc3"),
+                Err("illegal move: Nc3".to_string())
+            );
+            assert_eq!(
+                instance.make_move(2, "Nz9"),
+                Err("malformed move: Nz9".to_string())
+            );
+            assert_eq!(instance.make_move(2, "Nf6"), Ok(()));
+            assert_eq!(instance.make_move(1, "exf6"), Ok(()));
+            assert_eq!(instance.last_move(), Some("e5f6".to_string()));
+            assert_eq!(
+                format!("{}", Fmt(|f| instance.serialize(f))),
+                "rnbqkb1r/pp1ppppp/5P2/2p5/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 3,[e2e4,c7c5,e4e5,g8f6,e5f6]"
+            );
         } else {
             panic!("game should have parsed");
         }
     }
+
+    #[test]
+    fn chess_options_test() {
+        let game = ChessGame(None);
+        assert_eq!(game.check_options(""), Ok(()));
+        assert_eq!(game.check_options("draw_rule=none"), Ok(()));
+        assert_eq!(game.check_options("draw_rule=fivefold"), Ok(()));
+        assert_eq!(
+            game.check_options("draw_rule=sometimes"),
+            Err("unknown draw rule: sometimes".to_string())
+        );
+        assert_eq!(
+            game.check_options("colors=random"),
+            Err("unknown chess option: colors".to_string())
+        );
+    }
+
+    #[test]
+    fn chess_start_position_test() {
+        let game = ChessGame(None);
+        assert_eq!(
+            game.check_options("fen=8/8/4k3/8/8/4K3/8/4R3 w - - 0 1"),
+            Ok(())
+        );
+        assert_eq!(
+            game.check_options("fen=8/8/4k3/8/8/4KK2/8/8 w - - 0 1"),
+            Err("invalid start position: 8/8/4k3/8/8/4KK2/8/8 w - - 0 1".to_string())
+        );
+        assert_eq!(
+            game.check_options("fen=8/8/4k3/8/8/4K3/8 w - - 0 1"),
+            Err("invalid start position: 8/8/4k3/8/8/4K3/8 w - - 0 1".to_string())
+        );
+        // the player who just moved can't be in check
+        assert_eq!(
+            game.check_options("fen=8/8/4k3/8/8/4K3/8/4r3 b - - 0 1"),
+            Err("invalid start position: 8/8/4k3/8/8/4K3/8/4r3 b - - 0 1".to_string())
+        );
+
+        // EPD is stored as FEN, and the start position is kept in the serialization
+        let mut instance = game
+            .new(
+                &vec![1, 2],
+                "fen=8/8/4k3/8/8/4K3/8/4R3 w - -; draw_rule=none",
+            )
+            .unwrap();
+        assert_eq!(
+            format!("{}", Fmt(|f| instance.serialize(f))),
+            "8/8/4k3/8/8/4K3/8/4R3 w - - 0 1,8/8/4k3/8/8/4K3/8/4R3 w - - 0 1,[]"
+        );
+        assert_eq!(instance.make_move(1, "e1e2"), Ok(()));
+        let data = format!("{}", Fmt(|f| instance.serialize(f)));
+        assert_eq!(
+            data,
+            "8/8/4k3/8/8/4K3/8/4R3 w - - 0 1,8/8/4k3/8/8/4K3/4R3/8 b - - 0 1,[e1e2]"
+        );
+        let instance = game.deserialize(&data, &vec![1, 2], "").unwrap();
+        assert_eq!(format!("{}", Fmt(|f| instance.serialize(f))), data);
+
+        // the halfmove clock is kept from the start position, including after serialization
+        let options = "fen=8/8/4k3/8/8/4K3/8/4R3 w - - 99 80;draw_rule=threefold";
+        let instance = game.new(&vec![1, 2], options).unwrap();
+        let data = format!("{}", Fmt(|f| instance.serialize(f)));
+        assert!(data.starts_with("8/8/4k3/8/8/4K3/8/4R3 w - - 99 80,"));
+        let mut instance = game.deserialize(&data, &vec![1, 2], options).unwrap();
+        assert_eq!(instance.make_move(1, "e1e2"), Ok(()));
+        assert_eq!(instance.end_state(), Some(GameState::Tie));
+        assert_eq!(instance.end_reason(), Some("Fifty Move Rule".to_string()));
+
+        // games from the standard start position are serialized as before
+        let instance = game
+            .new(
+                &vec![1, 2],
+                "fen=rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            )
+            .unwrap();
+        assert_eq!(
+            format!("{}", Fmt(|f| instance.serialize(f))),
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1,[]"
+        );
+    }
+
+    // shuffle knights back and forth, repeating the starting position every four halfmoves
+    const KNIGHT_SHUFFLE: [&str; 4] = ["g1f3", "g8f6", "f3g1", "f6g8"];
+
+    // play halfmoves start..end of the knight shuffle
+    fn shuffle_knights(instance: &mut Box<dyn GameInstance>, start: usize, end: usize) {
+        for i in start..end {
+            let player = if i % 2 == 0 { 1 } else { 2 };
+            assert_eq!(instance.turn(), GameTurn::Turn(player));
+            assert_eq!(instance.make_move(player, KNIGHT_SHUFFLE[i % 4]), Ok(()));
+        }
+    }
+
+    #[test]
+    fn chess_repetition_test() {
+        let game = ChessGame(None);
+
+        // the starting position occurs for the third time after 8 halfmoves
+        let mut instance = game.new(&vec![1, 2], "draw_rule=threefold").unwrap();
+        shuffle_knights(&mut instance, 0, 7);
+        assert_eq!(instance.end_state(), Some(GameState::InProgress));
+        shuffle_knights(&mut instance, 7, 8);
+        assert_eq!(instance.turn(), GameTurn::Finished);
+        assert_eq!(instance.end_state(), Some(GameState::Tie));
+        assert_eq!(
+            instance.scores(),
+            Some([(1, 0.5), (2, 0.5)].iter().cloned().collect())
+        );
+
+        // history survives serialization
+        let mut instance = game.new(&vec![1, 2], "draw_rule=threefold").unwrap();
+        shuffle_knights(&mut instance, 0, 7);
+        let mut instance = game
+            .deserialize(
+                &format!("{}", Fmt(|f| instance.serialize(f))),
+                &vec![1, 2],
+                "draw_rule=threefold",
+            )
+            .unwrap();
+        shuffle_knights(&mut instance, 7, 8);
+        assert_eq!(instance.end_state(), Some(GameState::Tie));
+
+        // fivefold needs 16 halfmoves
+        let mut instance = game.new(&vec![1, 2], "draw_rule=fivefold").unwrap();
+        shuffle_knights(&mut instance, 0, 15);
+        assert_eq!(instance.end_state(), Some(GameState::InProgress));
+        shuffle_knights(&mut instance, 15, 16);
+        assert_eq!(instance.end_state(), Some(GameState::Tie));
+
+        // no automatic draws under the original rules, which are the default
+        for options in &["draw_rule=none", ""] {
+            let mut instance = game.new(&vec![1, 2], options).unwrap();
+            shuffle_knights(&mut instance, 0, 40);
+            assert_eq!(instance.end_state(), Some(GameState::InProgress));
+        }
+    }
+
+    #[test]
+    fn chess_fifty_move_test() {
+        let mut instance = ChessGameInstance::new(
+            &vec![1, 2],
+            &ChessOptions::parse("draw_rule=threefold").unwrap(),
+            DEFAULT_BOARD,
+        );
+        instance.halfmove_clock = 98;
+        assert_eq!(instance.make_move(1, "g1f3"), Ok(()));
+        assert_eq!(instance.end_state(), Some(GameState::InProgress));
+        assert_eq!(instance.make_move(2, "g8f6"), Ok(()));
+        assert_eq!(instance.end_state(), Some(GameState::Tie));
+
+        // pawn moves reset the count
+        let mut instance = ChessGameInstance::new(
+            &vec![1, 2],
+            &ChessOptions::parse("draw_rule=threefold").unwrap(),
+            DEFAULT_BOARD,
+        );
+        instance.halfmove_clock = 98;
+        assert_eq!(instance.make_move(1, "g1f3"), Ok(()));
+        assert_eq!(instance.make_move(2, "e7e5"), Ok(()));
+        assert_eq!(instance.halfmove_clock, 0);
+        assert_eq!(instance.end_state(), Some(GameState::InProgress));
+    }
+
+    #[test]
+    fn chess_insufficient_material_test() {
+        let game = ChessGame(None);
+        let check = |fen: &str, options: &str, expected: Option<&str>| {
+            let instance = game
+                .deserialize(&format!("{},[]", fen), &vec![1, 2], options)
+                .unwrap();
+            assert_eq!(instance.end_reason(), expected.map(|r| r.to_string()));
+            if expected.is_some() {
+                assert_eq!(instance.turn(), GameTurn::Finished);
+                assert_eq!(instance.end_state(), Some(GameState::Tie));
+            } else {
+                assert_eq!(instance.end_state(), Some(GameState::InProgress));
+            }
+        };
+        let insufficient = Some("Insufficient Material");
+        let rules = "draw_rule=threefold";
+
+        // king vs king, king + minor piece vs king
+        check("8/8/4k3/8/8/4K3/8/8 w - - 0 1", rules, insufficient);
+        check("8/8/4k3/8/8/3BK3/8/8 w - - 0 1", rules, insufficient);
+        check("8/8/4k3/8/8/4K3/3n4/8 b - - 0 1", rules, insufficient);
+        // bishops all on the same color
+        check("8/8/4k3/5b2/8/3BK3/8/8 w - - 0 1", rules, insufficient);
+        check("8/8/4k3/5b2/8/2BBK3/8/8 w - - 0 1", rules, None);
+        check("8/8/4k3/4b3/8/3BK3/8/8 w - - 0 1", rules, None);
+        // enough material to mate
+        check("8/8/4k3/8/8/3NKN2/8/8 w - - 0 1", rules, None);
+        check("8/8/4k3/8/8/4K3/8/7R w - - 0 1", rules, None);
+        check("8/8/4k3/8/8/4K3/4P3/8 w - - 0 1", rules, None);
+        // dead positions are drawn whatever the draw rule, unless turned off
+        check(
+            "8/8/4k3/8/8/4K3/8/8 w - - 0 1",
+            "draw_rule=none",
+            insufficient,
+        );
+        check("8/8/4k3/8/8/4K3/8/8 w - - 0 1", "", insufficient);
+        check(
+            "8/8/4k3/8/8/3BK3/8/8 w - - 0 1",
+            "insufficient_material=off",
+            None,
+        );
+        check(
+            "8/8/4k3/8/8/4K3/8/8 w - - 0 1",
+            "draw_rule=threefold; insufficient_material=off",
+            None,
+        );
+        assert!(ChessOptions::parse("insufficient_material=yes").is_err());
+    }
+
+    #[test]
+    fn chess960_start_fen_test() {
+        assert_eq!(
+            chess960_start_fen(518),
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w HAha - 0 1"
+        );
+        assert_eq!(
+            chess960_start_fen(0),
+            "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w HFhf - 0 1"
+        );
+        assert_eq!(
+            chess960_start_fen(959),
+            "rkrnnqbb/pppppppp/8/8/8/8/PPPPPPPP/RKRNNQBB w CAca - 0 1"
+        );
+
+        // every index gives a different position
+        let positions = (0..960)
+            .map(chess960_start_fen)
+            .collect::<std::collections::HashSet<String>>();
+        assert_eq!(positions.len(), 960);
+    }
+
+    #[test]
+    fn chess960_options_test() {
+        let game = Chess960Game();
+        assert_eq!(game.check_options(""), Ok(()));
+        assert_eq!(game.check_options("index=518;draw_rule=none"), Ok(()));
+        assert_eq!(game.check_options("seed=12345"), Ok(()));
+        assert_eq!(
+            game.check_options("index=960"),
+            Err("invalid chess960 index: 960".to_string())
+        );
+        assert_eq!(
+            game.check_options("seed=abc"),
+            Err("invalid chess960 seed: abc".to_string())
+        );
+        assert_eq!(
+            game.check_options("colors=random"),
+            Err("unknown chess option: colors".to_string())
+        );
+
+        // the same seed always gives the same position
+        let serialize_current = |options: &str| {
+            let instance = game.new(&vec![1, 2], options).unwrap();
+            format!("{}", Fmt(|f| instance.serialize_current(f)))
+        };
+        assert_eq!(serialize_current("seed=42"), serialize_current("seed=42"));
+        assert_eq!(
+            serialize_current("seed=42"),
+            chess960_start_fen(chess960_index_from_seed(42))
+        );
+        // pinned so the mapping can't change under existing games
+        assert_eq!(chess960_index_from_seed(0), 175);
+        assert_eq!(chess960_index_from_seed(1), 65);
+        assert_eq!(chess960_index_from_seed(42), 853);
+        assert_eq!(chess960_index_from_seed(12345), 224);
+        assert_eq!(serialize_current("index=0"), chess960_start_fen(0));
+    }
+
+    #[test]
+    fn chess960_castle_test() {
+        let game = Chess960Game();
+        let mut instance = game.new(&vec![1, 2], "index=408").unwrap();
+        for (player, move_str) in [(1, "f1g3"), (2, "f8g6")].iter() {
+            assert_eq!(instance.make_move(*player, move_str), Ok(()));
+        }
+        // castle kingside by moving the king onto its rook. The king stays on g1 and the rook moves to f1
+        assert_eq!(instance.make_move(1, "g1h1"), Ok(()));
+        assert_eq!(
+            format!("{}", Fmt(|f| instance.serialize_current(f))),
+            "rbqnb1kr/pppppppp/6n1/8/8/6N1/PPPPPPPP/RBQNBRK1 b ha - 0 2"
+        );
+
+        // the start position and castling survive serialization
+        let data = format!("{}", Fmt(|f| instance.serialize(f)));
+        assert_eq!(
+            data,
+            "rbqnbnkr/pppppppp/8/8/8/8/PPPPPPPP/RBQNBNKR w HAha - 0 1,rbqnb1kr/pppppppp/6n1/8/8/6N1/PPPPPPPP/RBQNBRK1 b ha - 0 2,[f1g3,f8g6,g1h1]"
+        );
+        let instance = game.deserialize(&data, &vec![1, 2], "").unwrap();
+        assert_eq!(format!("{}", Fmt(|f| instance.serialize(f))), data);
+    }
+
+    // encode polyglot book entries for (position, move, weight)
+    fn book_bytes(entries: &[(&str, u16, u16)]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for (fen, raw_move, weight) in entries.iter() {
+            data.extend_from_slice(&chess::Board::new(fen).hash().to_be_bytes());
+            data.extend_from_slice(&raw_move.to_be_bytes());
+            data.extend_from_slice(&weight.to_be_bytes());
+            data.extend_from_slice(&[0; 4]);
+        }
+        data
+    }
+
+    #[test]
+    fn book_test() {
+        let book = chess::book::Book::from_bytes(&book_bytes(&[
+            // e2e4
+            (DEFAULT_BOARD, 12 << 6 | 28, 1),
+            // d7d5
+            (
+                "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1",
+                51 << 6 | 35,
+                1,
+            ),
+        ]));
+
+        // games play book moves until the position leaves the book
+        let game = ChessGame(Some(book));
+        assert_eq!(game.check_options("book_plies=6"), Ok(()));
+        let instance = game.new(&vec![1, 2], "book_plies=6").unwrap();
+        assert_eq!(
+            format!("{}", Fmt(|f| instance.serialize(f))),
+            "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2,rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 2,[]"
+        );
+
+        assert_eq!(
+            ChessGame(None).check_options("book_plies=6"),
+            Err("the server doesn't have an opening book".to_string())
+        );
+        assert_eq!(
+            ChessGame(None).check_options("book_plies=many"),
+            Err("invalid number of book plies: many".to_string())
+        );
+        assert_eq!(
+            Chess960Game().check_options("book_plies=6"),
+            Err("chess960 games can't use an opening book".to_string())
+        );
+    }
 }
@@ -2,33 +2,21 @@ use crate::games::{Fmt, GameInstance, GameScore, GameState, GameTurn, GameType};
 use crate::models::UserId;
 use std::fmt::Formatter;
 
+/// Prefix of the serialized state of an ended game
+pub const ENDED_GAME_PREFIX: &str = "__ENDED_GAME";
+/// Reason given for games that end because a player ran out of time
+pub const TIME_EXPIRED_REASON: &str = "Time Expired";
+
 /// A game that has ended abnormally (such as through time expiration, resignation, etc)
 #[derive(Debug)]
 pub struct EndedGame();
 
 impl GameType for EndedGame {
-    fn deserialize(&self, data: &str, _: &[UserId]) -> Option<Box<dyn GameInstance>> {
-        let mut components = data.split(',');
-        components.next()?;
-        let winner = components.next()?;
-        let reason = components.next()?;
-        let game_type = components.next()?;
-        let prev_state = components.next()?;
-
-        let winner = match winner {
-            "-" => None,
-            id => id.parse::<UserId>().ok(),
-        };
-
-        Some(Box::new(EndedGameInstance {
-            winner,
-            reason: reason.to_string(),
-            game_type: game_type.to_string(),
-            prev_state: prev_state.to_string(),
-        }))
+    fn deserialize(&self, data: &str, _: &[UserId], _: &str) -> Option<Box<dyn GameInstance>> {
+        Some(Box::new(EndedGameInstance::from_state(data)?))
     }
 
-    fn new(&self, _: &[UserId]) -> Option<Box<dyn GameInstance>> {
+    fn new(&self, _: &[UserId], _: &str) -> Option<Box<dyn GameInstance>> {
         Some(Box::new(EndedGameInstance {
             winner: None,
             reason: "".to_string(),
@@ -47,6 +35,36 @@ pub struct EndedGameInstance {
 }
 
 impl EndedGameInstance {
+    /// Parse an ended game from its serialization
+    pub fn from_state(data: &str) -> Option<EndedGameInstance> {
+        // the previous state may itself contain commas, so only split off the leading fields
+        let mut components = data.splitn(5, ',').map(|c| c.trim());
+        if components.next()? != ENDED_GAME_PREFIX {
+            return None;
+        }
+        let winner = components.next()?;
+        let reason = components.next()?;
+        let game_type = components.next()?;
+        let prev_state = components.next()?;
+
+        let winner = match winner {
+            "-" => None,
+            id => id.parse::<UserId>().ok(),
+        };
+
+        Some(EndedGameInstance {
+            winner,
+            reason: reason.to_string(),
+            game_type: game_type.to_string(),
+            prev_state: prev_state.to_string(),
+        })
+    }
+
+    /// The serialized state of the game when it ended, or "-" if it never started
+    pub fn prev_state(&self) -> &str {
+        &self.prev_state
+    }
+
     pub fn from_current_state(
         state: Option<&dyn GameInstance>,
         game_type: String,
@@ -69,7 +87,8 @@ impl GameInstance for EndedGameInstance {
     fn serialize(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "__ENDED_GAME, {}, {}, {}, {}",
+            "{}, {}, {}, {}, {}",
+            ENDED_GAME_PREFIX,
             self.winner.map_or("-".to_string(), |i| i.to_string()),
             self.reason,
             self.game_type,
@@ -96,6 +115,10 @@ impl GameInstance for EndedGameInstance {
         }
     }
 
+    fn end_reason(&self) -> Option<String> {
+        Some(self.reason.clone())
+    }
+
     fn scores(&self) -> Option<GameScore> {
         None
     }
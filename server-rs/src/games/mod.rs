@@ -10,10 +10,25 @@ pub mod ended_game;
 /// `GameType` represents the type of game, not a specific instance of that game.
 pub trait GameType: Send + Sync {
     /// Create an instance of this game from it's serialized representation.
-    fn deserialize(&self, data: &str, players: &[UserId]) -> Option<Box<dyn GameInstance>>;
+    fn deserialize(
+        &self,
+        data: &str,
+        players: &[UserId],
+        options: &str,
+    ) -> Option<Box<dyn GameInstance>>;
 
     /// Create a new instance of this game with the given number of players. If a game cannot be created with this number of players, return None.
-    fn new(&self, players: &[UserId]) -> Option<Box<dyn GameInstance>>;
+    fn new(&self, players: &[UserId], options: &str) -> Option<Box<dyn GameInstance>>;
+
+    /// Check that an options string is valid for this game, or return an error describing why it isn't.
+    /// Options are given when a game is created, and passed back to `new` and `deserialize`.
+    fn check_options(&self, options: &str) -> Result<(), String> {
+        if options.is_empty() {
+            Ok(())
+        } else {
+            Err("this game type doesn't take any options".to_string())
+        }
+    }
 }
 
 /// Whose turn it is in a game
@@ -47,12 +62,38 @@ pub trait GameInstance {
     fn turn(&self) -> GameTurn;
     /// Make a move, or return an error describing why that move is illegal.
     fn make_move(&mut self, player: UserId, move_to_make: &str) -> Result<(), String>;
+    /// Get the last move made, as recorded in the game's state (which may differ from how the player gave it). If the game doesn't record its moves, return None.
+    fn last_move(&self) -> Option<String> {
+        None
+    }
     /// Get the end state of the game. If the game doesn't have a specific win/loss/tie result, return None.
     fn end_state(&self) -> Option<GameState>;
+    /// If the game has ended for a reason not evident from its state (such as a draw by repetition), describe that reason.
+    fn end_reason(&self) -> Option<String> {
+        None
+    }
     /// Get the scores for the game. If the game doesn't have score results, return None. May return None while the game is in progress and Some when scores are available.
     fn scores(&self) -> Option<GameScore>;
 }
 
+/// Parse a game options string of the form `key0=value0;key1=value1` into a map of keys to values
+pub fn parse_options(options: &str) -> Result<HashMap<&str, &str>, String> {
+    let mut res = HashMap::new();
+    for option in options.split(';') {
+        let option = option.trim();
+        if option.is_empty() {
+            continue;
+        }
+        match option.split_once('=') {
+            Some((key, value)) => {
+                res.insert(key.trim(), value.trim());
+            }
+            None => return Err(format!("option {} is missing a value", option)),
+        }
+    }
+    Ok(res)
+}
+
 /// mapping from game type string to GameType
 pub type GameTypeMap = HashMap<&'static str, Box<dyn GameType>>;
 
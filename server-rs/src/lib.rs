@@ -9,6 +9,9 @@ pub mod db;
 pub mod error;
 pub mod games;
 pub mod models;
+pub mod pgn;
+pub mod rating;
 pub mod schema;
 pub mod server;
+pub mod storage;
 pub mod tournament;
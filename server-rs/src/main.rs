@@ -11,14 +11,29 @@ use std::sync::Arc;
 async fn main() {
     dotenv().ok();
     let addr = env::var("SERVER_URL").unwrap_or_else(|_| "127.0.0.1:9000".to_string());
-    let db_url =
-        env::var("DATABASE_URL").expect("DATABASE_URL must be set to the postgres database url");
+    // DATABASE_URL=memory keeps everything in memory instead of postgres
+    let db_url = env::var("DATABASE_URL")
+        .expect("DATABASE_URL must be set to the postgres database url (or memory)");
+
+    // optional polyglot opening book, used by the book_plies chess option
+    let book = env::var("OPENING_BOOK").ok().map(|path| {
+        chess::book::Book::open(&path)
+            .unwrap_or_else(|e| panic!("couldn't read opening book {}: {}", path, e))
+    });
 
     let mut game_type_map: GameTypeMap = HashMap::new();
-    game_type_map.insert("chess", Box::new(games::chess_game::ChessGame()));
+    game_type_map.insert("chess", Box::new(games::chess_game::ChessGame(book)));
+    game_type_map.insert("chess960", Box::new(games::chess_game::Chess960Game()));
 
     let mut tournament_type_map: TournamentTypeMap = HashMap::new();
     tournament_type_map.insert("round_robin", Box::new(tournament::RoundRobin()));
+    tournament_type_map.insert("swiss", Box::new(tournament::swiss::Swiss()));
+    tournament_type_map.insert("knockout", Box::new(tournament::knockout::Knockout()));
+    tournament_type_map.insert(
+        "double_elim",
+        Box::new(tournament::knockout::DoubleElimination()),
+    );
+    tournament_type_map.insert("gauntlet", Box::new(tournament::gauntlet::Gauntlet()));
 
     server::run_server(
         &addr,
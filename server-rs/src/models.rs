@@ -1,12 +1,20 @@
-use super::schema::{game_players, games, tournament_players, tournaments, users};
+use super::schema::{
+    game_moves, game_players, games, move_infos, rating_changes, ratings, tournament_players,
+    tournaments, users,
+};
+use diesel::sql_types::{BigInt, Double, Integer, Nullable, Text};
 
 pub type UserId = i32;
 pub type GameId = i32;
 pub type GamePlayerId = i32;
 pub type TournamentId = i32;
 pub type TournamentPlayerId = i32;
+pub type MoveInfoId = i32;
+pub type GameMoveId = i32;
+pub type RatingId = i32;
+pub type RatingChangeId = i32;
 
-#[derive(Queryable, AsChangeset)]
+#[derive(Queryable, AsChangeset, Clone)]
 #[table_name = "users"]
 pub struct User {
     pub id: UserId,
@@ -27,7 +35,7 @@ pub struct NewUser<'a> {
     pub api_key_hash: &'a str,
 }
 
-#[derive(Queryable, AsChangeset)]
+#[derive(Queryable, AsChangeset, Clone)]
 #[table_name = "games"]
 pub struct DBGame {
     pub id: GameId,
@@ -42,6 +50,9 @@ pub struct DBGame {
     pub current_move_start_ms: Option<i64>,
     pub turn_id: Option<i64>,
     pub tournament_id: Option<TournamentId>,
+    pub options: String,
+    pub draw_offer: Option<UserId>,
+    pub rated: bool,
 }
 
 #[derive(Insertable)]
@@ -58,9 +69,12 @@ pub struct NewDBGame<'a> {
     pub current_move_start_ms: Option<i64>,
     pub turn_id: Option<i64>,
     pub tournament_id: Option<TournamentId>,
+    pub options: &'a str,
+    pub draw_offer: Option<UserId>,
+    pub rated: bool,
 }
 
-#[derive(Queryable, AsChangeset)]
+#[derive(Queryable, AsChangeset, Clone)]
 #[table_name = "game_players"]
 pub struct GamePlayer {
     pub id: GamePlayerId,
@@ -69,6 +83,7 @@ pub struct GamePlayer {
     pub score: Option<f64>,
     pub waiting_for_move: bool,
     pub time_ms: i64,
+    pub moves_made: i32,
 }
 
 #[derive(Insertable)]
@@ -79,9 +94,144 @@ pub struct NewGamePlayer {
     pub score: Option<f64>,
     pub waiting_for_move: bool,
     pub time_ms: i64,
+    pub moves_made: i32,
+}
+
+#[derive(Queryable, PartialEq, Debug, Clone)]
+pub struct GameMove {
+    pub id: GameMoveId,
+    pub game_id: GameId,
+    /// Position of the move in the game (starting from 1)
+    pub ply: i32,
+    pub user_id: UserId,
+    /// The move, as recorded in the game's state
+    pub play: String,
+    /// Time the player took to make the move
+    pub time_spent_ms: i64,
+    /// Time the player had left after the move
+    pub clock_ms: i64,
+    /// When the move was made (in ms since the unix epoch)
+    pub played_at_ms: i64,
+    /// Evaluation the player reported for the move
+    pub eval: Option<f64>,
+}
+
+#[derive(Insertable, PartialEq, Debug)]
+#[table_name = "game_moves"]
+pub struct NewGameMove {
+    pub game_id: GameId,
+    pub ply: i32,
+    pub user_id: UserId,
+    pub play: String,
+    pub time_spent_ms: i64,
+    pub clock_ms: i64,
+    pub played_at_ms: i64,
+    pub eval: Option<f64>,
+}
+
+#[derive(Queryable, PartialEq, Debug, Clone)]
+pub struct MoveInfo {
+    pub id: MoveInfoId,
+    pub game_id: GameId,
+    pub user_id: UserId,
+    /// Which of the user's moves in the game this describes (starting from 1)
+    pub move_number: i32,
+    pub eval: Option<f64>,
+    pub depth: Option<i32>,
+    pub node_count: Option<i64>,
+    pub pv: Option<String>,
+}
+
+#[derive(Insertable, AsChangeset, PartialEq, Debug)]
+#[table_name = "move_infos"]
+pub struct NewMoveInfo {
+    pub game_id: GameId,
+    pub user_id: UserId,
+    pub move_number: i32,
+    pub eval: Option<f64>,
+    pub depth: Option<i32>,
+    pub node_count: Option<i64>,
+    pub pv: Option<String>,
+}
+
+/// A user's Glicko-2 rating in a game type
+#[derive(Queryable, PartialEq, Debug, Clone)]
+pub struct Rating {
+    pub id: RatingId,
+    pub user_id: UserId,
+    pub game_type: String,
+    pub rating: f64,
+    pub deviation: f64,
+    pub volatility: f64,
+}
+
+#[derive(Insertable, AsChangeset, PartialEq, Debug)]
+#[table_name = "ratings"]
+pub struct NewRating {
+    pub user_id: UserId,
+    pub game_type: String,
+    pub rating: f64,
+    pub deviation: f64,
+    pub volatility: f64,
+}
+
+/// A user's rating after a rated game
+#[derive(Queryable, PartialEq, Debug, Clone)]
+pub struct RatingChange {
+    pub id: RatingChangeId,
+    pub user_id: UserId,
+    pub game_type: String,
+    pub game_id: GameId,
+    pub rating: f64,
+    pub deviation: f64,
+    pub volatility: f64,
+    /// When the game ended (in ms since the unix epoch)
+    pub changed_at_ms: i64,
+}
+
+#[derive(Insertable, PartialEq, Debug)]
+#[table_name = "rating_changes"]
+pub struct NewRatingChange {
+    pub user_id: UserId,
+    pub game_type: String,
+    pub game_id: GameId,
+    pub rating: f64,
+    pub deviation: f64,
+    pub volatility: f64,
+    pub changed_at_ms: i64,
+}
+
+/// Totals over a user's finished games in a game type, along with their current rating
+#[derive(QueryableByName, PartialEq, Debug, Clone)]
+pub struct UserStats {
+    #[sql_type = "Integer"]
+    pub user_id: UserId,
+    #[sql_type = "Text"]
+    pub game_type: String,
+    #[sql_type = "BigInt"]
+    pub games: i64,
+    #[sql_type = "BigInt"]
+    pub wins: i64,
+    #[sql_type = "BigInt"]
+    pub losses: i64,
+    #[sql_type = "BigInt"]
+    pub ties: i64,
+    /// Sum of the user's scores in the games
+    #[sql_type = "Double"]
+    pub score: f64,
+    /// Average time the user took to make a move (None if no moves were recorded)
+    #[sql_type = "Nullable<Double>"]
+    pub avg_move_time_ms: Option<f64>,
+    /// Games the user lost by running out of time
+    #[sql_type = "BigInt"]
+    pub time_forfeits: i64,
+    #[sql_type = "Double"]
+    pub rating: f64,
+    #[sql_type = "Double"]
+    pub deviation: f64,
 }
 
-#[derive(Queryable, AsChangeset)]
+#[derive(Queryable, AsChangeset, Clone)]
 #[table_name = "tournaments"]
 pub struct DBTournament {
     pub id: TournamentId,
@@ -94,6 +244,7 @@ pub struct DBTournament {
     pub finished: bool,
     pub winner: Option<UserId>,
     pub options: String,
+    pub game_options: String,
 }
 
 #[derive(Insertable)]
@@ -108,6 +259,7 @@ pub struct NewDBTournament<'a> {
     pub finished: bool,
     pub winner: Option<UserId>,
     pub options: &'a str,
+    pub game_options: &'a str,
 }
 
 #[derive(Queryable, AsChangeset, PartialEq, Eq, Hash, Debug, Copy, Clone)]
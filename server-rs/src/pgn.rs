@@ -0,0 +1,523 @@
+use crate::db::Game;
+use crate::error::Error;
+use crate::games::chess_game::{chess960_start_fen, normalize_fen, ChessState, DEFAULT_BOARD};
+use crate::games::ended_game::{EndedGameInstance, ENDED_GAME_PREFIX};
+use crate::games::{Fmt, GameState, GameTurn};
+use crate::models::GamePlayer;
+use chess::{Board, Move, Player};
+
+// maximum length of a line of movetext
+const PGN_LINE_LENGTH: usize = 80;
+
+/// Format a player's remaining time as a clock comment
+fn clock_comment(time_ms: i64) -> String {
+    let secs = time_ms.max(0) / 1000;
+    format!(
+        "{{[%clk {}:{:02}:{:02}]}}",
+        secs / 3600,
+        (secs / 60) % 60,
+        secs % 60
+    )
+}
+
+/// Format a tag pair, escaping its value
+fn tag(name: &str, value: &str) -> String {
+    format!(
+        "[{} \"{}\"]\n",
+        name,
+        value.replace('\\', "\\\\").replace('"', "\\\"")
+    )
+}
+
+/// Export a chess game as PGN. names are the names of the game's players, in the same order as players.
+/// Each player's remaining time is given as a clock comment after their last move.
+pub fn game_to_pgn(game: &Game, players: &[GamePlayer], names: &[String]) -> Result<String, Error> {
+    let instance = game.instance.as_ref().ok_or(Error::PgnUnavailable)?;
+    if players.len() != 2 || names.len() != 2 {
+        return Err(Error::PgnUnavailable);
+    }
+
+    // ended games keep the chess game's state from when they ended
+    let state = format!("{}", Fmt(|f| instance.serialize(f)));
+    let ended = if state.starts_with(ENDED_GAME_PREFIX) {
+        Some(EndedGameInstance::from_state(&state).ok_or(Error::PgnUnavailable)?)
+    } else {
+        None
+    };
+    let chess_state = ChessState::parse(match &ended {
+        Some(ended) => ended.prev_state(),
+        None => &state,
+    });
+    let mut board = Board::from_fen(chess_state.start).ok_or(Error::PgnUnavailable)?;
+
+    let result = match (instance.turn(), instance.end_state()) {
+        (GameTurn::Finished, Some(GameState::Win(uid))) if uid == players[0].user_id => "1-0",
+        (GameTurn::Finished, Some(GameState::Win(_))) => "0-1",
+        (GameTurn::Finished, Some(GameState::Tie)) => "1/2-1/2",
+        _ => "*",
+    };
+
+    let mut pgn = String::new();
+    match game.tournament_id {
+        Some(id) => pgn.push_str(&tag("Event", &format!("Tournament {}", id))),
+        None => pgn.push_str(&tag("Event", &format!("Game {}", game.id))),
+    }
+    pgn.push_str(&tag("Site", "?"));
+    pgn.push_str(&tag("Date", "????.??.??"));
+    pgn.push_str(&tag(
+        "Round",
+        if game.tournament_id.is_some() {
+            "?"
+        } else {
+            "-"
+        },
+    ));
+    pgn.push_str(&tag("White", &names[0]));
+    pgn.push_str(&tag("Black", &names[1]));
+    pgn.push_str(&tag("Result", result));
+    if board.is_chess960() {
+        pgn.push_str(&tag("Variant", "Chess960"));
+    }
+    if chess_state.start != DEFAULT_BOARD {
+        pgn.push_str(&tag("SetUp", "1"));
+        pgn.push_str(&tag("FEN", chess_state.start));
+    }
+    if let Some(reason) = instance.end_reason() {
+        pgn.push_str(&tag("Termination", &reason));
+    }
+    pgn.push('\n');
+
+    // find each player's last move, which gets their clock comment
+    let first_player = board.player_to_move();
+    let last_move = |player: Player| {
+        let offset = if player == first_player { 0 } else { 1 };
+        (offset..chess_state.moves.len()).step_by(2).next_back()
+    };
+    let last_moves = [last_move(Player::White), last_move(Player::Black)];
+
+    let start_fen = board.to_string();
+    let mut move_num = start_fen
+        .split_whitespace()
+        .nth(5)
+        .and_then(|num| num.parse::<u32>().ok())
+        .unwrap_or(1);
+    let mut tokens = Vec::new();
+    for (i, move_str) in chess_state.moves.iter().enumerate() {
+        let player = board.player_to_move();
+        if player == Player::White {
+            tokens.push(format!("{}.", move_num));
+        } else if i == 0 {
+            tokens.push(format!("{}...", move_num));
+        }
+        let chess_move = match Move::from_str(move_str, &board) {
+            Some(chess_move) if chess_move.is_legal(&mut board) => chess_move,
+            _ => return Err(Error::PgnUnavailable),
+        };
+        tokens.push(chess_move.to_san(&board));
+        if last_moves[player as usize] == Some(i) {
+            tokens.push(clock_comment(players[player as usize].time_ms));
+        }
+        board.make_move(chess_move);
+        if player == Player::Black {
+            move_num += 1;
+        }
+    }
+    tokens.push(result.to_string());
+
+    // wrap movetext
+    let mut line_len = 0;
+    for token in tokens {
+        if line_len > 0 && line_len + 1 + token.len() > PGN_LINE_LENGTH {
+            pgn.push('\n');
+            line_len = 0;
+        } else if line_len > 0 {
+            pgn.push(' ');
+            line_len += 1;
+        }
+        line_len += token.len();
+        pgn.push_str(&token);
+    }
+    Ok(pgn)
+}
+
+/// A chess game read from PGN
+#[derive(Debug, PartialEq, Eq)]
+pub struct PgnGame {
+    /// type of game to create (chess or chess960)
+    pub game_type: &'static str,
+    /// options to create the game with, giving its start position
+    pub options: String,
+    /// moves, in long algebraic notation
+    pub moves: Vec<String>,
+    /// whether the game has a result, or is still in progress
+    pub finished: bool,
+    /// winner of a finished game, or None if it was a draw
+    pub winner: Option<Player>,
+    /// value of the Termination tag, if it's one of the values in the PGN standard
+    pub termination: Option<String>,
+    /// white and black's remaining time (in ms), from the last clock comment after each player's moves
+    pub clocks: [Option<i64>; 2],
+}
+
+// values of the Termination tag defined by the PGN standard
+const TERMINATIONS: [&str; 8] = [
+    "abandoned",
+    "adjudication",
+    "death",
+    "emergency",
+    "normal",
+    "rules infraction",
+    "time forfeit",
+    "unterminated",
+];
+
+// a tag pair's name and value
+type Tag = (String, String);
+
+// a piece of movetext
+#[derive(Debug, PartialEq, Eq)]
+enum Token {
+    Move(String),
+    Clock(i64),
+    Result(String),
+}
+
+/// Parse the time in a clock comment (h:mm:ss, with optional fractional seconds) into ms
+fn parse_clock(clock: &str) -> Option<i64> {
+    let mut ms = 0;
+    for (i, part) in clock.split(':').enumerate() {
+        if i > 2 {
+            return None;
+        }
+        ms = ms * 60 + (part.parse::<f64>().ok()? * 1000.0) as i64;
+    }
+    Some(ms)
+}
+
+/// Split PGN into its tag pairs and movetext tokens. Comments other than clock comments, variations, and NAGs are dropped.
+fn tokenize(pgn: &str) -> Result<(Vec<Tag>, Vec<Token>), Error> {
+    let mut tags = Vec::new();
+    let mut tokens = Vec::new();
+    let mut chars = pgn.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '[' => {
+                let name = chars.by_ref().take_while(|c| *c != '"').collect::<String>();
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('\\') => value.extend(chars.next()),
+                        Some('"') => break,
+                        Some(c) => value.push(c),
+                        None => return Err(Error::InvalidPgn("unterminated tag".to_string())),
+                    }
+                }
+                if chars.by_ref().find(|c| *c == ']').is_none() {
+                    return Err(Error::InvalidPgn("unterminated tag".to_string()));
+                }
+                tags.push((name.trim().to_string(), value));
+            }
+            '{' => {
+                let comment = chars.by_ref().take_while(|c| *c != '}').collect::<String>();
+                if let Some((_, clock)) = comment.split_once("[%clk ") {
+                    let clock = clock.split(']').next().unwrap_or("").trim();
+                    tokens.push(Token::Clock(parse_clock(clock).ok_or_else(|| {
+                        Error::InvalidPgn(format!("invalid clock: {}", clock))
+                    })?));
+                }
+            }
+            ';' => {
+                chars.by_ref().find(|c| *c == '\n');
+            }
+            '(' => {
+                // skip variations, which may be nested and contain comments
+                let mut depth = 1;
+                while depth > 0 {
+                    match chars.next() {
+                        Some('(') => depth += 1,
+                        Some(')') => depth -= 1,
+                        Some('{') => {
+                            chars.by_ref().find(|c| *c == '}');
+                        }
+                        Some(_) => {}
+                        None => {
+                            return Err(Error::InvalidPgn("unterminated variation".to_string()))
+                        }
+                    }
+                }
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let mut word = c.to_string();
+                while let Some(c) = chars.peek() {
+                    if c.is_whitespace() || "[]{}();".contains(*c) {
+                        break;
+                    }
+                    word.push(*c);
+                    chars.next();
+                }
+                match word.as_str() {
+                    "1-0" | "0-1" | "1/2-1/2" | "*" => tokens.push(Token::Result(word)),
+                    "e.p." => {}
+                    _ if word.starts_with('$') => {}
+                    _ => {
+                        // drop move numbers (1. or 1...), which may not be separated from the move. Castling can be
+                        // written with zeros (0-0), so only digits followed by a period are a move number.
+                        let after_digits = word.trim_start_matches(|c: char| c.is_ascii_digit());
+                        let san = if after_digits.starts_with('.') {
+                            after_digits.trim_start_matches('.')
+                        } else {
+                            &word
+                        };
+                        if !san.is_empty() {
+                            tokens.push(Token::Move(san.to_string()));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok((tags, tokens))
+}
+
+/// Read a chess game from PGN, checking that its moves are legal.
+/// Games in the Chess960 variant are read as chess960 games. If the PGN contains multiple games, only the first is read.
+pub fn pgn_to_game(pgn: &str) -> Result<PgnGame, Error> {
+    let (tags, tokens) = tokenize(pgn)?;
+    let tag = |name: &str| {
+        tags.iter()
+            .find(|(tag_name, _)| tag_name == name)
+            .map(|(_, value)| value.as_str())
+    };
+
+    let chess960 = match tag("Variant") {
+        None => false,
+        Some(variant) => match variant.to_lowercase().as_str() {
+            "standard" | "chess" => false,
+            "chess960" | "fischerandom" => true,
+            _ => {
+                return Err(Error::InvalidPgn(format!(
+                    "unsupported variant: {}",
+                    variant
+                )))
+            }
+        },
+    };
+    let (mut board, options) = match tag("FEN") {
+        Some(fen) => {
+            let start = normalize_fen(fen)
+                .ok_or_else(|| Error::InvalidPgn(format!("invalid start position: {}", fen)))?;
+            (Board::new(&start), format!("fen={}", start))
+        }
+        None if chess960 => (
+            Board::new(&chess960_start_fen(518)),
+            "index=518".to_string(),
+        ),
+        None => (Board::new(DEFAULT_BOARD), String::new()),
+    };
+
+    let mut game = PgnGame {
+        game_type: if chess960 { "chess960" } else { "chess" },
+        options,
+        moves: Vec::new(),
+        finished: false,
+        winner: None,
+        termination: tag("Termination").and_then(|termination| {
+            // only the standard values are kept, since the termination is stored as the reason the game ended
+            TERMINATIONS
+                .iter()
+                .find(|standard| standard.eq_ignore_ascii_case(termination.trim()))
+                .map(|standard| standard.to_string())
+        }),
+        clocks: [None, None],
+    };
+    let mut result = tag("Result").map(|result| result.to_string());
+    let mut last_player = None;
+    for token in tokens {
+        match token {
+            Token::Move(san) => {
+                let chess_move = Move::from_san(&san, &board)
+                    .ok_or_else(|| Error::InvalidPgn(format!("illegal move: {}", san)))?;
+                game.moves.push(chess_move.to_string());
+                last_player = Some(board.player_to_move());
+                board.make_move(chess_move);
+            }
+            Token::Clock(ms) => {
+                if let Some(player) = last_player {
+                    game.clocks[player as usize] = Some(ms);
+                }
+            }
+            // the result ends the game's movetext
+            Token::Result(token_result) => {
+                result = Some(token_result);
+                break;
+            }
+        }
+    }
+
+    match result.as_deref() {
+        Some("1-0") => game.winner = Some(Player::White),
+        Some("0-1") => game.winner = Some(Player::Black),
+        Some("1/2-1/2") => {}
+        _ => return Ok(game),
+    }
+    game.finished = true;
+    Ok(game)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn game_to_pgn_test() {
+        use crate::db::GameTimeCfg;
+        use crate::games::chess_game::ChessGame;
+        use crate::games::GameType;
+        use std::time::Duration;
+
+        let start = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1";
+        let state = format!(
+            "{},rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 0 2,[e7e5,g1f3]",
+            start
+        );
+        let game = Game {
+            id: 1,
+            owner_id: 1,
+            tournament_id: None,
+            game_type: "chess".to_string(),
+            options: String::new(),
+            instance: ChessGame(None).deserialize(&state, &[1, 2], ""),
+            time: GameTimeCfg {
+                per_move: Duration::from_millis(0),
+                sudden_death: Duration::from_millis(100000),
+            },
+            current_move_start: None,
+            turn_id: None,
+            draw_offer: None,
+            rated: true,
+        };
+        let player = |id, user_id, time_ms| GamePlayer {
+            id,
+            user_id,
+            game_id: 1,
+            score: None,
+            waiting_for_move: false,
+            time_ms,
+            moves_made: 0,
+        };
+        let players = [player(1, 1, 50000), player(2, 2, 100000)];
+        let names = ["Test \"1\"".to_string(), "Test2".to_string()];
+        assert_eq!(
+            game_to_pgn(&game, &players, &names),
+            Ok(format!(
+                "[Event \"Game 1\"]\n\
+                 [Site \"?\"]\n\
+                 [Date \"????.??.??\"]\n\
+                 [Round \"-\"]\n\
+                 [White \"Test \\\"1\\\"\"]\n\
+                 [Black \"Test2\"]\n\
+                 [Result \"*\"]\n\
+                 [SetUp \"1\"]\n\
+                 [FEN \"{}\"]\n\
+                 \n\
+                 1... e5 {{[%clk 0:01:40]}} 2. Nf3 {{[%clk 0:00:50]}} *",
+                start
+            ))
+        );
+    }
+
+    #[test]
+    fn pgn_to_game_test() {
+        let game = pgn_to_game(
+            "[Event \"Test, \\\"1\\\"\"]\n\
+             [Result \"0-1\"]\n\
+             [Termination \"time forfeit\"]\n\
+             \n\
+             1. e4 {[%clk 0:01:40]} e5 $1 {[%clk 0:01:39.5]} 2.Nf3 (2. f4 {King's Gambit} (2. Nc3)) ; comment\n\
+             2... Nc6 {book} 0-1\n\
+             \n\
+             [Event \"Next game\"]\n\
+             1. d4 *",
+        );
+        assert_eq!(
+            game,
+            Ok(PgnGame {
+                game_type: "chess",
+                options: String::new(),
+                moves: vec![
+                    "e2e4".to_string(),
+                    "e7e5".to_string(),
+                    "g1f3".to_string(),
+                    "b8c6".to_string()
+                ],
+                finished: true,
+                winner: Some(Player::Black),
+                termination: Some("time forfeit".to_string()),
+                clocks: [Some(100000), Some(99500)],
+            })
+        );
+
+        let start = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1";
+        let game =
+            pgn_to_game(&format!("[SetUp \"1\"]\n[FEN \"{}\"]\n\n1... e5 *", start)).unwrap();
+        assert_eq!(game.options, format!("fen={}", Board::new(start)));
+        assert_eq!(game.moves, vec!["e7e5".to_string()]);
+        assert!(!game.finished);
+
+        // castling can be written with zeros
+        let game = pgn_to_game(
+            "1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 4. 0-0 d6 5.d3 Bg4 6.Nc3 Qd7 7.Be3 0-0-0 *",
+        )
+        .unwrap();
+        assert_eq!(
+            &game.moves[6..],
+            ["e1g1", "d7d6", "d2d3", "c8g4", "b1c3", "d8d7", "c1e3", "e8c8"]
+        );
+
+        // other terminations are dropped
+        for (termination, expected) in [
+            ("Time forfeit", Some("time forfeit")),
+            ("Time Expired", None),
+            ("won, on time\n", None),
+        ]
+        .iter()
+        {
+            let game = pgn_to_game(&format!("[Termination \"{}\"]\n\n1. e4 1-0", termination));
+            assert_eq!(
+                game.unwrap().termination,
+                expected.map(|termination| termination.to_string())
+            );
+        }
+
+        let game = pgn_to_game("[Variant \"Chess960\"]\n\n1. e4 1/2-1/2").unwrap();
+        assert_eq!(game.game_type, "chess960");
+        assert_eq!(game.options, "index=518");
+        assert!(game.finished);
+        assert_eq!(game.winner, None);
+
+        assert_eq!(
+            pgn_to_game("1. e4 e4"),
+            Err(Error::InvalidPgn("illegal move: e4".to_string()))
+        );
+        assert_eq!(
+            pgn_to_game("[Variant \"Atomic\"]\n\n1. e4"),
+            Err(Error::InvalidPgn("unsupported variant: Atomic".to_string()))
+        );
+        assert_eq!(
+            pgn_to_game("1. e4 {[%clk 1:ab]}"),
+            Err(Error::InvalidPgn("invalid clock: 1:ab".to_string()))
+        );
+        assert_eq!(
+            pgn_to_game("[Event \"Test"),
+            Err(Error::InvalidPgn("unterminated tag".to_string()))
+        );
+    }
+
+    #[test]
+    fn clock_comment_test() {
+        assert_eq!(clock_comment(100000), "{[%clk 0:01:40]}");
+        assert_eq!(clock_comment(3723999), "{[%clk 1:02:03]}");
+        assert_eq!(clock_comment(-5), "{[%clk 0:00:00]}");
+    }
+}
@@ -0,0 +1,158 @@
+use std::f64::consts::PI;
+
+/// Rating given to players who haven't played a rated game
+pub const DEFAULT_RATING: f64 = 1500.0;
+/// Rating deviation given to players who haven't played a rated game
+pub const DEFAULT_DEVIATION: f64 = 350.0;
+/// Volatility given to players who haven't played a rated game
+pub const DEFAULT_VOLATILITY: f64 = 0.06;
+// constrains the change in volatility over time (the system constant τ)
+const TAU: f64 = 0.5;
+// conversion between the glicko scale and the glicko-2 scale
+const SCALE: f64 = 173.7178;
+// tolerance used when solving for the new volatility
+const CONVERGENCE_TOLERANCE: f64 = 0.000001;
+
+/// A Glicko-2 rating (see http://www.glicko.net/glicko/glicko2.pdf), on the glicko scale
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Glicko {
+    pub rating: f64,
+    pub deviation: f64,
+    pub volatility: f64,
+}
+
+impl Default for Glicko {
+    fn default() -> Self {
+        Glicko {
+            rating: DEFAULT_RATING,
+            deviation: DEFAULT_DEVIATION,
+            volatility: DEFAULT_VOLATILITY,
+        }
+    }
+}
+
+// reduces the impact of a game based on the opponent's deviation
+fn g(phi: f64) -> f64 {
+    1.0 / (1.0 + 3.0 * phi * phi / (PI * PI)).sqrt()
+}
+
+// expected score against an opponent
+fn expected(mu: f64, opp_mu: f64, opp_phi: f64) -> f64 {
+    1.0 / (1.0 + (-g(opp_phi) * (mu - opp_mu)).exp())
+}
+
+impl Glicko {
+    /// Find the rating after a rating period, given each opponent's rating and the score against them (1 for a win,
+    /// 0.5 for a tie, and 0 for a loss). Opponents' ratings should be from before the period.
+    pub fn update(&self, results: &[(Glicko, f64)]) -> Glicko {
+        let mu = (self.rating - DEFAULT_RATING) / SCALE;
+        let phi = self.deviation / SCALE;
+        let sigma = self.volatility;
+        // without games, only the deviation changes
+        if results.is_empty() {
+            return Glicko {
+                deviation: (phi * phi + sigma * sigma).sqrt() * SCALE,
+                ..*self
+            };
+        }
+
+        let opponents = results
+            .iter()
+            .map(|(opp, score)| {
+                let opp_mu = (opp.rating - DEFAULT_RATING) / SCALE;
+                let opp_phi = opp.deviation / SCALE;
+                (g(opp_phi), expected(mu, opp_mu, opp_phi), *score)
+            })
+            .collect::<Vec<(f64, f64, f64)>>();
+        // estimated variance of the rating based only on game outcomes
+        let v = 1.0
+            / opponents
+                .iter()
+                .map(|(g, e, _)| g * g * e * (1.0 - e))
+                .sum::<f64>();
+        let improvement = opponents.iter().map(|(g, e, s)| g * (s - e)).sum::<f64>();
+        let delta = v * improvement;
+
+        // solve for the new volatility with the Illinois algorithm
+        let a = (sigma * sigma).ln();
+        let f = |x: f64| {
+            let ex = x.exp();
+            ex * (delta * delta - phi * phi - v - ex) / (2.0 * (phi * phi + v + ex).powi(2))
+                - (x - a) / (TAU * TAU)
+        };
+        let mut lower = a;
+        let mut upper = if delta * delta > phi * phi + v {
+            (delta * delta - phi * phi - v).ln()
+        } else {
+            let mut k = 1.0;
+            while f(a - k * TAU) < 0.0 {
+                k += 1.0;
+            }
+            a - k * TAU
+        };
+        let mut f_lower = f(lower);
+        let mut f_upper = f(upper);
+        while (upper - lower).abs() > CONVERGENCE_TOLERANCE {
+            let mid = lower + (lower - upper) * f_lower / (f_upper - f_lower);
+            let f_mid = f(mid);
+            if f_mid * f_upper <= 0.0 {
+                lower = upper;
+                f_lower = f_upper;
+            } else {
+                f_lower /= 2.0;
+            }
+            upper = mid;
+            f_upper = f_mid;
+        }
+        let new_sigma = (lower / 2.0).exp();
+
+        let pre_period_phi = (phi * phi + new_sigma * new_sigma).sqrt();
+        let new_phi = 1.0 / (1.0 / (pre_period_phi * pre_period_phi) + 1.0 / v).sqrt();
+        let new_mu = mu + new_phi * new_phi * improvement;
+        Glicko {
+            rating: new_mu * SCALE + DEFAULT_RATING,
+            deviation: new_phi * SCALE,
+            volatility: new_sigma,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rating(rating: f64, deviation: f64) -> Glicko {
+        Glicko {
+            rating,
+            deviation,
+            volatility: DEFAULT_VOLATILITY,
+        }
+    }
+
+    #[test]
+    fn update() {
+        // example from the glicko-2 paper
+        let player = rating(1500.0, 200.0);
+        let new = player.update(&[
+            (rating(1400.0, 30.0), 1.0),
+            (rating(1550.0, 100.0), 0.0),
+            (rating(1700.0, 300.0), 0.0),
+        ]);
+        assert!((new.rating - 1464.06).abs() < 0.01);
+        assert!((new.deviation - 151.52).abs() < 0.01);
+        assert!((new.volatility - 0.05999).abs() < 0.00001);
+
+        // without games, deviation increases
+        let idle = player.update(&[]);
+        assert_eq!(idle.rating, 1500.0);
+        assert!(idle.deviation > 200.0);
+
+        // a win against an equal opponent is worth as much as a loss
+        let win = Glicko::default().update(&[(Glicko::default(), 1.0)]);
+        let loss = Glicko::default().update(&[(Glicko::default(), 0.0)]);
+        let tie = Glicko::default().update(&[(Glicko::default(), 0.5)]);
+        assert!(win.rating > DEFAULT_RATING);
+        assert!(((win.rating - DEFAULT_RATING) - (DEFAULT_RATING - loss.rating)).abs() < 0.000001);
+        assert!((tie.rating - DEFAULT_RATING).abs() < 0.000001);
+    }
+}
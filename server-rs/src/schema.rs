@@ -1,3 +1,17 @@
+table! {
+    game_moves (id) {
+        id -> Int4,
+        game_id -> Int4,
+        ply -> Int4,
+        user_id -> Int4,
+        play -> Text,
+        time_spent_ms -> Int8,
+        clock_ms -> Int8,
+        played_at_ms -> Int8,
+        eval -> Nullable<Float8>,
+    }
+}
+
 table! {
     game_players (id) {
         id -> Int4,
@@ -6,6 +20,7 @@ table! {
         score -> Nullable<Float8>,
         waiting_for_move -> Bool,
         time_ms -> Int8,
+        moves_made -> Int4,
     }
 }
 
@@ -23,6 +38,46 @@ table! {
         current_move_start_ms -> Nullable<Int8>,
         turn_id -> Nullable<Int8>,
         tournament_id -> Nullable<Int4>,
+        options -> Text,
+        draw_offer -> Nullable<Int4>,
+        rated -> Bool,
+    }
+}
+
+table! {
+    move_infos (id) {
+        id -> Int4,
+        game_id -> Int4,
+        user_id -> Int4,
+        move_number -> Int4,
+        eval -> Nullable<Float8>,
+        depth -> Nullable<Int4>,
+        node_count -> Nullable<Int8>,
+        pv -> Nullable<Text>,
+    }
+}
+
+table! {
+    rating_changes (id) {
+        id -> Int4,
+        user_id -> Int4,
+        game_type -> Text,
+        game_id -> Int4,
+        rating -> Float8,
+        deviation -> Float8,
+        volatility -> Float8,
+        changed_at_ms -> Int8,
+    }
+}
+
+table! {
+    ratings (id) {
+        id -> Int4,
+        user_id -> Int4,
+        game_type -> Text,
+        rating -> Float8,
+        deviation -> Float8,
+        volatility -> Float8,
     }
 }
 
@@ -49,6 +104,7 @@ table! {
         finished -> Bool,
         winner -> Nullable<Int4>,
         options -> Text,
+        game_options -> Text,
     }
 }
 
@@ -63,4 +119,14 @@ table! {
     }
 }
 
-allow_tables_to_appear_in_same_query!(game_players, games, tournament_players, tournaments, users,);
+allow_tables_to_appear_in_same_query!(
+    game_moves,
+    game_players,
+    games,
+    move_infos,
+    rating_changes,
+    ratings,
+    tournament_players,
+    tournaments,
+    users,
+);
@@ -1,9 +1,12 @@
 use crate::apikey::ApiKey;
 use crate::cmd::{ClientCommand, ProtocolVersion, ServerCommand};
-use crate::db::{init_db_pool, DBWrapper, Game, GameTimeCfg, PgPool, PlayerTimeExpiry, Tournament};
+use crate::db::{DBWrapper, Game, GameTimeCfg, PlayerTimeExpiry, Tournament};
 use crate::error::Error;
+use crate::games::ended_game::TIME_EXPIRED_REASON;
 use crate::games::{Fmt, GameState, GameTurn, GameTypeMap};
 use crate::models::{GameId, GamePlayer, TournamentId, TournamentPlayer, User, UserId};
+use crate::pgn;
+use crate::storage::{open_storage, StoragePool};
 use crate::tournament::{TournamentCfg, TournamentTypeMap};
 use futures_channel::mpsc;
 use futures_util::{future, pin_mut, stream::TryStreamExt, StreamExt};
@@ -47,6 +50,8 @@ type ClientTxChannel = mpsc::UnboundedSender<Message>;
 struct ClientConnInfo {
     tx: ClientTxChannel,
     protocol: ProtocolVersion,
+    // the game the client last moved in (legacy clients send info without a game id)
+    last_moved_game: Option<GameId>,
 }
 
 /// A collection of connected clients. PeerMap contains a mapping of topics to clients addresses, and client addresses to a communication channel.
@@ -70,6 +75,7 @@ impl ClientMap {
             ClientConnInfo {
                 tx,
                 protocol: ProtocolVersion::Legacy,
+                last_moved_game: None,
             },
         );
     }
@@ -175,6 +181,18 @@ impl ClientMap {
         self.channels[client].protocol
     }
 
+    /// Get the game a connection last made a move in
+    pub fn last_moved_game(&self, client: &SocketAddr) -> Option<GameId> {
+        self.channels[client].last_moved_game
+    }
+
+    /// Set the game a connection last made a move in
+    pub fn set_last_moved_game(&mut self, client: &SocketAddr, game_id: GameId) {
+        if let Some(conn) = self.channels.get_mut(client) {
+            conn.last_moved_game = Some(game_id);
+        }
+    }
+
     /// Set a connection's protocol version
     pub fn set_protocol_ver(&mut self, client: &SocketAddr, ver: ProtocolVersion) {
         let user = self.is_user(client);
@@ -202,7 +220,11 @@ impl ClientMap {
 }
 
 /// Convert a game and its players to a game command
-fn serialize_game_state(game: &Game, players: &[GamePlayer]) -> ServerCommand {
+fn serialize_game_state(
+    game: &Game,
+    players: &[GamePlayer],
+    db: &DBWrapper,
+) -> Result<ServerCommand, Error> {
     let (finished, winner, state) = match &game.instance {
         &None => (false, GameState::InProgress, None),
         Some(inst) => {
@@ -226,7 +248,14 @@ fn serialize_game_state(game: &Game, players: &[GamePlayer]) -> ServerCommand {
         ),
         None => None,
     };
-    ServerCommand::Game {
+    let players = players
+        .iter()
+        .map(|p| {
+            let rating = db.find_rating(p.user_id, &game.game_type)?;
+            Ok((p.user_id, p.score, p.time_ms, rating.rating))
+        })
+        .collect::<Result<Vec<(UserId, Option<f64>, i64, f64)>, Error>>()?;
+    Ok(ServerCommand::Game {
         id: game.id,
         game_type: game.game_type.clone(),
         owner: game.owner_id,
@@ -235,12 +264,10 @@ fn serialize_game_state(game: &Game, players: &[GamePlayer]) -> ServerCommand {
         winner,
         time_dur: game.time.to_ms(),
         current_player_time_for_move,
-        players: players
-            .iter()
-            .map(|p| (p.user_id, p.score, p.time_ms))
-            .collect::<Vec<(UserId, Option<f64>, i64)>>(),
+        players,
+        draw_offer: game.draw_offer,
         state,
-    }
+    })
 }
 
 /// Convert a tournament into a tournament command
@@ -259,6 +286,10 @@ fn serialize_tournament_state(
             .instance
             .serialize_games(tourney.id, &tourney.cfg, f, db))
     );
+    let players = players
+        .into_iter()
+        .map(|p| Ok((p, db.find_rating(p.user_id, &tourney.cfg.game_type)?.rating)))
+        .collect::<Result<Vec<(TournamentPlayer, f64)>, Error>>()?;
 
     Ok(ServerCommand::Tournament {
         id: tourney.id,
@@ -285,7 +316,7 @@ fn serialize_tournament_games(
     let games = db.find_tournament_games(id)?;
     for dbgame in games.into_iter() {
         let (game, players) = db.dbgame_to_game_and_players(dbgame)?;
-        res.push(serialize_game_state(&game, &*players));
+        res.push(serialize_game_state(&game, &*players, db)?);
     }
     Ok(res)
 }
@@ -323,6 +354,7 @@ fn serialize_game_for_player(
                             time_for_turn_ms: time_remaining.per_move_ms,
                             time_ms: time_remaining.sudden_death_ms,
                             state: Some(state),
+                            draw_offer: matches!(game.draw_offer, Some(uid) if uid != user_id),
                         },
                         ProtocolVersion::Legacy => ServerCommand::Position { state: Some(state) },
                     },
@@ -385,7 +417,7 @@ fn handle_game_update(
     db: &DBWrapper,
     clients: &Mutex<ClientMap>,
 ) {
-    let state_cmd = serialize_game_state(game, players);
+    let state_cmd = serialize_game_state(game, players, db).unwrap_or_else(ServerCommand::Error);
     let state_msg = Message::from(state_cmd.to_string());
     let clients = clients.lock().unwrap();
     // send game to all observers
@@ -437,7 +469,7 @@ fn handle_tournament_update(
 fn handle_player_expiry(
     expiry: PlayerTimeExpiry,
     client_map: &Mutex<ClientMap>,
-    db_pool: &PgPool,
+    db_pool: &dyn StoragePool,
     game_type_map: &GameTypeMap,
     tournament_type_map: &TournamentTypeMap,
     time_expiry_tx: mpsc::UnboundedSender<PlayerTimeExpiry>,
@@ -449,7 +481,7 @@ fn handle_player_expiry(
         |tourney: &Tournament, players: &[TournamentPlayer], db: &DBWrapper| {
             handle_tournament_update(tourney, players, db, client_map);
         };
-    let db = DBWrapper::from_pg_pool(
+    let db = DBWrapper::from_pool(
         db_pool,
         game_type_map,
         tournament_type_map,
@@ -471,7 +503,12 @@ fn handle_player_expiry(
                 }
             }
 
-            db.end_game(&mut game, &mut *players, winner, "Time Expired".to_string())?;
+            db.end_game(
+                &mut game,
+                &mut *players,
+                winner,
+                TIME_EXPIRED_REASON.to_string(),
+            )?;
         }
     }
     Ok(())
@@ -482,7 +519,7 @@ fn handle_cmd(
     cmd: &ClientCommand,
     client_map: &Mutex<ClientMap>,
     client_addr: &SocketAddr,
-    db_pool: &PgPool,
+    db_pool: &dyn StoragePool,
     game_type_map: &GameTypeMap,
     tournament_type_map: &TournamentTypeMap,
     player_expiry_tx: mpsc::UnboundedSender<PlayerTimeExpiry>,
@@ -502,7 +539,7 @@ fn handle_cmd(
 
     // get a database connection
     let db = || {
-        DBWrapper::from_pg_pool(
+        DBWrapper::from_pool(
             db_pool,
             game_type_map,
             tournament_type_map,
@@ -639,11 +676,13 @@ fn handle_cmd(
             game_type,
             total_time,
             time_per_move,
+            options,
         } => {
             let db = &db()?;
             let user = user(db, client_addr, clients())?;
             let game = db.new_game(
                 *game_type,
+                *options,
                 user.id,
                 GameTimeCfg::from_ms(*time_per_move, *total_time),
                 None,
@@ -651,9 +690,82 @@ fn handle_cmd(
             Ok(Some(ServerCommand::NewGame(game.id)))
         }
         ObserveGame(game_id) => {
-            let (game, players) = db()?.find_game(*game_id)?;
-            clients().add_to_topic(Topic::Game(*game_id), *client_addr);
-            Ok(Some(serialize_game_state(&game, &players)))
+            let db = &db()?;
+            let (game, players) = db.find_game(*game_id)?;
+            let mut clients = clients();
+            // send info already reported about the game's moves
+            for info in db.find_move_infos(*game_id)? {
+                let cmd = ServerCommand::MoveInfo(info);
+                clients.send(client_addr, Message::from(cmd.to_string()))?;
+            }
+            clients.add_to_topic(Topic::Game(*game_id), *client_addr);
+            Ok(Some(serialize_game_state(&game, &players, db)?))
+        }
+        Pgn(game_id) => {
+            let db = &db()?;
+            let (game, players) = db.find_game(*game_id)?;
+            let names = players
+                .iter()
+                .map(|player| Ok(db.find_user(player.user_id)?.name))
+                .collect::<Result<Vec<String>, Error>>()?;
+            Ok(Some(ServerCommand::Pgn {
+                id: *game_id,
+                pgn: pgn::game_to_pgn(&game, &players, &names)?,
+            }))
+        }
+        ImportPgn {
+            white,
+            black,
+            total_time,
+            time_per_move,
+            pgn,
+        } => {
+            let db = &db()?;
+            let user = user(db, client_addr, clients())?;
+            let game_id = db.import_game(
+                user.id,
+                *white,
+                *black,
+                GameTimeCfg::from_ms(*time_per_move, *total_time),
+                &pgn::pgn_to_game(pgn)?,
+            )?;
+            Ok(Some(ServerCommand::ImportPgn(game_id)))
+        }
+        Rating { user_id, game_type } => {
+            let db = &db()?;
+            db.find_user(*user_id)?;
+            if !game_type_map.contains_key(*game_type) {
+                return Err(Error::NoSuchGameType(game_type.to_string()));
+            }
+            Ok(Some(ServerCommand::Rating {
+                user_id: *user_id,
+                game_type: game_type.to_string(),
+                rating: db.find_rating(*user_id, *game_type)?,
+                changes: db.find_rating_changes(*user_id, *game_type)?,
+            }))
+        }
+        Leaderboard {
+            game_type,
+            offset,
+            limit,
+        } => {
+            let db = &db()?;
+            if !game_type_map.contains_key(*game_type) {
+                return Err(Error::NoSuchGameType(game_type.to_string()));
+            }
+            Ok(Some(ServerCommand::Leaderboard {
+                game_type: game_type.to_string(),
+                offset: *offset,
+                stats: db.find_leaderboard(*game_type, *offset, *limit)?,
+            }))
+        }
+        UserStats(user_id) => {
+            let db = &db()?;
+            db.find_user(*user_id)?;
+            Ok(Some(ServerCommand::UserStats {
+                user_id: *user_id,
+                stats: db.find_user_stats(*user_id)?,
+            }))
         }
         StopObserveGame(game_id) => {
             clients().remove_from_topic(Topic::Game(*game_id), client_addr);
@@ -674,6 +786,30 @@ fn handle_cmd(
             db.start_game(*game_id, user(db, client_addr, clients())?.id)?;
             Ok(None)
         }
+        Resign(game_id) => {
+            expect_proto(ProtocolVersion::Current)?;
+            let db = &db()?;
+            db.resign_game(*game_id, user(db, client_addr, clients())?.id)?;
+            Ok(None)
+        }
+        OfferDraw(game_id) => {
+            expect_proto(ProtocolVersion::Current)?;
+            let db = &db()?;
+            db.offer_draw(*game_id, user(db, client_addr, clients())?.id)?;
+            Ok(None)
+        }
+        AcceptDraw(game_id) => {
+            expect_proto(ProtocolVersion::Current)?;
+            let db = &db()?;
+            db.accept_draw(*game_id, user(db, client_addr, clients())?.id)?;
+            Ok(None)
+        }
+        DeclineDraw(game_id) => {
+            expect_proto(ProtocolVersion::Current)?;
+            let db = &db()?;
+            db.decline_draw(*game_id, user(db, client_addr, clients())?.id)?;
+            Ok(None)
+        }
         Play { id, play } => {
             expect_proto(ProtocolVersion::Current)?;
             let db = &db()?;
@@ -690,16 +826,42 @@ fn handle_cmd(
                 None => Err(Error::NotTurn),
                 Some(game_id) => {
                     db.make_move(game_id, user.id, *play)?;
+                    clients().set_last_moved_game(client_addr, game_id);
                     Ok(None)
                 }
             }
         }
+        Info { id, info } => {
+            expect_proto(ProtocolVersion::Current)?;
+            let db = &db()?;
+            let user = user(db, client_addr, clients())?;
+            if let Some(move_info) = db.report_move_info(*id, user.id, *info)? {
+                let msg = Message::from(ServerCommand::MoveInfo(move_info).to_string());
+                clients().publish(Topic::Game(*id), &msg)?;
+            }
+            Ok(None)
+        }
+        LegacyInfo(info) => {
+            expect_proto(ProtocolVersion::Legacy)?;
+            let db = &db()?;
+            let user = user(db, client_addr, clients())?;
+            let game_id = match clients().last_moved_game(client_addr) {
+                Some(game_id) => game_id,
+                None => return Err(Error::InvalidInfo("you haven't made a move".to_string())),
+            };
+            if let Some(move_info) = db.report_move_info(game_id, user.id, *info)? {
+                let msg = Message::from(ServerCommand::MoveInfo(move_info).to_string());
+                clients().publish(Topic::Game(game_id), &msg)?;
+            }
+            Ok(None)
+        }
         NewTournament {
             tourney_type,
             game_type,
             total_time,
             time_per_move,
             options,
+            game_options,
         } => {
             let db = &db()?;
             let user = user(db, client_addr, clients())?;
@@ -709,6 +871,7 @@ fn handle_cmd(
                 &TournamentCfg {
                     game_type: game_type.to_string(),
                     time_cfg: GameTimeCfg::from_ms(*time_per_move, *total_time),
+                    game_options: game_options.to_string(),
                 },
                 *options,
             )?;
@@ -760,7 +923,7 @@ fn handle_message(
     msg: &Message,
     client_map: &Mutex<ClientMap>,
     client_addr: &SocketAddr,
-    db_pool: &PgPool,
+    db_pool: &dyn StoragePool,
     game_type_map: &GameTypeMap,
     tournament_type_map: &TournamentTypeMap,
     player_expiry_tx: mpsc::UnboundedSender<PlayerTimeExpiry>,
@@ -813,7 +976,7 @@ async fn handle_connection(
     client_map: ClientMapLock,
     raw_stream: TcpStream,
     addr: SocketAddr,
-    db_pool: Arc<PgPool>,
+    db_pool: Arc<dyn StoragePool>,
     game_type_map: Arc<GameTypeMap>,
     tournament_type_map: Arc<TournamentTypeMap>,
     player_expiry_tx: mpsc::UnboundedSender<PlayerTimeExpiry>,
@@ -833,7 +996,7 @@ async fn handle_connection(
             &msg,
             &*client_map,
             &addr,
-            &db_pool,
+            &*db_pool,
             &game_type_map,
             &*tournament_type_map,
             player_expiry_tx.clone(),
@@ -850,9 +1013,35 @@ async fn handle_connection(
     client_map.lock().unwrap().remove_client(&addr);
 }
 
+/// Restart the expiry timers of games that were waiting for a move when the server last stopped
+fn restore_game_timers(
+    client_map: &Mutex<ClientMap>,
+    db_pool: &dyn StoragePool,
+    game_type_map: &GameTypeMap,
+    tournament_type_map: &TournamentTypeMap,
+    time_expiry_tx: mpsc::UnboundedSender<PlayerTimeExpiry>,
+) -> Result<usize, Error> {
+    let game_update_callback = |game: &Game, players: &[GamePlayer], db: &DBWrapper| {
+        handle_game_update(game, players, db, client_map);
+    };
+    let tournament_update_callback =
+        |tourney: &Tournament, players: &[TournamentPlayer], db: &DBWrapper| {
+            handle_tournament_update(tourney, players, db, client_map);
+        };
+    let db = DBWrapper::from_pool(
+        db_pool,
+        game_type_map,
+        tournament_type_map,
+        game_update_callback,
+        tournament_update_callback,
+        time_expiry_tx,
+    )?;
+    db.restore_game_timers()
+}
+
 fn run_expiry_rx(
     clients: Arc<Mutex<ClientMap>>,
-    db_pool: Arc<PgPool>,
+    db_pool: Arc<dyn StoragePool>,
     game_type_map: Arc<GameTypeMap>,
     tournament_type_map: Arc<TournamentTypeMap>,
     expiry_tx: mpsc::UnboundedSender<PlayerTimeExpiry>,
@@ -882,7 +1071,8 @@ pub fn run_server<'a>(
     async move {
         // Create application state
         let clients = Arc::new(Mutex::new(ClientMap::default()));
-        let db_pool = Arc::new(init_db_pool(db_url).expect("Can't open database"));
+        let db_pool: Arc<dyn StoragePool> =
+            Arc::from(open_storage(db_url).expect("Can't open database"));
 
         // Setup a tcp server and accept connections
         let try_socket = TcpListener::bind(url).await;
@@ -899,6 +1089,18 @@ pub fn run_server<'a>(
             expiry_tx.clone(),
             expiry_rx,
         );
+        // games that were in progress when the server stopped need their timers restarted
+        match restore_game_timers(
+            &*clients,
+            &*db_pool,
+            &*game_type_map,
+            &*tournament_type_map,
+            expiry_tx.clone(),
+        ) {
+            Ok(count) if count > 0 => println!("Restored timers for {} games", count),
+            Ok(_) => {}
+            Err(e) => eprintln!("failed to restore game timers: {}", e),
+        }
 
         while let Ok((stream, addr)) = listener.accept().await {
             tokio::spawn(handle_connection(
@@ -913,3 +1115,78 @@ pub fn run_server<'a>(
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::games::chess_game::ChessGame;
+    use std::time::SystemTime;
+
+    #[tokio::test]
+    async fn restore_game_timers_after_restart() {
+        let mut game_type_map: GameTypeMap = HashMap::new();
+        game_type_map.insert("chess", Box::new(ChessGame(None)));
+        let game_type_map = Arc::new(game_type_map);
+        let tournament_type_map: Arc<TournamentTypeMap> = Arc::new(HashMap::new());
+        let clients = Arc::new(Mutex::new(ClientMap::default()));
+        let db_pool: Arc<dyn StoragePool> = Arc::from(open_storage("memory").unwrap());
+
+        // start two games on a server whose timers are lost when it stops
+        let (old_expiry_tx, _old_expiry_rx) = mpsc::unbounded::<PlayerTimeExpiry>();
+        let db = DBWrapper::from_pool(
+            &*db_pool,
+            &*game_type_map,
+            &*tournament_type_map,
+            |_, _, _| {},
+            |_, _, _| {},
+            old_expiry_tx,
+        )
+        .unwrap();
+        let white = db.new_tmp_user("White").unwrap().id;
+        let black = db.new_tmp_user("Black").unwrap().id;
+        let start_game = || {
+            let time = GameTimeCfg::from_ms(0, 60000);
+            let id = db.new_game("chess", "", white, time, None).unwrap().id;
+            db.join_game(id, white).unwrap();
+            db.join_game(id, black).unwrap();
+            db.start_game(id, white).unwrap();
+            id
+        };
+        let running = start_game();
+        let expired = start_game();
+        // white's time in the second game ran out while the server was stopped
+        let (mut game, _) = db.find_game(expired).unwrap();
+        game.current_move_start = Some(SystemTime::now() - Duration::from_secs(120));
+        db.save_game(&game).unwrap();
+
+        // restart the timers, as the server does when it starts
+        let (expiry_tx, expiry_rx) = mpsc::unbounded::<PlayerTimeExpiry>();
+        run_expiry_rx(
+            clients.clone(),
+            db_pool.clone(),
+            game_type_map.clone(),
+            tournament_type_map.clone(),
+            expiry_tx.clone(),
+            expiry_rx,
+        );
+        let restored = restore_game_timers(
+            &*clients,
+            &*db_pool,
+            &*game_type_map,
+            &*tournament_type_map,
+            expiry_tx,
+        )
+        .unwrap();
+        assert_eq!(restored, 2);
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        // the game that ran out of time ends right away, and the other keeps waiting for white
+        let (game, _) = db.find_game(expired).unwrap();
+        let instance = game.instance.unwrap();
+        assert_eq!(instance.turn(), GameTurn::Finished);
+        assert!(matches!(instance.end_state(), Some(GameState::Win(uid)) if uid == black));
+        let (game, _) = db.find_game(running).unwrap();
+        assert_eq!(game.instance.unwrap().turn(), GameTurn::Turn(white));
+        assert_eq!(db.restore_game_timers().unwrap(), 1);
+    }
+}
@@ -0,0 +1,770 @@
+use crate::error::Error;
+use crate::games::ended_game::{EndedGameInstance, TIME_EXPIRED_REASON};
+use crate::games::GameInstance;
+use crate::models::{
+    DBGame, DBTournament, GameId, GameMove, GamePlayer, GamePlayerId, MoveInfo, NewDBGame,
+    NewDBTournament, NewGameMove, NewGamePlayer, NewMoveInfo, NewRating, NewRatingChange,
+    NewTournamentPlayer, NewUser, Rating, RatingChange, TournamentId, TournamentPlayer,
+    TournamentPlayerId, User, UserId, UserStats,
+};
+use crate::rating::{DEFAULT_DEVIATION, DEFAULT_RATING};
+use crate::storage::{Storage, StoragePool};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Rows of one table, keyed by id. Ids are assigned in increasing order and aren't reused, like serial columns.
+#[derive(Clone)]
+struct Table<T> {
+    rows: BTreeMap<i32, T>,
+    last_id: i32,
+}
+
+impl<T: Row> Table<T> {
+    fn new() -> Table<T> {
+        Table {
+            rows: BTreeMap::new(),
+            last_id: 0,
+        }
+    }
+
+    /// Insert a row, built from its newly assigned id
+    fn insert(&mut self, undo_log: &mut UndoLog, row: impl FnOnce(i32) -> T) -> T {
+        self.last_id += 1;
+        let row = row(self.last_id);
+        self.rows.insert(self.last_id, row.clone());
+        undo_log.record::<T>(self.last_id, None);
+        row
+    }
+
+    /// Lookup the row with the given id
+    fn get(&self, id: i32) -> Option<T> {
+        self.rows.get(&id).cloned()
+    }
+
+    /// Find the first row (in order of id) matching a predicate
+    fn find(&self, pred: impl Fn(&T) -> bool) -> Option<T> {
+        self.rows.values().find(|row| pred(row)).cloned()
+    }
+
+    /// Find all rows (in order of id) matching a predicate
+    fn filter(&self, pred: impl Fn(&T) -> bool) -> Vec<T> {
+        self.rows
+            .values()
+            .filter(|row| pred(row))
+            .cloned()
+            .collect()
+    }
+
+    /// Change an existing row. Rows that don't exist aren't inserted.
+    fn update(&mut self, undo_log: &mut UndoLog, id: i32, update: impl FnOnce(&mut T)) {
+        if let Some(row) = self.rows.get_mut(&id) {
+            undo_log.record(id, Some(row.clone()));
+            update(row);
+        }
+    }
+
+    /// Delete a row, if it exists
+    fn remove(&mut self, undo_log: &mut UndoLog, id: i32) {
+        if let Some(row) = self.rows.remove(&id) {
+            undo_log.record(id, Some(row));
+        }
+    }
+}
+
+/// A row type, stored in one of the tables
+trait Row: Clone + Send + 'static {
+    fn table(data: &mut MemoryData) -> &mut Table<Self>;
+}
+
+macro_rules! impl_row {
+    ($row:ty, $table:ident) => {
+        impl Row for $row {
+            fn table(data: &mut MemoryData) -> &mut Table<Self> {
+                &mut data.$table
+            }
+        }
+    };
+}
+
+impl_row!(User, users);
+impl_row!(DBGame, games);
+impl_row!(GamePlayer, game_players);
+impl_row!(GameMove, game_moves);
+impl_row!(MoveInfo, move_infos);
+impl_row!(Rating, ratings);
+impl_row!(RatingChange, rating_changes);
+impl_row!(DBTournament, tournaments);
+impl_row!(TournamentPlayer, tournament_players);
+
+// puts back a row as it was before a write
+type Undo = Box<dyn FnOnce(&mut MemoryData) + Send>;
+
+/// The writes a connection has made in its current transaction, newest last. Nothing is recorded outside a
+/// transaction.
+#[derive(Default)]
+struct UndoLog(Option<Vec<Undo>>);
+
+impl UndoLog {
+    /// Record a write to the row with the given id, and what the row was before (None if it was inserted)
+    fn record<T: Row>(&mut self, id: i32, previous: Option<T>) {
+        if let Some(undos) = &mut self.0 {
+            undos.push(Box::new(move |data| {
+                let rows = &mut T::table(data).rows;
+                match previous {
+                    Some(row) => rows.insert(id, row),
+                    None => rows.remove(&id),
+                };
+            }));
+        }
+    }
+}
+
+struct MemoryData {
+    users: Table<User>,
+    games: Table<DBGame>,
+    game_players: Table<GamePlayer>,
+    game_moves: Table<GameMove>,
+    move_infos: Table<MoveInfo>,
+    ratings: Table<Rating>,
+    rating_changes: Table<RatingChange>,
+    tournaments: Table<DBTournament>,
+    tournament_players: Table<TournamentPlayer>,
+}
+
+/// Storage kept in memory, for running the server without a database. Everything is lost when the server stops.
+/// Each clone is a separate connection to the same data.
+pub struct MemoryStorage {
+    data: Arc<Mutex<MemoryData>>,
+    undo_log: Mutex<UndoLog>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> MemoryStorage {
+        MemoryStorage::from_data(Arc::new(Mutex::new(MemoryData {
+            users: Table::new(),
+            games: Table::new(),
+            game_players: Table::new(),
+            game_moves: Table::new(),
+            move_infos: Table::new(),
+            ratings: Table::new(),
+            rating_changes: Table::new(),
+            tournaments: Table::new(),
+            tournament_players: Table::new(),
+        })))
+    }
+
+    fn from_data(data: Arc<Mutex<MemoryData>>) -> MemoryStorage {
+        MemoryStorage {
+            data,
+            undo_log: Mutex::new(UndoLog::default()),
+        }
+    }
+
+    fn data(&self) -> MutexGuard<'_, MemoryData> {
+        self.data.lock().unwrap()
+    }
+
+    fn undo_log(&self) -> MutexGuard<'_, UndoLog> {
+        self.undo_log.lock().unwrap()
+    }
+}
+
+impl Clone for MemoryStorage {
+    fn clone(&self) -> MemoryStorage {
+        MemoryStorage::from_data(self.data.clone())
+    }
+}
+
+impl MemoryData {
+    /// Find the totals of each user's finished games in each game type, in order of user id and game type
+    fn user_stats(&self) -> Vec<UserStats> {
+        let mut stats = BTreeMap::new();
+        let mut move_times = BTreeMap::new();
+        for player in self.game_players.rows.values() {
+            let game = match self.games.rows.get(&player.game_id) {
+                Some(game) if game.finished => game,
+                _ => continue,
+            };
+            let entry = stats
+                .entry((player.user_id, game.game_type.clone()))
+                .or_insert_with(|| UserStats {
+                    user_id: player.user_id,
+                    game_type: game.game_type.clone(),
+                    games: 0,
+                    wins: 0,
+                    losses: 0,
+                    ties: 0,
+                    score: 0.0,
+                    avg_move_time_ms: None,
+                    time_forfeits: 0,
+                    rating: DEFAULT_RATING,
+                    deviation: DEFAULT_DEVIATION,
+                });
+            entry.games += 1;
+            match game.winner {
+                Some(winner) if winner == player.user_id => entry.wins += 1,
+                Some(_) => entry.losses += 1,
+                None => {}
+            }
+            if game.is_tie == Some(true) {
+                entry.ties += 1;
+            }
+            entry.score += player.score.unwrap_or(0.0);
+            let reason = game
+                .state
+                .as_deref()
+                .and_then(EndedGameInstance::from_state)
+                .and_then(|inst| inst.end_reason());
+            if reason.as_deref() == Some(TIME_EXPIRED_REASON) && game.winner != Some(player.user_id)
+            {
+                entry.time_forfeits += 1;
+            }
+        }
+        for game_move in self.game_moves.rows.values() {
+            if let Some(game) = self.games.rows.get(&game_move.game_id) {
+                if game.finished {
+                    let (total, count) = move_times
+                        .entry((game_move.user_id, game.game_type.clone()))
+                        .or_insert((0, 0));
+                    *total += game_move.time_spent_ms;
+                    *count += 1;
+                }
+            }
+        }
+        stats
+            .into_iter()
+            .map(|(key, mut entry)| {
+                if let Some((total, count)) = move_times.get(&key) {
+                    entry.avg_move_time_ms = Some(*total as f64 / *count as f64);
+                }
+                if let Some(rating) = self
+                    .ratings
+                    .find(|r| r.user_id == entry.user_id && r.game_type == entry.game_type)
+                {
+                    entry.rating = rating.rating;
+                    entry.deviation = rating.deviation;
+                }
+                entry
+            })
+            .collect()
+    }
+}
+
+impl Default for MemoryStorage {
+    fn default() -> Self {
+        MemoryStorage::new()
+    }
+}
+
+impl StoragePool for MemoryStorage {
+    fn get(&self) -> Result<Box<dyn Storage + '_>, Error> {
+        Ok(Box::new(self.clone()))
+    }
+}
+
+impl Storage for MemoryStorage {
+    // a failed transaction puts back the rows this connection wrote, newest first. Writes made through other
+    // connections in the meantime are kept, unless they were to the same rows.
+    fn transaction(&self, f: Box<dyn FnOnce() -> Result<(), Error> + '_>) -> Result<(), Error> {
+        self.undo_log().0 = Some(Vec::new());
+        let result = f();
+        let undos = self.undo_log().0.take().unwrap_or_default();
+        if result.is_err() {
+            let mut data = self.data();
+            for undo in undos.into_iter().rev() {
+                undo(&mut data);
+            }
+        }
+        result
+    }
+
+    // ---- Users ----
+    fn find_user(&self, id: UserId) -> Result<Option<User>, Error> {
+        Ok(self.data().users.get(id))
+    }
+
+    fn find_user_by_apikey_hash(&self, hash: &str) -> Result<Option<User>, Error> {
+        Ok(self.data().users.find(|u| u.api_key_hash == hash))
+    }
+
+    fn find_user_by_email(&self, email: &str) -> Result<Option<User>, Error> {
+        Ok(self
+            .data()
+            .users
+            .find(|u| u.email.as_deref() == Some(email)))
+    }
+
+    fn insert_user(&self, user: &NewUser) -> Result<User, Error> {
+        Ok(self.data().users.insert(&mut self.undo_log(), |id| User {
+            id,
+            email: user.email.map(|e| e.to_string()),
+            name: user.name.to_string(),
+            is_admin: user.is_admin,
+            password_hash: user.password_hash.map(|p| p.to_string()),
+            api_key_hash: user.api_key_hash.to_string(),
+        }))
+    }
+
+    fn save_user(&self, user: &User) -> Result<(), Error> {
+        self.data()
+            .users
+            .update(&mut self.undo_log(), user.id, |row| {
+                if let Some(email) = &user.email {
+                    row.email = Some(email.clone());
+                }
+                if let Some(password_hash) = &user.password_hash {
+                    row.password_hash = Some(password_hash.clone());
+                }
+                row.name = user.name.clone();
+                row.is_admin = user.is_admin;
+                row.api_key_hash = user.api_key_hash.clone();
+            });
+        Ok(())
+    }
+
+    // ---- Games ----
+    fn insert_game(&self, game: &NewDBGame) -> Result<DBGame, Error> {
+        Ok(self.data().games.insert(&mut self.undo_log(), |id| DBGame {
+            id,
+            owner_id: game.owner_id,
+            game_type: game.game_type.to_string(),
+            state: game.state.map(|s| s.to_string()),
+            finished: game.finished,
+            winner: game.winner,
+            is_tie: game.is_tie,
+            dur_per_move_ms: game.dur_per_move_ms,
+            dur_sudden_death_ms: game.dur_sudden_death_ms,
+            current_move_start_ms: game.current_move_start_ms,
+            turn_id: game.turn_id,
+            tournament_id: game.tournament_id,
+            options: game.options.to_string(),
+            draw_offer: game.draw_offer,
+            rated: game.rated,
+        }))
+    }
+
+    fn find_game(&self, id: GameId) -> Result<Option<DBGame>, Error> {
+        Ok(self.data().games.get(id))
+    }
+
+    fn save_game(&self, game: &DBGame) -> Result<(), Error> {
+        self.data()
+            .games
+            .update(&mut self.undo_log(), game.id, |row| {
+                *row = DBGame {
+                    state: game.state.clone().or_else(|| row.state.take()),
+                    winner: game.winner.or(row.winner),
+                    is_tie: game.is_tie.or(row.is_tie),
+                    current_move_start_ms: game.current_move_start_ms.or(row.current_move_start_ms),
+                    turn_id: game.turn_id.or(row.turn_id),
+                    tournament_id: game.tournament_id.or(row.tournament_id),
+                    ..game.clone()
+                }
+            });
+        Ok(())
+    }
+
+    fn find_timed_games(&self) -> Result<Vec<GameId>, Error> {
+        Ok(self
+            .data()
+            .games
+            .filter(|g| !g.finished && g.turn_id.is_some() && g.current_move_start_ms.is_some())
+            .iter()
+            .map(|g| g.id)
+            .collect())
+    }
+
+    fn find_tournament_games(&self, id: TournamentId) -> Result<Vec<DBGame>, Error> {
+        Ok(self.data().games.filter(|g| g.tournament_id == Some(id)))
+    }
+
+    // ---- Game Players ----
+    fn insert_game_player(&self, player: &NewGamePlayer) -> Result<GamePlayer, Error> {
+        Ok(self
+            .data()
+            .game_players
+            .insert(&mut self.undo_log(), |id| GamePlayer {
+                id,
+                user_id: player.user_id,
+                game_id: player.game_id,
+                score: player.score,
+                waiting_for_move: player.waiting_for_move,
+                time_ms: player.time_ms,
+                moves_made: player.moves_made,
+            }))
+    }
+
+    fn find_game_players(&self, game_id: GameId) -> Result<Vec<GamePlayer>, Error> {
+        Ok(self.data().game_players.filter(|p| p.game_id == game_id))
+    }
+
+    fn find_game_player(
+        &self,
+        game_id: GameId,
+        user_id: UserId,
+    ) -> Result<Option<GamePlayer>, Error> {
+        Ok(self
+            .data()
+            .game_players
+            .find(|p| p.game_id == game_id && p.user_id == user_id))
+    }
+
+    fn save_game_player(&self, player: &GamePlayer) -> Result<(), Error> {
+        self.data()
+            .game_players
+            .update(&mut self.undo_log(), player.id, |row| {
+                let score = player.score.or(row.score);
+                *row = player.clone();
+                row.score = score;
+            });
+        Ok(())
+    }
+
+    fn delete_game_player(&self, id: GamePlayerId) -> Result<(), Error> {
+        self.data().game_players.remove(&mut self.undo_log(), id);
+        Ok(())
+    }
+
+    fn find_waiting_games_for_user(&self, user_id: UserId) -> Result<Vec<GameId>, Error> {
+        Ok(self
+            .data()
+            .game_players
+            .filter(|p| p.user_id == user_id && p.waiting_for_move)
+            .iter()
+            .map(|p| p.game_id)
+            .collect())
+    }
+
+    // ---- Moves ----
+    fn insert_game_move(&self, game_move: &NewGameMove) -> Result<(), Error> {
+        self.data()
+            .game_moves
+            .insert(&mut self.undo_log(), |id| GameMove {
+                id,
+                game_id: game_move.game_id,
+                ply: game_move.ply,
+                user_id: game_move.user_id,
+                play: game_move.play.clone(),
+                time_spent_ms: game_move.time_spent_ms,
+                clock_ms: game_move.clock_ms,
+                played_at_ms: game_move.played_at_ms,
+                eval: game_move.eval,
+            });
+        Ok(())
+    }
+
+    fn find_game_moves(&self, game_id: GameId) -> Result<Vec<GameMove>, Error> {
+        let mut moves = self.data().game_moves.filter(|m| m.game_id == game_id);
+        moves.sort_by_key(|m| m.ply);
+        Ok(moves)
+    }
+
+    fn set_last_move_eval(&self, game_id: GameId, user_id: UserId, eval: f64) -> Result<(), Error> {
+        let mut data = self.data();
+        let last_move = data
+            .game_moves
+            .filter(|m| m.game_id == game_id && m.user_id == user_id)
+            .into_iter()
+            .max_by_key(|m| m.ply);
+        if let Some(last_move) = last_move {
+            data.game_moves
+                .update(&mut self.undo_log(), last_move.id, |row| {
+                    row.eval = Some(eval)
+                });
+        }
+        Ok(())
+    }
+
+    fn save_move_info(&self, info: &NewMoveInfo) -> Result<MoveInfo, Error> {
+        let mut data = self.data();
+        let existing = data.move_infos.find(|i| {
+            i.game_id == info.game_id
+                && i.user_id == info.user_id
+                && i.move_number == info.move_number
+        });
+        match existing {
+            // fields that aren't given keep any earlier value
+            Some(existing) => {
+                let merged = MoveInfo {
+                    eval: info.eval.or(existing.eval),
+                    depth: info.depth.or(existing.depth),
+                    node_count: info.node_count.or(existing.node_count),
+                    pv: info.pv.clone().or(existing.pv),
+                    ..existing
+                };
+                data.move_infos
+                    .update(&mut self.undo_log(), merged.id, |row| *row = merged.clone());
+                Ok(merged)
+            }
+            None => Ok(data.move_infos.insert(&mut self.undo_log(), |id| MoveInfo {
+                id,
+                game_id: info.game_id,
+                user_id: info.user_id,
+                move_number: info.move_number,
+                eval: info.eval,
+                depth: info.depth,
+                node_count: info.node_count,
+                pv: info.pv.clone(),
+            })),
+        }
+    }
+
+    fn find_move_infos(&self, game_id: GameId) -> Result<Vec<MoveInfo>, Error> {
+        Ok(self.data().move_infos.filter(|i| i.game_id == game_id))
+    }
+
+    // ---- Ratings ----
+    fn find_rating(&self, user_id: UserId, game_type: &str) -> Result<Option<Rating>, Error> {
+        Ok(self
+            .data()
+            .ratings
+            .find(|r| r.user_id == user_id && r.game_type == game_type))
+    }
+
+    fn save_rating(&self, rating: &NewRating) -> Result<Rating, Error> {
+        let mut data = self.data();
+        let existing = data
+            .ratings
+            .find(|r| r.user_id == rating.user_id && r.game_type == rating.game_type);
+        match existing {
+            Some(existing) => {
+                let updated = Rating {
+                    rating: rating.rating,
+                    deviation: rating.deviation,
+                    volatility: rating.volatility,
+                    ..existing
+                };
+                data.ratings
+                    .update(&mut self.undo_log(), updated.id, |row| {
+                        *row = updated.clone()
+                    });
+                Ok(updated)
+            }
+            None => Ok(data.ratings.insert(&mut self.undo_log(), |id| Rating {
+                id,
+                user_id: rating.user_id,
+                game_type: rating.game_type.clone(),
+                rating: rating.rating,
+                deviation: rating.deviation,
+                volatility: rating.volatility,
+            })),
+        }
+    }
+
+    fn insert_rating_change(&self, change: &NewRatingChange) -> Result<(), Error> {
+        self.data()
+            .rating_changes
+            .insert(&mut self.undo_log(), |id| RatingChange {
+                id,
+                user_id: change.user_id,
+                game_type: change.game_type.clone(),
+                game_id: change.game_id,
+                rating: change.rating,
+                deviation: change.deviation,
+                volatility: change.volatility,
+                changed_at_ms: change.changed_at_ms,
+            });
+        Ok(())
+    }
+
+    fn find_rating_changes(
+        &self,
+        user_id: UserId,
+        game_type: &str,
+    ) -> Result<Vec<RatingChange>, Error> {
+        Ok(self
+            .data()
+            .rating_changes
+            .filter(|c| c.user_id == user_id && c.game_type == game_type))
+    }
+
+    // ---- Statistics ----
+    fn find_user_stats(&self, user_id: UserId) -> Result<Vec<UserStats>, Error> {
+        Ok(self
+            .data()
+            .user_stats()
+            .into_iter()
+            .filter(|s| s.user_id == user_id)
+            .collect())
+    }
+
+    fn find_leaderboard(
+        &self,
+        game_type: &str,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<UserStats>, Error> {
+        let data = self.data();
+        // only users with a rating are ranked
+        let mut stats = data
+            .user_stats()
+            .into_iter()
+            .filter(|s| s.game_type == game_type)
+            .filter(|s| {
+                data.ratings
+                    .find(|r| r.user_id == s.user_id && r.game_type == game_type)
+                    .is_some()
+            })
+            .collect::<Vec<UserStats>>();
+        stats.sort_by(|a, b| {
+            b.rating
+                .partial_cmp(&a.rating)
+                .unwrap_or(Ordering::Equal)
+                .then(a.user_id.cmp(&b.user_id))
+        });
+        Ok(stats
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .collect())
+    }
+
+    // ---- Tournaments ----
+    fn insert_tournament(&self, tourney: &NewDBTournament) -> Result<DBTournament, Error> {
+        Ok(self
+            .data()
+            .tournaments
+            .insert(&mut self.undo_log(), |id| DBTournament {
+                id,
+                owner_id: tourney.owner_id,
+                tournament_type: tourney.tournament_type.to_string(),
+                game_type: tourney.game_type.to_string(),
+                dur_per_move_ms: tourney.dur_per_move_ms,
+                dur_sudden_death_ms: tourney.dur_sudden_death_ms,
+                started: tourney.started,
+                finished: tourney.finished,
+                winner: tourney.winner,
+                options: tourney.options.to_string(),
+                game_options: tourney.game_options.to_string(),
+            }))
+    }
+
+    fn find_tournament(&self, id: TournamentId) -> Result<Option<DBTournament>, Error> {
+        Ok(self.data().tournaments.get(id))
+    }
+
+    fn save_tournament(&self, tourney: &DBTournament) -> Result<(), Error> {
+        self.data()
+            .tournaments
+            .update(&mut self.undo_log(), tourney.id, |row| {
+                let winner = tourney.winner.or(row.winner);
+                *row = tourney.clone();
+                row.winner = winner;
+            });
+        Ok(())
+    }
+
+    fn insert_tournament_player(
+        &self,
+        player: &NewTournamentPlayer,
+    ) -> Result<TournamentPlayer, Error> {
+        Ok(self
+            .data()
+            .tournament_players
+            .insert(&mut self.undo_log(), |id| TournamentPlayer {
+                id,
+                user_id: player.user_id,
+                tournament_id: player.tournament_id,
+                win: player.win,
+                loss: player.loss,
+                tie: player.tie,
+            }))
+    }
+
+    fn find_tournament_players(&self, id: TournamentId) -> Result<Vec<TournamentPlayer>, Error> {
+        Ok(self
+            .data()
+            .tournament_players
+            .filter(|p| p.tournament_id == id))
+    }
+
+    fn find_tournament_player(
+        &self,
+        id: TournamentId,
+        user_id: UserId,
+    ) -> Result<Option<TournamentPlayer>, Error> {
+        Ok(self
+            .data()
+            .tournament_players
+            .find(|p| p.tournament_id == id && p.user_id == user_id))
+    }
+
+    fn find_user_tournament_players(
+        &self,
+        user_id: UserId,
+    ) -> Result<Vec<TournamentPlayer>, Error> {
+        Ok(self
+            .data()
+            .tournament_players
+            .filter(|p| p.user_id == user_id))
+    }
+
+    fn save_tournament_player(&self, player: &TournamentPlayer) -> Result<(), Error> {
+        self.data()
+            .tournament_players
+            .update(&mut self.undo_log(), player.id, |row| *row = *player);
+        Ok(())
+    }
+
+    fn delete_tournament_player(&self, id: TournamentPlayerId) -> Result<(), Error> {
+        self.data()
+            .tournament_players
+            .remove(&mut self.undo_log(), id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn insert_user(storage: &MemoryStorage, name: &str) -> User {
+        storage
+            .insert_user(&NewUser {
+                email: None,
+                name,
+                is_admin: false,
+                password_hash: None,
+                api_key_hash: name,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn failed_transaction_keeps_other_connections_writes() {
+        let storage = MemoryStorage::new();
+        let other = storage.clone();
+        let mut existing = insert_user(&storage, "existing");
+
+        let mut inserted = None;
+        let result = storage.transaction(Box::new(|| {
+            inserted = Some(insert_user(&storage, "inserted"));
+            let mut renamed = existing.clone();
+            renamed.name = "renamed".to_string();
+            storage.save_user(&renamed)?;
+            // another connection writes while the transaction is running
+            insert_user(&other, "other");
+            Err(Error::NoSuchUser)
+        }));
+        assert_eq!(result, Err(Error::NoSuchUser));
+
+        // the transaction's writes are undone
+        assert!(storage.find_user(inserted.unwrap().id).unwrap().is_none());
+        assert_eq!(
+            storage.find_user(existing.id).unwrap().unwrap().name,
+            "existing"
+        );
+        // but not the other connection's
+        assert!(storage.find_user_by_apikey_hash("other").unwrap().is_some());
+
+        // nothing is recorded outside a transaction, and successful transactions keep their writes
+        existing.name = "renamed".to_string();
+        storage.save_user(&existing).unwrap();
+        assert!(storage.transaction(Box::new(|| Ok(()))).is_ok());
+        assert_eq!(
+            storage.find_user(existing.id).unwrap().unwrap().name,
+            "renamed"
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use crate::error::Error;
+use crate::models::{
+    DBGame, DBTournament, GameId, GameMove, GamePlayer, GamePlayerId, MoveInfo, NewDBGame,
+    NewDBTournament, NewGameMove, NewGamePlayer, NewMoveInfo, NewRating, NewRatingChange,
+    NewTournamentPlayer, NewUser, Rating, RatingChange, TournamentId, TournamentPlayer,
+    TournamentPlayerId, User, UserId, UserStats,
+};
+
+pub mod memory;
+pub mod pg;
+
+/// A connection to the server's storage, which loads and saves users, games, and tournaments.
+/// Lookups return None when no row matches, and leave it to the caller to decide if that is an error.
+pub trait Storage {
+    /// Run f as one transaction, so none of its changes are kept if it returns an error
+    fn transaction(&self, f: Box<dyn FnOnce() -> Result<(), Error> + '_>) -> Result<(), Error>;
+
+    // ---- Users ----
+    /// Lookup a user with the given id
+    fn find_user(&self, id: UserId) -> Result<Option<User>, Error>;
+    /// Lookup a user by the hash of their api key
+    fn find_user_by_apikey_hash(&self, hash: &str) -> Result<Option<User>, Error>;
+    /// Lookup a user by email
+    fn find_user_by_email(&self, email: &str) -> Result<Option<User>, Error>;
+    /// Insert a new user
+    fn insert_user(&self, user: &NewUser) -> Result<User, Error>;
+    /// Update a user. Fields that are None are left unchanged.
+    fn save_user(&self, user: &User) -> Result<(), Error>;
+
+    // ---- Games ----
+    /// Insert a new game
+    fn insert_game(&self, game: &NewDBGame) -> Result<DBGame, Error>;
+    /// Lookup a game with the given id
+    fn find_game(&self, id: GameId) -> Result<Option<DBGame>, Error>;
+    /// Update a game. Fields that are None are left unchanged, except for the draw offer.
+    fn save_game(&self, game: &DBGame) -> Result<(), Error>;
+    /// Find all unfinished games that are timing a move, in order of id
+    fn find_timed_games(&self) -> Result<Vec<GameId>, Error>;
+    /// Find all games in a tournament, in order of id
+    fn find_tournament_games(&self, id: TournamentId) -> Result<Vec<DBGame>, Error>;
+
+    // ---- Game Players ----
+    /// Add a player to a game
+    fn insert_game_player(&self, player: &NewGamePlayer) -> Result<GamePlayer, Error>;
+    /// Load all players in a game, in the order they joined
+    fn find_game_players(&self, game_id: GameId) -> Result<Vec<GamePlayer>, Error>;
+    /// Lookup a user's player in a game
+    fn find_game_player(
+        &self,
+        game_id: GameId,
+        user_id: UserId,
+    ) -> Result<Option<GamePlayer>, Error>;
+    /// Update a game player. A score of None leaves the score unchanged.
+    fn save_game_player(&self, player: &GamePlayer) -> Result<(), Error>;
+    /// Remove a player from a game
+    fn delete_game_player(&self, id: GamePlayerId) -> Result<(), Error>;
+    /// Find all games waiting for a user to play, in the order the user joined them
+    fn find_waiting_games_for_user(&self, user_id: UserId) -> Result<Vec<GameId>, Error>;
+
+    // ---- Moves ----
+    /// Record a move made in a game
+    fn insert_game_move(&self, game_move: &NewGameMove) -> Result<(), Error>;
+    /// Load the moves made in a game, in order
+    fn find_game_moves(&self, game_id: GameId) -> Result<Vec<GameMove>, Error>;
+    /// Set the eval of the last move a user made in a game (if they have a recorded move)
+    fn set_last_move_eval(&self, game_id: GameId, user_id: UserId, eval: f64) -> Result<(), Error>;
+    /// Save info about a move, merging it with info already saved about the same move
+    fn save_move_info(&self, info: &NewMoveInfo) -> Result<MoveInfo, Error>;
+    /// Load all info reported about moves in a game, in the order it was first reported
+    fn find_move_infos(&self, game_id: GameId) -> Result<Vec<MoveInfo>, Error>;
+
+    // ---- Ratings ----
+    /// Lookup a user's rating in a game type
+    fn find_rating(&self, user_id: UserId, game_type: &str) -> Result<Option<Rating>, Error>;
+    /// Save a user's rating in a game type, replacing any earlier rating
+    fn save_rating(&self, rating: &NewRating) -> Result<Rating, Error>;
+    /// Record a change in a user's rating
+    fn insert_rating_change(&self, change: &NewRatingChange) -> Result<(), Error>;
+    /// Load the changes in a user's rating in a game type, in the order they were made
+    fn find_rating_changes(
+        &self,
+        user_id: UserId,
+        game_type: &str,
+    ) -> Result<Vec<RatingChange>, Error>;
+
+    // ---- Statistics ----
+    /// Find the totals of a user's finished games in each game type they've played, in order of game type. Users
+    /// without a rating in a game type have the default rating.
+    fn find_user_stats(&self, user_id: UserId) -> Result<Vec<UserStats>, Error>;
+    /// Find the totals of finished games for rated users in a game type, from the highest rating to the lowest (ties
+    /// are ordered by user id). `offset` users are skipped, and at most `limit` users are returned.
+    fn find_leaderboard(
+        &self,
+        game_type: &str,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<UserStats>, Error>;
+
+    // ---- Tournaments ----
+    /// Insert a new tournament
+    fn insert_tournament(&self, tourney: &NewDBTournament) -> Result<DBTournament, Error>;
+    /// Lookup a tournament with the given id
+    fn find_tournament(&self, id: TournamentId) -> Result<Option<DBTournament>, Error>;
+    /// Update a tournament. A winner of None leaves the winner unchanged.
+    fn save_tournament(&self, tourney: &DBTournament) -> Result<(), Error>;
+    /// Add a player to a tournament
+    fn insert_tournament_player(
+        &self,
+        player: &NewTournamentPlayer,
+    ) -> Result<TournamentPlayer, Error>;
+    /// Load all players in a tournament, in the order they joined
+    fn find_tournament_players(&self, id: TournamentId) -> Result<Vec<TournamentPlayer>, Error>;
+    /// Lookup a user's player in a tournament
+    fn find_tournament_player(
+        &self,
+        id: TournamentId,
+        user_id: UserId,
+    ) -> Result<Option<TournamentPlayer>, Error>;
+    /// Load all of a user's players in tournaments, in the order they joined
+    fn find_user_tournament_players(&self, user_id: UserId)
+        -> Result<Vec<TournamentPlayer>, Error>;
+    /// Update a tournament player
+    fn save_tournament_player(&self, player: &TournamentPlayer) -> Result<(), Error>;
+    /// Remove a player from a tournament
+    fn delete_tournament_player(&self, id: TournamentPlayerId) -> Result<(), Error>;
+}
+
+/// A source of storage connections, shared between the server's tasks
+pub trait StoragePool: Send + Sync {
+    /// Get a connection to the storage
+    fn get(&self) -> Result<Box<dyn Storage + '_>, Error>;
+}
+
+/// Open the storage at a url. `memory` keeps everything in memory (and loses it when the server stops), and
+/// other urls are postgres database urls.
+pub fn open_storage(url: &str) -> Result<Box<dyn StoragePool>, Error> {
+    if url == "memory" {
+        Ok(Box::new(memory::MemoryStorage::new()))
+    } else {
+        Ok(Box::new(pg::init_db_pool(url)?))
+    }
+}
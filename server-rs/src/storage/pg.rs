@@ -0,0 +1,401 @@
+use crate::diesel::prelude::*;
+use crate::error::Error;
+use crate::games::ended_game::{ENDED_GAME_PREFIX, TIME_EXPIRED_REASON};
+use crate::models::{
+    DBGame, DBTournament, GameId, GameMove, GameMoveId, GamePlayer, GamePlayerId, MoveInfo,
+    NewDBGame, NewDBTournament, NewGameMove, NewGamePlayer, NewMoveInfo, NewRating,
+    NewRatingChange, NewTournamentPlayer, NewUser, Rating, RatingChange, TournamentId,
+    TournamentPlayer, TournamentPlayerId, User, UserId, UserStats,
+};
+use crate::rating::{DEFAULT_DEVIATION, DEFAULT_RATING};
+use crate::schema::{
+    game_moves, game_players, games, move_infos, rating_changes, ratings, tournament_players,
+    tournaments, users,
+};
+use crate::storage::{Storage, StoragePool};
+use diesel::pg::PgConnection;
+use diesel::r2d2::{ConnectionManager, Pool, PoolError, PooledConnection};
+use diesel::sql_types::{BigInt, Double, Integer, Text};
+
+pub type PgPool = Pool<ConnectionManager<PgConnection>>;
+
+// Totals of each user's finished games in each game type (as `stats`), along with their average move time and
+// current rating. Takes the pattern matching the state of games lost on time ($1) and the default rating and
+// deviation ($2, $3).
+const USER_STATS_QUERY: &str = "
+    WITH stats AS (
+        SELECT gp.user_id, g.game_type,
+            COUNT(*) AS games,
+            COUNT(*) FILTER (WHERE g.winner = gp.user_id) AS wins,
+            COUNT(*) FILTER (WHERE g.winner <> gp.user_id) AS losses,
+            COUNT(*) FILTER (WHERE g.is_tie) AS ties,
+            COALESCE(SUM(gp.score), 0) AS score,
+            COUNT(*) FILTER (WHERE g.state LIKE $1 AND g.winner IS DISTINCT FROM gp.user_id)
+                AS time_forfeits
+        FROM game_players gp JOIN games g ON g.id = gp.game_id
+        WHERE g.finished
+        GROUP BY gp.user_id, g.game_type
+    ), move_times AS (
+        SELECT m.user_id, g.game_type, AVG(m.time_spent_ms)::DOUBLE PRECISION AS avg_move_time_ms
+        FROM game_moves m JOIN games g ON g.id = m.game_id
+        WHERE g.finished
+        GROUP BY m.user_id, g.game_type
+    )
+    SELECT stats.user_id, stats.game_type, stats.games, stats.wins, stats.losses, stats.ties, stats.score,
+        move_times.avg_move_time_ms, stats.time_forfeits,
+        COALESCE(ratings.rating, $2) AS rating, COALESCE(ratings.deviation, $3) AS deviation
+    FROM stats
+    LEFT JOIN move_times
+        ON move_times.user_id = stats.user_id AND move_times.game_type = stats.game_type
+    LEFT JOIN ratings ON ratings.user_id = stats.user_id AND ratings.game_type = stats.game_type";
+
+/// The LIKE pattern matching the state of games that ended by a player running out of time
+fn time_expired_pattern() -> String {
+    format!("{}, %, {}, %", ENDED_GAME_PREFIX, TIME_EXPIRED_REASON).replace('_', "\\_")
+}
+
+pub fn init_db_pool(db_url: &str) -> Result<PgPool, PoolError> {
+    let manage = ConnectionManager::<PgConnection>::new(db_url);
+    Pool::builder().build(manage)
+}
+
+impl StoragePool for PgPool {
+    fn get(&self) -> Result<Box<dyn Storage + '_>, Error> {
+        Ok(Box::new(PgStorage(Pool::get(self)?)))
+    }
+}
+
+/// A connection to a postgres database
+pub struct PgStorage(PooledConnection<ConnectionManager<PgConnection>>);
+
+impl Storage for PgStorage {
+    fn transaction(&self, f: Box<dyn FnOnce() -> Result<(), Error> + '_>) -> Result<(), Error> {
+        self.0.transaction(f)
+    }
+
+    // ---- Users ----
+    fn find_user(&self, id: UserId) -> Result<Option<User>, Error> {
+        Ok(users::dsl::users
+            .find(id)
+            .first::<User>(&self.0)
+            .optional()?)
+    }
+
+    fn find_user_by_apikey_hash(&self, hash: &str) -> Result<Option<User>, Error> {
+        Ok(users::dsl::users
+            .filter(users::dsl::api_key_hash.eq(hash))
+            .first::<User>(&self.0)
+            .optional()?)
+    }
+
+    fn find_user_by_email(&self, email: &str) -> Result<Option<User>, Error> {
+        Ok(users::dsl::users
+            .filter(users::dsl::email.eq(email))
+            .first::<User>(&self.0)
+            .optional()?)
+    }
+
+    fn insert_user(&self, user: &NewUser) -> Result<User, Error> {
+        Ok(diesel::insert_into(users::table)
+            .values(user)
+            .get_result::<User>(&self.0)?)
+    }
+
+    fn save_user(&self, user: &User) -> Result<(), Error> {
+        diesel::update(users::dsl::users.find(user.id))
+            .set(user)
+            .execute(&self.0)?;
+        Ok(())
+    }
+
+    // ---- Games ----
+    fn insert_game(&self, game: &NewDBGame) -> Result<DBGame, Error> {
+        Ok(diesel::insert_into(games::table)
+            .values(game)
+            .get_result::<DBGame>(&self.0)?)
+    }
+
+    fn find_game(&self, id: GameId) -> Result<Option<DBGame>, Error> {
+        Ok(games::dsl::games
+            .find(id)
+            .first::<DBGame>(&self.0)
+            .optional()?)
+    }
+
+    fn save_game(&self, game: &DBGame) -> Result<(), Error> {
+        diesel::update(games::dsl::games.find(game.id))
+            .set(game)
+            .execute(&self.0)?;
+        // the changeset skips fields that are None, but a withdrawn draw offer has to be cleared
+        if game.draw_offer.is_none() {
+            diesel::update(games::dsl::games.find(game.id))
+                .set(games::dsl::draw_offer.eq(None::<UserId>))
+                .execute(&self.0)?;
+        }
+        Ok(())
+    }
+
+    fn find_timed_games(&self) -> Result<Vec<GameId>, Error> {
+        use games::dsl;
+        Ok(dsl::games
+            .filter(dsl::finished.eq(false))
+            .filter(dsl::turn_id.is_not_null())
+            .filter(dsl::current_move_start_ms.is_not_null())
+            .order(dsl::id.asc())
+            .select(dsl::id)
+            .load::<GameId>(&self.0)?)
+    }
+
+    fn find_tournament_games(&self, id: TournamentId) -> Result<Vec<DBGame>, Error> {
+        Ok(games::dsl::games
+            .filter(games::dsl::tournament_id.eq(id))
+            .order(games::dsl::id.asc())
+            .load::<DBGame>(&self.0)?)
+    }
+
+    // ---- Game Players ----
+    fn insert_game_player(&self, player: &NewGamePlayer) -> Result<GamePlayer, Error> {
+        Ok(diesel::insert_into(game_players::table)
+            .values(player)
+            .get_result::<GamePlayer>(&self.0)?)
+    }
+
+    fn find_game_players(&self, game_id: GameId) -> Result<Vec<GamePlayer>, Error> {
+        use game_players::dsl;
+        Ok(dsl::game_players
+            .filter(dsl::game_id.eq(game_id))
+            .order(dsl::id.asc())
+            .load::<GamePlayer>(&self.0)?)
+    }
+
+    fn find_game_player(
+        &self,
+        game_id: GameId,
+        user_id: UserId,
+    ) -> Result<Option<GamePlayer>, Error> {
+        use game_players::dsl;
+        Ok(dsl::game_players
+            .filter(dsl::game_id.eq(game_id).and(dsl::user_id.eq(user_id)))
+            .first::<GamePlayer>(&self.0)
+            .optional()?)
+    }
+
+    fn save_game_player(&self, player: &GamePlayer) -> Result<(), Error> {
+        diesel::update(game_players::dsl::game_players.find(player.id))
+            .set(player)
+            .execute(&self.0)?;
+        Ok(())
+    }
+
+    fn delete_game_player(&self, id: GamePlayerId) -> Result<(), Error> {
+        use game_players::dsl;
+        diesel::delete(dsl::game_players.filter(dsl::id.eq(id))).execute(&self.0)?;
+        Ok(())
+    }
+
+    fn find_waiting_games_for_user(&self, user_id: UserId) -> Result<Vec<GameId>, Error> {
+        use game_players::dsl;
+        Ok(dsl::game_players
+            .filter(dsl::user_id.eq(user_id).and(dsl::waiting_for_move.eq(true)))
+            .order(dsl::id.asc())
+            .select(dsl::game_id)
+            .load::<GameId>(&self.0)?)
+    }
+
+    // ---- Moves ----
+    fn insert_game_move(&self, game_move: &NewGameMove) -> Result<(), Error> {
+        diesel::insert_into(game_moves::table)
+            .values(game_move)
+            .execute(&self.0)?;
+        Ok(())
+    }
+
+    fn find_game_moves(&self, game_id: GameId) -> Result<Vec<GameMove>, Error> {
+        use game_moves::dsl;
+        Ok(dsl::game_moves
+            .filter(dsl::game_id.eq(game_id))
+            .order(dsl::ply.asc())
+            .load::<GameMove>(&self.0)?)
+    }
+
+    fn set_last_move_eval(&self, game_id: GameId, user_id: UserId, eval: f64) -> Result<(), Error> {
+        use game_moves::dsl;
+        let last_move = dsl::game_moves
+            .filter(dsl::game_id.eq(game_id))
+            .filter(dsl::user_id.eq(user_id))
+            .order(dsl::ply.desc())
+            .select(dsl::id)
+            .first::<GameMoveId>(&self.0)
+            .optional()?;
+        if let Some(id) = last_move {
+            diesel::update(dsl::game_moves.find(id))
+                .set(dsl::eval.eq(eval))
+                .execute(&self.0)?;
+        }
+        Ok(())
+    }
+
+    fn save_move_info(&self, info: &NewMoveInfo) -> Result<MoveInfo, Error> {
+        use move_infos::dsl;
+        // fields that are None are skipped by the update, so they keep any earlier value
+        Ok(diesel::insert_into(move_infos::table)
+            .values(info)
+            .on_conflict((dsl::game_id, dsl::user_id, dsl::move_number))
+            .do_update()
+            .set(info)
+            .get_result::<MoveInfo>(&self.0)?)
+    }
+
+    fn find_move_infos(&self, game_id: GameId) -> Result<Vec<MoveInfo>, Error> {
+        use move_infos::dsl;
+        Ok(dsl::move_infos
+            .filter(dsl::game_id.eq(game_id))
+            .order(dsl::id.asc())
+            .load::<MoveInfo>(&self.0)?)
+    }
+
+    // ---- Ratings ----
+    fn find_rating(&self, user_id: UserId, game_type: &str) -> Result<Option<Rating>, Error> {
+        use ratings::dsl;
+        Ok(dsl::ratings
+            .filter(dsl::user_id.eq(user_id).and(dsl::game_type.eq(game_type)))
+            .first::<Rating>(&self.0)
+            .optional()?)
+    }
+
+    fn save_rating(&self, rating: &NewRating) -> Result<Rating, Error> {
+        use ratings::dsl;
+        Ok(diesel::insert_into(ratings::table)
+            .values(rating)
+            .on_conflict((dsl::user_id, dsl::game_type))
+            .do_update()
+            .set(rating)
+            .get_result::<Rating>(&self.0)?)
+    }
+
+    fn insert_rating_change(&self, change: &NewRatingChange) -> Result<(), Error> {
+        diesel::insert_into(rating_changes::table)
+            .values(change)
+            .execute(&self.0)?;
+        Ok(())
+    }
+
+    fn find_rating_changes(
+        &self,
+        user_id: UserId,
+        game_type: &str,
+    ) -> Result<Vec<RatingChange>, Error> {
+        use rating_changes::dsl;
+        Ok(dsl::rating_changes
+            .filter(dsl::user_id.eq(user_id).and(dsl::game_type.eq(game_type)))
+            .order(dsl::id.asc())
+            .load::<RatingChange>(&self.0)?)
+    }
+
+    // ---- Statistics ----
+    fn find_user_stats(&self, user_id: UserId) -> Result<Vec<UserStats>, Error> {
+        Ok(diesel::sql_query(format!(
+            "{} WHERE stats.user_id = $4 ORDER BY stats.game_type",
+            USER_STATS_QUERY
+        ))
+        .bind::<Text, _>(time_expired_pattern())
+        .bind::<Double, _>(DEFAULT_RATING)
+        .bind::<Double, _>(DEFAULT_DEVIATION)
+        .bind::<Integer, _>(user_id)
+        .load::<UserStats>(&self.0)?)
+    }
+
+    fn find_leaderboard(
+        &self,
+        game_type: &str,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<UserStats>, Error> {
+        // only users with a rating are ranked
+        Ok(diesel::sql_query(format!(
+            "{} WHERE stats.game_type = $4 AND ratings.id IS NOT NULL
+            ORDER BY ratings.rating DESC, stats.user_id OFFSET $5 LIMIT $6",
+            USER_STATS_QUERY
+        ))
+        .bind::<Text, _>(time_expired_pattern())
+        .bind::<Double, _>(DEFAULT_RATING)
+        .bind::<Double, _>(DEFAULT_DEVIATION)
+        .bind::<Text, _>(game_type)
+        .bind::<BigInt, _>(offset)
+        .bind::<BigInt, _>(limit)
+        .load::<UserStats>(&self.0)?)
+    }
+
+    // ---- Tournaments ----
+    fn insert_tournament(&self, tourney: &NewDBTournament) -> Result<DBTournament, Error> {
+        Ok(diesel::insert_into(tournaments::table)
+            .values(tourney)
+            .get_result::<DBTournament>(&self.0)?)
+    }
+
+    fn find_tournament(&self, id: TournamentId) -> Result<Option<DBTournament>, Error> {
+        Ok(tournaments::dsl::tournaments
+            .find(id)
+            .first::<DBTournament>(&self.0)
+            .optional()?)
+    }
+
+    fn save_tournament(&self, tourney: &DBTournament) -> Result<(), Error> {
+        diesel::update(tournaments::dsl::tournaments.find(tourney.id))
+            .set(tourney)
+            .execute(&self.0)?;
+        Ok(())
+    }
+
+    fn insert_tournament_player(
+        &self,
+        player: &NewTournamentPlayer,
+    ) -> Result<TournamentPlayer, Error> {
+        Ok(diesel::insert_into(tournament_players::table)
+            .values(player)
+            .get_result::<TournamentPlayer>(&self.0)?)
+    }
+
+    fn find_tournament_players(&self, id: TournamentId) -> Result<Vec<TournamentPlayer>, Error> {
+        use tournament_players::dsl;
+        Ok(dsl::tournament_players
+            .filter(dsl::tournament_id.eq(id))
+            .order(dsl::id.asc())
+            .load::<TournamentPlayer>(&self.0)?)
+    }
+
+    fn find_tournament_player(
+        &self,
+        id: TournamentId,
+        user_id: UserId,
+    ) -> Result<Option<TournamentPlayer>, Error> {
+        use tournament_players::dsl;
+        Ok(dsl::tournament_players
+            .filter(dsl::tournament_id.eq(id).and(dsl::user_id.eq(user_id)))
+            .first::<TournamentPlayer>(&self.0)
+            .optional()?)
+    }
+
+    fn find_user_tournament_players(
+        &self,
+        user_id: UserId,
+    ) -> Result<Vec<TournamentPlayer>, Error> {
+        use tournament_players::dsl;
+        Ok(dsl::tournament_players
+            .filter(dsl::user_id.eq(user_id))
+            .order(dsl::id.asc())
+            .load::<TournamentPlayer>(&self.0)?)
+    }
+
+    fn save_tournament_player(&self, player: &TournamentPlayer) -> Result<(), Error> {
+        diesel::update(tournament_players::dsl::tournament_players.find(player.id))
+            .set(player)
+            .execute(&self.0)?;
+        Ok(())
+    }
+
+    fn delete_tournament_player(&self, id: TournamentPlayerId) -> Result<(), Error> {
+        diesel::delete(tournament_players::dsl::tournament_players.find(id)).execute(&self.0)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,276 @@
+use crate::db::DBWrapper;
+use crate::error::Error;
+use crate::games::GameState;
+use crate::models::{TournamentId, TournamentPlayer, UserId};
+use crate::tournament::{
+    create_tournament_game, load_paired_games, start_idle_games, PairedGame, TournamentCfg,
+    TournamentType, TournamentTypeInstance,
+};
+use std::fmt;
+use std::fmt::Formatter;
+
+/// A gauntlet tournament, where one challenger plays every other player a set number of times, alternating colors.
+/// The other players don't play each other. Options are the challenger's user id and the number of games against
+/// each opponent.
+pub struct Gauntlet();
+pub struct GauntletInstance {
+    challenger: UserId,
+    games_per_opponent: usize,
+}
+
+/// The challenger's results against an opponent
+#[derive(Debug, PartialEq, Clone)]
+struct OpponentRecord {
+    opponent: UserId,
+    challenger_score: f64,
+    opponent_score: f64,
+    games: Vec<usize>,
+}
+
+impl TournamentType for Gauntlet {
+    fn new(
+        &self,
+        data: &str,
+        _cfg: &TournamentCfg,
+    ) -> Result<Box<dyn TournamentTypeInstance>, Error> {
+        let fields = data.split_whitespace().collect::<Vec<&str>>();
+        let (challenger, games_per_opponent) = match fields.as_slice() {
+            [challenger, games] => (challenger.parse::<UserId>()?, games.parse::<usize>()?),
+            _ => {
+                return Err(Error::InvalidTournamentOptions(
+                    "expected challenger user id and games per opponent".to_string(),
+                ))
+            }
+        };
+        if games_per_opponent == 0 {
+            return Err(Error::InvalidTournamentOptions(
+                "games per opponent must be a positive number".to_string(),
+            ));
+        }
+        Ok(Box::new(GauntletInstance {
+            challenger,
+            games_per_opponent,
+        }))
+    }
+}
+
+impl GauntletInstance {
+    fn create_games(
+        &mut self,
+        id: TournamentId,
+        owner: UserId,
+        cfg: &TournamentCfg,
+        players: &[TournamentPlayer],
+        db: &DBWrapper,
+    ) -> Result<usize, Error> {
+        // the challenger plays each opponent, alternating colors
+        let mut created = 0;
+        for opponent in players.iter().filter(|p| p.user_id != self.challenger) {
+            for game in 0..self.games_per_opponent {
+                let players = if game % 2 == 0 {
+                    [self.challenger, opponent.user_id]
+                } else {
+                    [opponent.user_id, self.challenger]
+                };
+                create_tournament_game(id, owner, cfg, cfg.time_cfg, &players, db)?;
+                created += 1;
+            }
+        }
+
+        Ok(created)
+    }
+
+    /// Find the challenger's score against each opponent, in the order opponents joined
+    fn records(&self, players: &[TournamentPlayer], games: &[PairedGame]) -> Vec<OpponentRecord> {
+        let mut records = vec![];
+        for opponent in players.iter().filter(|p| p.user_id != self.challenger) {
+            let mut record = OpponentRecord {
+                opponent: opponent.user_id,
+                challenger_score: 0.0,
+                opponent_score: 0.0,
+                games: vec![],
+            };
+            for (index, game) in games.iter().enumerate() {
+                let players = [game.white, game.black];
+                if !players.contains(&self.challenger) || !players.contains(&opponent.user_id) {
+                    continue;
+                }
+                record.games.push(index);
+                match game.result {
+                    Some(GameState::Win(winner)) if winner == self.challenger => {
+                        record.challenger_score += 1.0
+                    }
+                    Some(GameState::Win(_)) => record.opponent_score += 1.0,
+                    Some(GameState::Tie) => {
+                        record.challenger_score += 0.5;
+                        record.opponent_score += 0.5;
+                    }
+                    _ => {}
+                }
+            }
+            records.push(record);
+        }
+        records
+    }
+}
+
+impl TournamentTypeInstance for GauntletInstance {
+    fn serialize(&self, _cfg: &TournamentCfg, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.challenger, self.games_per_opponent)
+    }
+
+    /// Serialize the challenger's record against each opponent
+    /// (`[[<opponent_id>, <challenger_score>, <opponent_score>, [<game ids>]], ...]`)
+    fn serialize_games(
+        &self,
+        id: TournamentId,
+        _cfg: &TournamentCfg,
+        f: &mut Formatter<'_>,
+        db: &DBWrapper,
+    ) -> fmt::Result {
+        let games = load_paired_games(id, db)?;
+        let players = db.find_tournament_players(id)?;
+        let records = self.records(&players, &games);
+        write!(f, "[")?;
+        for (index, record) in records.iter().enumerate() {
+            write!(
+                f,
+                "[{}, {}, {}, [",
+                record.opponent, record.challenger_score, record.opponent_score
+            )?;
+            for (i, game) in record.games.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", games[*game].id)?;
+            }
+            write!(f, "]]")?;
+            if index < records.len() - 1 {
+                write!(f, ", ")?;
+            }
+        }
+        write!(f, "]")
+    }
+
+    fn check_start(&self, players: &[TournamentPlayer]) -> Result<(), Error> {
+        if !players.iter().any(|p| p.user_id == self.challenger) {
+            Err(Error::InvalidTournamentOptions(
+                "the challenger hasn't joined the tournament".to_string(),
+            ))
+        } else if players.iter().all(|p| p.user_id == self.challenger) {
+            Err(Error::InvalidTournamentOptions(
+                "the challenger has no opponents".to_string(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn advance(
+        &mut self,
+        id: TournamentId,
+        owner: UserId,
+        cfg: &TournamentCfg,
+        players: &[TournamentPlayer],
+        db: &DBWrapper,
+    ) -> Result<(), Error> {
+        // if no players, do nothing (tournament ended)
+        if players.is_empty() {
+            return Ok(());
+        }
+        let games = db.find_tournament_games(id)?;
+        // if no games exist, tournament was just started, so make games
+        if games.is_empty() {
+            // advance to start games, if there were any to make
+            if self.create_games(id, owner, cfg, players, db)? > 0 {
+                return self.advance(id, owner, cfg, players, db);
+            }
+            return Ok(());
+        }
+        // the challenger is in every game, so games are played one at a time
+        start_idle_games(games, owner, players, db)
+    }
+
+    fn end_state(
+        &self,
+        started: bool,
+        id: TournamentId,
+        _cfg: &TournamentCfg,
+        players: &[TournamentPlayer],
+        db: &DBWrapper,
+    ) -> Result<GameState, Error> {
+        if !started {
+            return Ok(GameState::InProgress);
+        }
+        // no players forces a tie
+        if players.is_empty() {
+            return Ok(GameState::Tie);
+        }
+        let games = load_paired_games(id, db)?;
+        if games.is_empty() || games.iter().any(|g| !g.finished) {
+            return Ok(GameState::InProgress);
+        }
+
+        // the challenger wins with more than half of the points. Otherwise, the opponent who did best against the
+        // challenger wins.
+        let records = self.records(players, &games);
+        let challenger_score = records.iter().map(|r| r.challenger_score).sum::<f64>();
+        let opponent_score = records.iter().map(|r| r.opponent_score).sum::<f64>();
+        if challenger_score > opponent_score {
+            return Ok(GameState::Win(self.challenger));
+        }
+        if challenger_score == opponent_score {
+            return Ok(GameState::Tie);
+        }
+        let best = records
+            .iter()
+            .map(|r| r.opponent_score - r.challenger_score)
+            .fold(f64::MIN, f64::max);
+        let best_opponents = records
+            .iter()
+            .filter(|r| r.opponent_score - r.challenger_score == best)
+            .collect::<Vec<_>>();
+        match best_opponents.as_slice() {
+            [only] => Ok(GameState::Win(only.opponent)),
+            _ => Ok(GameState::Tie),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tournament::test_util::{game, player};
+
+    #[test]
+    fn records() {
+        let inst = GauntletInstance {
+            challenger: 2,
+            games_per_opponent: 2,
+        };
+        let players = vec![player(1, 0, 0, 0), player(2, 0, 0, 0), player(3, 0, 0, 0)];
+        let games = vec![
+            game(2, 1, Some(GameState::Win(2))),
+            game(1, 2, Some(GameState::Tie)),
+            game(2, 3, Some(GameState::Win(3))),
+            game(3, 2, None),
+        ];
+        assert_eq!(
+            inst.records(&players, &games),
+            vec![
+                OpponentRecord {
+                    opponent: 1,
+                    challenger_score: 1.5,
+                    opponent_score: 0.5,
+                    games: vec![0, 1],
+                },
+                OpponentRecord {
+                    opponent: 3,
+                    challenger_score: 0.0,
+                    opponent_score: 1.0,
+                    games: vec![2, 3],
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,747 @@
+use crate::db::{DBWrapper, GameTimeCfg};
+use crate::error::Error;
+use crate::games::GameState;
+use crate::models::{TournamentId, TournamentPlayer, UserId};
+use crate::tournament::{
+    create_tournament_game, load_paired_games, PairedGame, TournamentCfg, TournamentType,
+    TournamentTypeInstance,
+};
+use std::cmp::Ordering;
+use std::fmt;
+use std::fmt::Formatter;
+
+// drawn tiebreak games to play before the better seed advances
+const MAX_TIEBREAK_GAMES: usize = 4;
+// tiebreak games are played with the time control divided by this
+const TIEBREAK_TIME_DIVISOR: u32 = 4;
+
+/// A single elimination tournament, where players are placed in a bracket by seed and the loser of each match is
+/// eliminated. Options are the number of games in each match, optionally followed by how to seed players.
+pub struct Knockout();
+
+/// A double elimination tournament, where players who lose a match in the winners bracket drop to the losers
+/// bracket, and are only eliminated by a second loss. Takes the same options as `Knockout`.
+pub struct DoubleElimination();
+
+/// How players are seeded when the tournament starts
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Seeding {
+    // in the order players joined
+    Join,
+    // by rating in the tournament's game type, best first (ties in rating are broken by join order)
+    Rating,
+}
+
+pub struct KnockoutInstance {
+    double: bool,
+    games_per_match: usize,
+    seeding: Seeding,
+    // players in order of seed, once the tournament has started
+    seeds: Vec<UserId>,
+}
+
+/// Where a player in a match comes from
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Source {
+    Seed(usize),
+    Winner(usize),
+    Loser(usize),
+}
+
+/// The part of the bracket a match is in
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Section {
+    Winners,
+    Losers,
+    Final,
+}
+
+/// A match in the bracket
+#[derive(Debug, PartialEq, Clone)]
+struct Match {
+    section: Section,
+    round: usize,
+    sources: [Source; 2],
+    // for the grand final reset, the grand final match. The reset is only played if the player from the losers
+    // bracket wins the grand final.
+    reset_of: Option<usize>,
+}
+
+/// A player's place in a match
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Slot {
+    // not known yet
+    Pending,
+    Player(UserId),
+    // no player (an empty spot in the bracket)
+    Nobody,
+}
+
+impl fmt::Display for Slot {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Slot::Pending => write!(f, "-"),
+            Slot::Player(user_id) => write!(f, "{}", user_id),
+            Slot::Nobody => write!(f, "bye"),
+        }
+    }
+}
+
+/// The state of a match
+#[derive(Debug, PartialEq, Clone)]
+struct MatchResult {
+    players: [Slot; 2],
+    // indexes of the match's games in the tournament's games
+    games: Vec<usize>,
+    winner: Slot,
+    loser: Slot,
+}
+
+impl TournamentType for Knockout {
+    fn new(
+        &self,
+        data: &str,
+        _cfg: &TournamentCfg,
+    ) -> Result<Box<dyn TournamentTypeInstance>, Error> {
+        Ok(Box::new(KnockoutInstance::parse(data, false)?))
+    }
+}
+
+impl TournamentType for DoubleElimination {
+    fn new(
+        &self,
+        data: &str,
+        _cfg: &TournamentCfg,
+    ) -> Result<Box<dyn TournamentTypeInstance>, Error> {
+        Ok(Box::new(KnockoutInstance::parse(data, true)?))
+    }
+}
+
+/// Order seeds in a bracket of the given size (a power of two), so that each pair of seeds plays in the first round,
+/// and the best seeds meet as late as possible
+fn seed_order(size: usize) -> Vec<usize> {
+    let mut order = vec![0];
+    while order.len() < size {
+        let len = order.len() * 2;
+        order = order.iter().flat_map(|s| vec![*s, len - 1 - s]).collect();
+    }
+    order
+}
+
+impl KnockoutInstance {
+    /// Parse the games per match and seeding, followed by the seeds once the tournament has started (as
+    /// `seeds:<user_id>.<user_id>...`)
+    fn parse(data: &str, double: bool) -> Result<KnockoutInstance, Error> {
+        let mut fields = data.split_whitespace();
+        let games_per_match = fields
+            .next()
+            .and_then(|n| n.parse::<usize>().ok())
+            .filter(|n| *n > 0)
+            .ok_or_else(|| {
+                Error::InvalidTournamentOptions(
+                    "games per match must be a positive number".to_string(),
+                )
+            })?;
+        let mut inst = KnockoutInstance {
+            double,
+            games_per_match,
+            seeding: Seeding::Join,
+            seeds: vec![],
+        };
+        for field in fields {
+            match field {
+                "join" => inst.seeding = Seeding::Join,
+                "rating" => inst.seeding = Seeding::Rating,
+                _ if field.starts_with("seeds:") => {
+                    for seed in field["seeds:".len()..].split('.') {
+                        inst.seeds.push(seed.parse::<UserId>()?);
+                    }
+                }
+                _ => {
+                    return Err(Error::InvalidTournamentOptions(format!(
+                        "unknown seeding: {}",
+                        field
+                    )))
+                }
+            }
+        }
+        Ok(inst)
+    }
+
+    /// Build the bracket for the seeded players. Matches only take players from earlier matches.
+    fn bracket(&self) -> Vec<Match> {
+        let mut matches = vec![];
+        if self.seeds.len() < 2 {
+            return matches;
+        }
+        let size = self.seeds.len().next_power_of_two();
+        let num_rounds = size.trailing_zeros() as usize;
+        let add = |matches: &mut Vec<Match>, section, round, sources| {
+            matches.push(Match {
+                section,
+                round,
+                sources,
+                reset_of: None,
+            });
+            matches.len() - 1
+        };
+
+        // winners bracket
+        let mut winners: Vec<Vec<usize>> = vec![seed_order(size)
+            .chunks(2)
+            .map(|pair| {
+                add(
+                    &mut matches,
+                    Section::Winners,
+                    0,
+                    [Source::Seed(pair[0]), Source::Seed(pair[1])],
+                )
+            })
+            .collect()];
+        for round in 1..num_rounds {
+            let prev = winners[round - 1].clone();
+            winners.push(
+                prev.chunks(2)
+                    .map(|pair| {
+                        add(
+                            &mut matches,
+                            Section::Winners,
+                            round,
+                            [Source::Winner(pair[0]), Source::Winner(pair[1])],
+                        )
+                    })
+                    .collect(),
+            );
+        }
+        let winners_final = winners[num_rounds - 1][0];
+        if !self.double {
+            return matches;
+        }
+
+        // losers bracket: losers of the first round play each other, then each later round's losers drop in to play
+        // the losers bracket's survivors, who play each other between drops
+        let mut losers: Vec<usize> = vec![];
+        let mut round = 0;
+        if num_rounds >= 2 {
+            losers = winners[0]
+                .chunks(2)
+                .map(|pair| {
+                    add(
+                        &mut matches,
+                        Section::Losers,
+                        round,
+                        [Source::Loser(pair[0]), Source::Loser(pair[1])],
+                    )
+                })
+                .collect();
+            round += 1;
+        }
+        for (winners_round, dropping) in winners.iter().enumerate().skip(1) {
+            // alternate the order players drop in, to avoid rematches
+            let count = dropping.len();
+            losers = (0..count)
+                .map(|i| {
+                    let drop = if winners_round % 2 == 1 {
+                        dropping[count - 1 - i]
+                    } else {
+                        dropping[i]
+                    };
+                    add(
+                        &mut matches,
+                        Section::Losers,
+                        round,
+                        [Source::Winner(losers[i]), Source::Loser(drop)],
+                    )
+                })
+                .collect();
+            round += 1;
+            if winners_round < num_rounds - 1 {
+                losers = losers
+                    .chunks(2)
+                    .map(|pair| {
+                        add(
+                            &mut matches,
+                            Section::Losers,
+                            round,
+                            [Source::Winner(pair[0]), Source::Winner(pair[1])],
+                        )
+                    })
+                    .collect();
+                round += 1;
+            }
+        }
+        let losers_champion = match losers.last() {
+            Some(last) => Source::Winner(*last),
+            None => Source::Loser(winners_final),
+        };
+
+        // grand final, and a reset if the winners bracket's champion loses it
+        let sources = [Source::Winner(winners_final), losers_champion];
+        let grand_final = add(&mut matches, Section::Final, 0, sources);
+        let reset = add(&mut matches, Section::Final, 1, sources);
+        matches[reset].reset_of = Some(grand_final);
+        matches
+    }
+
+    /// Rank of a player's seed (lower is better)
+    fn seed_rank(&self, user_id: UserId) -> usize {
+        self.seeds
+            .iter()
+            .position(|s| *s == user_id)
+            .unwrap_or(usize::MAX)
+    }
+
+    /// Find the winner of a match between two players from its games, or None if it isn't decided yet.
+    /// Drawn games score half a point each.
+    fn decide(&self, a: UserId, b: UserId, games: &[&PairedGame]) -> Option<UserId> {
+        if games.len() < self.games_per_match || games.iter().any(|g| !g.finished) {
+            return None;
+        }
+        let (regular, tiebreaks) = games.split_at(self.games_per_match);
+        // count half points
+        let (mut score_a, mut score_b) = (0, 0);
+        for game in regular.iter() {
+            match game.result {
+                Some(GameState::Win(winner)) if winner == a => score_a += 2,
+                Some(GameState::Win(winner)) if winner == b => score_b += 2,
+                _ => {
+                    score_a += 1;
+                    score_b += 1;
+                }
+            }
+        }
+        if score_a != score_b {
+            return Some(if score_a > score_b { a } else { b });
+        }
+        // the first decisive tiebreak game wins the match
+        for game in tiebreaks.iter() {
+            if let Some(GameState::Win(winner)) = game.result {
+                return Some(winner);
+            }
+        }
+        if tiebreaks.len() >= MAX_TIEBREAK_GAMES {
+            return Some(if self.seed_rank(a) <= self.seed_rank(b) {
+                a
+            } else {
+                b
+            });
+        }
+        None
+    }
+
+    /// Find the state of each match in the bracket. Games are assigned to matches by their players: each match takes
+    /// games between its players (in the order they were created) until it is decided.
+    fn resolve(&self, bracket: &[Match], games: &[PairedGame]) -> Vec<MatchResult> {
+        let mut used = vec![false; games.len()];
+        let mut results: Vec<MatchResult> = vec![];
+        for m in bracket.iter() {
+            let slot = |source: Source| match source {
+                Source::Seed(seed) => self
+                    .seeds
+                    .get(seed)
+                    .map(|uid| Slot::Player(*uid))
+                    .unwrap_or(Slot::Nobody),
+                Source::Winner(index) => results[index].winner,
+                Source::Loser(index) => results[index].loser,
+            };
+            let players = [slot(m.sources[0]), slot(m.sources[1])];
+            let mut res = MatchResult {
+                players,
+                games: vec![],
+                winner: Slot::Pending,
+                loser: Slot::Pending,
+            };
+            match players {
+                [Slot::Nobody, Slot::Nobody] => {
+                    res.winner = Slot::Nobody;
+                    res.loser = Slot::Nobody;
+                }
+                [Slot::Player(_), Slot::Nobody] => {
+                    res.winner = players[0];
+                    res.loser = Slot::Nobody;
+                }
+                [Slot::Nobody, Slot::Player(_)] => {
+                    res.winner = players[1];
+                    res.loser = Slot::Nobody;
+                }
+                [Slot::Player(a), Slot::Player(b)] => match m.reset_of.map(|gf| results[gf].winner)
+                {
+                    // wait to see if the reset is needed
+                    Some(Slot::Pending) => {}
+                    // the winners bracket's champion won the grand final, so there is no reset
+                    Some(winner) if winner == Slot::Player(a) => {
+                        res.winner = Slot::Player(a);
+                        res.loser = Slot::Player(b);
+                    }
+                    _ => {
+                        for (index, game) in games.iter().enumerate() {
+                            let in_match = (game.white == a && game.black == b)
+                                || (game.white == b && game.black == a);
+                            if used[index] || !in_match {
+                                continue;
+                            }
+                            let match_games =
+                                res.games.iter().map(|i| &games[*i]).collect::<Vec<_>>();
+                            if self.decide(a, b, &match_games).is_some() {
+                                break;
+                            }
+                            used[index] = true;
+                            res.games.push(index);
+                        }
+                        let match_games = res.games.iter().map(|i| &games[*i]).collect::<Vec<_>>();
+                        if let Some(winner) = self.decide(a, b, &match_games) {
+                            res.winner = Slot::Player(winner);
+                            res.loser = Slot::Player(if winner == a { b } else { a });
+                        }
+                    }
+                },
+                _ => {}
+            }
+            results.push(res);
+        }
+        results
+    }
+
+    /// Create the next games in a match: all of the regular games, or the next tiebreak game
+    fn create_match_games(
+        &self,
+        id: TournamentId,
+        owner: UserId,
+        cfg: &TournamentCfg,
+        players: [UserId; 2],
+        num_games: usize,
+        db: &DBWrapper,
+    ) -> Result<(), Error> {
+        let (time_cfg, end) = if num_games < self.games_per_match {
+            (cfg.time_cfg, self.games_per_match)
+        } else {
+            let time_cfg = GameTimeCfg {
+                per_move: cfg.time_cfg.per_move / TIEBREAK_TIME_DIVISOR,
+                sudden_death: cfg.time_cfg.sudden_death / TIEBREAK_TIME_DIVISOR,
+            };
+            (time_cfg, num_games + 1)
+        };
+        // colors alternate between games
+        for game in num_games..end {
+            let (white, black) = if game % 2 == 0 {
+                (players[0], players[1])
+            } else {
+                (players[1], players[0])
+            };
+            create_tournament_game(id, owner, cfg, time_cfg, &[white, black], db)?;
+        }
+        Ok(())
+    }
+
+    fn write_round(
+        f: &mut Formatter<'_>,
+        matches: &[(&Match, &MatchResult)],
+        games: &[PairedGame],
+    ) -> fmt::Result {
+        write!(f, "[")?;
+        for (index, (_, res)) in matches.iter().enumerate() {
+            write!(
+                f,
+                "[{}, {}, {}, [",
+                res.players[0], res.players[1], res.winner
+            )?;
+            for (i, game) in res.games.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", games[*game].id)?;
+            }
+            write!(f, "]]")?;
+            if index < matches.len() - 1 {
+                write!(f, ", ")?;
+            }
+        }
+        write!(f, "]")
+    }
+}
+
+impl TournamentTypeInstance for KnockoutInstance {
+    fn serialize(&self, _cfg: &TournamentCfg, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.games_per_match)?;
+        match self.seeding {
+            Seeding::Join => write!(f, " join")?,
+            Seeding::Rating => write!(f, " rating")?,
+        }
+        if !self.seeds.is_empty() {
+            write!(f, " seeds:")?;
+            for (index, seed) in self.seeds.iter().enumerate() {
+                if index > 0 {
+                    write!(f, ".")?;
+                }
+                write!(f, "{}", seed)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Serialize the bracket. Each section of the bracket (the winners bracket, and for double elimination the losers
+    /// bracket and the grand final) is an array of rounds, and each round is an array of matches
+    /// (`[<player>, <player>, <winner>, [<game ids>]]`).
+    fn serialize_games(
+        &self,
+        id: TournamentId,
+        _cfg: &TournamentCfg,
+        f: &mut Formatter<'_>,
+        db: &DBWrapper,
+    ) -> fmt::Result {
+        let games = load_paired_games(id, db)?;
+        let bracket = self.bracket();
+        let results = self.resolve(&bracket, &games);
+        let sections = if self.double {
+            vec![Section::Winners, Section::Losers, Section::Final]
+        } else {
+            vec![Section::Winners]
+        };
+        for (index, section) in sections.iter().enumerate() {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            let matches = bracket
+                .iter()
+                .zip(results.iter())
+                .filter(|(m, _)| m.section == *section)
+                .collect::<Vec<(&Match, &MatchResult)>>();
+            let num_rounds = matches.iter().map(|(m, _)| m.round + 1).max().unwrap_or(0);
+            write!(f, "[")?;
+            for round in 0..num_rounds {
+                if round > 0 {
+                    write!(f, ", ")?;
+                }
+                let round_matches = matches
+                    .iter()
+                    .filter(|(m, _)| m.round == round)
+                    .cloned()
+                    .collect::<Vec<_>>();
+                KnockoutInstance::write_round(f, &round_matches, &games)?;
+            }
+            write!(f, "]")?;
+        }
+        Ok(())
+    }
+
+    fn advance(
+        &mut self,
+        id: TournamentId,
+        owner: UserId,
+        cfg: &TournamentCfg,
+        players: &[TournamentPlayer],
+        db: &DBWrapper,
+    ) -> Result<(), Error> {
+        // if no players, do nothing (tournament ended)
+        if players.is_empty() {
+            return Ok(());
+        }
+        // seed players when the tournament starts
+        if self.seeds.is_empty() {
+            self.seeds = match self.seeding {
+                Seeding::Join => players.iter().map(|p| p.user_id).collect(),
+                Seeding::Rating => {
+                    let mut ratings = players
+                        .iter()
+                        .map(|p| Ok((p.user_id, db.find_rating(p.user_id, &cfg.game_type)?.rating)))
+                        .collect::<Result<Vec<(UserId, f64)>, Error>>()?;
+                    ratings.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+                    ratings.into_iter().map(|(uid, _)| uid).collect()
+                }
+            };
+        }
+        let bracket = self.bracket();
+        loop {
+            let games = load_paired_games(id, db)?;
+            let results = self.resolve(&bracket, &games);
+            let mut created = false;
+            for (m, res) in bracket.iter().zip(results.iter()) {
+                let (a, b) = match (res.players, res.winner) {
+                    ([Slot::Player(a), Slot::Player(b)], Slot::Pending) => (a, b),
+                    _ => continue,
+                };
+                // wait for the grand final before playing a reset
+                if m.reset_of.map(|gf| results[gf].winner) == Some(Slot::Pending) {
+                    continue;
+                }
+                let match_games = res.games.iter().map(|i| &games[*i]).collect::<Vec<_>>();
+                if match_games.iter().all(|g| g.finished) {
+                    self.create_match_games(id, owner, cfg, [a, b], match_games.len(), db)?;
+                    created = true;
+                } else if !match_games.iter().any(|g| g.started && !g.finished) {
+                    // start the match's next game
+                    if let Some(game) = match_games.iter().find(|g| !g.started) {
+                        db.start_game(game.id, owner)?;
+                    }
+                }
+            }
+            // start any games that were just created
+            if !created {
+                return Ok(());
+            }
+        }
+    }
+
+    fn end_state(
+        &self,
+        started: bool,
+        id: TournamentId,
+        _cfg: &TournamentCfg,
+        players: &[TournamentPlayer],
+        db: &DBWrapper,
+    ) -> Result<GameState, Error> {
+        if !started {
+            return Ok(GameState::InProgress);
+        }
+        // no players forces a tie
+        if players.is_empty() {
+            return Ok(GameState::Tie);
+        }
+        match self.seeds.as_slice() {
+            [] => return Ok(GameState::InProgress),
+            [only] => return Ok(GameState::Win(*only)),
+            _ => {}
+        }
+        // the winner of the last match wins the tournament
+        let bracket = self.bracket();
+        let results = self.resolve(&bracket, &load_paired_games(id, db)?);
+        match results.last().map(|r| r.winner) {
+            Some(Slot::Player(winner)) => Ok(GameState::Win(winner)),
+            Some(Slot::Nobody) => Ok(GameState::Tie),
+            _ => Ok(GameState::InProgress),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tournament::test_util::game;
+
+    fn inst(double: bool, games_per_match: usize, seeds: Vec<UserId>) -> KnockoutInstance {
+        KnockoutInstance {
+            double,
+            games_per_match,
+            seeding: Seeding::Join,
+            seeds,
+        }
+    }
+
+    #[test]
+    fn parse_serialize() {
+        let inst = KnockoutInstance::parse("2 join seeds:4.1.3", true).unwrap();
+        assert_eq!(inst.games_per_match, 2);
+        assert_eq!(inst.seeds, vec![4, 1, 3]);
+        let inst = KnockoutInstance::parse("1 rating", false).unwrap();
+        assert_eq!(inst.seeding, Seeding::Rating);
+        assert!(KnockoutInstance::parse("0", false).is_err());
+        assert!(KnockoutInstance::parse("2 elo", false).is_err());
+    }
+
+    #[test]
+    fn seeding() {
+        assert_eq!(seed_order(4), vec![0, 3, 1, 2]);
+        assert_eq!(seed_order(8), vec![0, 7, 3, 4, 1, 6, 2, 5]);
+    }
+
+    #[test]
+    fn brackets() {
+        for double in [false, true] {
+            for num_players in 2..=16 {
+                let bracket = inst(double, 1, (1..=num_players).collect()).bracket();
+                let size = (num_players as usize).next_power_of_two();
+                let expected = if double { 2 * size - 1 } else { size - 1 };
+                assert_eq!(bracket.len(), expected);
+                // matches only take players from earlier matches
+                for (index, m) in bracket.iter().enumerate() {
+                    for source in m.sources.iter() {
+                        match source {
+                            Source::Winner(i) | Source::Loser(i) => assert!(*i < index),
+                            Source::Seed(seed) => assert!(*seed < size),
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn byes() {
+        let inst = inst(false, 1, vec![10, 20, 30]);
+        let bracket = inst.bracket();
+        assert_eq!(bracket.len(), 3);
+        let results = inst.resolve(&bracket, &[]);
+        // the top seed has no opponent in the first round
+        assert_eq!(results[0].players, [Slot::Player(10), Slot::Nobody]);
+        assert_eq!(results[0].winner, Slot::Player(10));
+        assert_eq!(results[1].players, [Slot::Player(20), Slot::Player(30)]);
+        assert_eq!(results[1].winner, Slot::Pending);
+        assert_eq!(results[2].players, [Slot::Player(10), Slot::Pending]);
+    }
+
+    #[test]
+    fn tiebreaks() {
+        let inst = inst(false, 2, vec![1, 2]);
+        let split = vec![
+            game(1, 2, Some(GameState::Win(1))),
+            game(2, 1, Some(GameState::Win(2))),
+        ];
+        assert_eq!(inst.decide(1, 2, &split.iter().collect::<Vec<_>>()), None);
+
+        let mut games = split;
+        games.push(game(1, 2, Some(GameState::Tie)));
+        games.push(game(2, 1, Some(GameState::Win(2))));
+        assert_eq!(
+            inst.decide(1, 2, &games.iter().collect::<Vec<_>>()),
+            Some(2)
+        );
+
+        // after enough drawn tiebreaks, the better seed advances
+        let mut games = vec![
+            game(1, 2, Some(GameState::Tie)),
+            game(2, 1, Some(GameState::Tie)),
+        ];
+        for _ in 0..MAX_TIEBREAK_GAMES {
+            assert_eq!(inst.decide(2, 1, &games.iter().collect::<Vec<_>>()), None);
+            games.push(game(1, 2, Some(GameState::Tie)));
+        }
+        assert_eq!(
+            inst.decide(2, 1, &games.iter().collect::<Vec<_>>()),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn double_elimination() {
+        let inst = inst(true, 1, vec![1, 2, 3, 4]);
+        let bracket = inst.bracket();
+        // 3 winners bracket matches, 2 losers bracket matches, the grand final and its reset
+        assert_eq!(bracket.len(), 7);
+        let mut games = vec![
+            game(1, 4, Some(GameState::Win(1))),
+            game(2, 3, Some(GameState::Win(2))),
+            game(1, 2, Some(GameState::Win(1))),
+            game(4, 3, Some(GameState::Win(4))),
+            game(4, 2, Some(GameState::Win(2))),
+        ];
+        let results = inst.resolve(&bracket, &games);
+        assert_eq!(results[4].winner, Slot::Player(2));
+        assert_eq!(results[5].players, [Slot::Player(1), Slot::Player(2)]);
+        assert_eq!(results[6].winner, Slot::Pending);
+
+        // the winners bracket's champion winning the grand final ends the tournament
+        games.push(game(1, 2, Some(GameState::Win(1))));
+        let results = inst.resolve(&bracket, &games);
+        assert_eq!(results[6].winner, Slot::Player(1));
+        assert_eq!(results[6].games, vec![]);
+
+        // otherwise, the reset decides the winner
+        games.pop();
+        games.push(game(1, 2, Some(GameState::Win(2))));
+        games.push(game(1, 2, Some(GameState::Win(1))));
+        let results = inst.resolve(&bracket, &games);
+        assert_eq!(results[5].winner, Slot::Player(2));
+        assert_eq!(results[6].games, vec![6]);
+        assert_eq!(results[6].winner, Slot::Player(1));
+    }
+}
@@ -1,15 +1,21 @@
 use crate::db::{DBWrapper, GameTimeCfg};
 use crate::error::Error;
 use crate::games::{GameState, GameTurn};
-use crate::models::{TournamentId, TournamentPlayer, UserId};
+use crate::models::{DBGame, GameId, TournamentId, TournamentPlayer, UserId};
 use itertools::Itertools;
 use std::collections::HashMap;
 use std::fmt;
 use std::fmt::Formatter;
 
+pub mod gauntlet;
+pub mod knockout;
+pub mod swiss;
+
 pub struct TournamentCfg {
     pub game_type: String,
     pub time_cfg: GameTimeCfg,
+    // options passed to each game in the tournament
+    pub game_options: String,
 }
 
 /// A type of tournament game assignment method
@@ -54,6 +60,11 @@ pub trait TournamentTypeInstance {
         write!(f, "]")
     }
 
+    /// Check that the tournament can be started with the given players
+    fn check_start(&self, _players: &[TournamentPlayer]) -> Result<(), Error> {
+        Ok(())
+    }
+
     /// Advance the tournament -- create or start games + otherwise move the tournament forwards.
     /// Called when the tournament is first created, and when a game finishes
     fn advance(
@@ -78,6 +89,132 @@ pub trait TournamentTypeInstance {
 
 pub type TournamentTypeMap = HashMap<&'static str, Box<dyn TournamentType>>;
 
+/// Start a tournament's unstarted games that don't include any players who are already in an active game
+pub(crate) fn start_idle_games(
+    games: Vec<DBGame>,
+    owner: UserId,
+    players: &[TournamentPlayer],
+    db: &DBWrapper,
+) -> Result<(), Error> {
+    // load full game information
+    let mut games_and_players = vec![];
+    for game in games {
+        games_and_players.push(db.dbgame_to_game_and_players(game)?);
+    }
+    // count number of active games each player is in
+    let mut games_per_player = HashMap::new();
+    for player in players.iter() {
+        games_per_player.insert(player.user_id, 0);
+    }
+    for (game, players) in &games_and_players {
+        if let Some(instance) = game.instance.as_ref() {
+            if let GameTurn::Turn(_) = instance.turn() {
+                // game is active, so mark players as being in active game
+                for player in players {
+                    if let Some(active) = games_per_player.get_mut(&player.user_id) {
+                        *active += 1
+                    }
+                }
+            }
+        }
+    }
+    // threshold of most active games a player can be in at once (TODO: make configurable)
+    let max_active_games = 1;
+    // start games that don't include any active players
+    for (game, players) in &games_and_players {
+        // skip games that are already started or finished
+        if game.instance.is_some() {
+            continue;
+        }
+        let mut violates_thresh = false;
+        for player in players {
+            if games_per_player[&player.user_id] >= max_active_games {
+                violates_thresh = true;
+                break;
+            }
+        }
+        if violates_thresh {
+            continue;
+        }
+
+        // no players are involved in too many games, so we can start this game
+        db.start_game(game.id, owner)?;
+        // mark players as being in a game
+        for player in players {
+            *games_per_player.get_mut(&player.user_id).unwrap() += 1;
+        }
+    }
+
+    Ok(())
+}
+
+/// A two player game in a tournament
+#[derive(Debug)]
+pub(crate) struct PairedGame {
+    pub id: GameId,
+    pub white: UserId,
+    pub black: UserId,
+    pub started: bool,
+    pub finished: bool,
+    // None if the game is unfinished, or ended without a result
+    pub result: Option<GameState>,
+}
+
+/// Load the two player games in a tournament, in the order they were created
+pub(crate) fn load_paired_games(
+    id: TournamentId,
+    db: &DBWrapper,
+) -> Result<Vec<PairedGame>, Error> {
+    let mut res = vec![];
+    for game in db.find_tournament_games(id)? {
+        let players = db.find_game_players(game.id)?;
+        if players.len() != 2 {
+            continue;
+        }
+        let result = match (game.winner, game.is_tie) {
+            (Some(winner), _) => Some(GameState::Win(winner)),
+            (None, Some(true)) => Some(GameState::Tie),
+            _ => None,
+        };
+        res.push(PairedGame {
+            id: game.id,
+            white: players[0].user_id,
+            black: players[1].user_id,
+            started: game.state.is_some(),
+            finished: game.finished,
+            result,
+        });
+    }
+    Ok(res)
+}
+
+/// Create a game in a tournament and join its players in order (so the first player is white in chess).
+/// The game's info is published once the last player has joined.
+pub(crate) fn create_tournament_game(
+    id: TournamentId,
+    owner: UserId,
+    cfg: &TournamentCfg,
+    time_cfg: GameTimeCfg,
+    players: &[UserId],
+    db: &DBWrapper,
+) -> Result<GameId, Error> {
+    let game = db.without_callbacks()?.new_game(
+        &*cfg.game_type,
+        &*cfg.game_options,
+        owner,
+        time_cfg,
+        Some(id),
+    )?;
+    for (index, user_id) in players.iter().enumerate() {
+        if index < players.len() - 1 {
+            db.without_callbacks()?.join_game(game.id, *user_id)?;
+        } else {
+            db.join_game(game.id, *user_id)?;
+        }
+    }
+    Ok(game.id)
+}
+
 // A round robin tournament, where each permutation of players in run once
 pub struct RoundRobin();
 pub struct RoundRobinInstance {
@@ -112,19 +249,8 @@ impl RoundRobinInstance {
             .permutations(self.num_players_per_game)
             .unique()
         {
-            // make game
-            let game =
-                db.without_callbacks()?
-                    .new_game(&*cfg.game_type, owner, cfg.time_cfg, Some(id))?;
-            // attach players to game
-            for (index, player) in players.iter().enumerate() {
-                // wait until last player has joined to publish game info
-                if index < players.len() - 1 {
-                    db.without_callbacks()?.join_game(game.id, player.user_id)?;
-                } else {
-                    db.join_game(game.id, player.user_id)?;
-                };
-            }
+            let user_ids = players.iter().map(|p| p.user_id).collect::<Vec<UserId>>();
+            create_tournament_game(id, owner, cfg, cfg.time_cfg, &user_ids, db)?;
         }
 
         Ok(())
@@ -160,52 +286,8 @@ impl TournamentTypeInstance for RoundRobinInstance {
             // advance to start games
             return self.advance(id, owner, cfg, players, db);
         }
-        // otherwise, load full game information
-        let mut games_and_players = vec![];
-        for game in games {
-            games_and_players.push(db.dbgame_to_game_and_players(game)?);
-        }
-        // count number of active games each player is in
-        let mut games_per_player = HashMap::new();
-        for player in players.iter() {
-            games_per_player.insert(player.user_id, 0);
-        }
-        for (game, players) in &games_and_players {
-            if let Some(instance) = game.instance.as_ref() {
-                if let GameTurn::Turn(_) = instance.turn() {
-                    // game is active, so mark players as being in active game
-                    for player in players {
-                        if let Some(active) = games_per_player.get_mut(&player.user_id) {
-                            *active += 1
-                        }
-                    }
-                }
-            }
-        }
-        // threshold of most active games a player can be in at once (TODO: make configurable)
-        let max_active_games = 1;
-        // start games that don't include any active players
-        for (game, players) in &games_and_players {
-            let mut violates_thresh = false;
-            for player in players {
-                if games_per_player[&player.user_id] >= max_active_games {
-                    violates_thresh = true;
-                    break;
-                }
-            }
-            if violates_thresh {
-                continue;
-            }
-
-            // no players are involved in too many games, so we can start this game
-            db.start_game(game.id, owner)?;
-            // mark players as being in a game
-            for player in players {
-                *games_per_player.get_mut(&player.user_id).unwrap() += 1;
-            }
-        }
-
-        Ok(())
+        // otherwise, start games whose players are free
+        start_idle_games(games, owner, players, db)
     }
 
     fn end_state(
@@ -261,3 +343,35 @@ impl TournamentTypeInstance for RoundRobinInstance {
         }
     }
 }
+
+/// Test data shared by the tournament types' tests
+#[cfg(test)]
+pub(crate) mod test_util {
+    use super::PairedGame;
+    use crate::games::GameState;
+    use crate::models::{TournamentPlayer, UserId};
+
+    /// A finished game with the given result
+    pub fn game(white: UserId, black: UserId, result: Option<GameState>) -> PairedGame {
+        PairedGame {
+            id: 0,
+            white,
+            black,
+            started: true,
+            finished: true,
+            result,
+        }
+    }
+
+    /// A player in tournament 1 with the given record
+    pub fn player(user_id: UserId, win: i32, loss: i32, tie: i32) -> TournamentPlayer {
+        TournamentPlayer {
+            id: user_id,
+            user_id,
+            tournament_id: 1,
+            win,
+            loss,
+            tie,
+        }
+    }
+}
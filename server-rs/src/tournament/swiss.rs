@@ -0,0 +1,600 @@
+use crate::db::DBWrapper;
+use crate::error::Error;
+use crate::games::GameState;
+use crate::models::{TournamentId, TournamentPlayer, UserId};
+use crate::tournament::{
+    create_tournament_game, load_paired_games, PairedGame, TournamentCfg, TournamentType,
+    TournamentTypeInstance,
+};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fmt::Formatter;
+
+// most pairings to try before allowing players to meet again
+const MAX_PAIRING_STEPS: usize = 100_000;
+
+/// A swiss system tournament, where players with similar scores are paired against each other for a set number of
+/// rounds. Options are the number of rounds to play.
+pub struct Swiss();
+
+/// A round that has been paired
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct SwissRound {
+    num_games: usize,
+    // player who sat out the round (and was given a win for it)
+    bye: Option<UserId>,
+}
+
+pub struct SwissInstance {
+    rounds: usize,
+    played: Vec<SwissRound>,
+}
+
+/// A player's place in the tournament
+#[derive(Debug, PartialEq, Clone)]
+struct Standing {
+    user_id: UserId,
+    score: f64,
+    // sum of opponents' scores
+    buchholz: f64,
+    // sum of beaten opponents' scores and half of drawn opponents' scores
+    sonneborn_berger: f64,
+}
+
+impl Standing {
+    fn cmp_rank(&self, other: &Standing) -> Ordering {
+        other
+            .score
+            .partial_cmp(&self.score)
+            .unwrap_or(Ordering::Equal)
+            .then(
+                other
+                    .buchholz
+                    .partial_cmp(&self.buchholz)
+                    .unwrap_or(Ordering::Equal),
+            )
+            .then(
+                other
+                    .sonneborn_berger
+                    .partial_cmp(&self.sonneborn_berger)
+                    .unwrap_or(Ordering::Equal),
+            )
+    }
+}
+
+impl TournamentType for Swiss {
+    fn new(
+        &self,
+        data: &str,
+        _cfg: &TournamentCfg,
+    ) -> Result<Box<dyn TournamentTypeInstance>, Error> {
+        Ok(Box::new(SwissInstance::parse(data)?))
+    }
+}
+
+impl SwissInstance {
+    /// Parse the number of rounds, followed by each round that has been paired (as `<games>` or `<games>:<bye>`)
+    fn parse(data: &str) -> Result<SwissInstance, Error> {
+        let invalid_rounds =
+            || Error::InvalidTournamentOptions("rounds must be a positive number".to_string());
+        let mut fields = data.split_whitespace();
+        let rounds = fields
+            .next()
+            .and_then(|r| r.parse::<usize>().ok())
+            .filter(|r| *r > 0)
+            .ok_or_else(invalid_rounds)?;
+        let mut played = vec![];
+        for field in fields {
+            let mut parts = field.splitn(2, ':');
+            let num_games = parts.next().unwrap_or("").parse::<usize>()?;
+            let bye = match parts.next() {
+                Some(bye) => Some(bye.parse::<UserId>()?),
+                None => None,
+            };
+            played.push(SwissRound { num_games, bye });
+        }
+        Ok(SwissInstance { rounds, played })
+    }
+
+    /// Create the games for the next round
+    fn create_round(
+        &mut self,
+        id: TournamentId,
+        owner: UserId,
+        cfg: &TournamentCfg,
+        db: &DBWrapper,
+    ) -> Result<(), Error> {
+        // byes from earlier rounds change scores, so load the latest scores
+        let players = db.find_tournament_players(id)?;
+        let games = load_paired_games(id, db)?;
+        let standings = standings(&players, &games);
+        let had_bye = self
+            .played
+            .iter()
+            .filter_map(|r| r.bye)
+            .collect::<HashSet<UserId>>();
+        let (pairs, bye) = pair_round(&standings, &games, &had_bye);
+
+        for (white, black) in pairs.iter() {
+            create_tournament_game(id, owner, cfg, cfg.time_cfg, &[*white, *black], db)?;
+        }
+        if let Some(user_id) = bye {
+            let mut player = db.find_tournament_player(id, user_id)?;
+            player.win += 1;
+            db.save_tournament_player(&player)?;
+        }
+        self.played.push(SwissRound {
+            num_games: pairs.len(),
+            bye,
+        });
+        Ok(())
+    }
+}
+
+impl TournamentTypeInstance for SwissInstance {
+    fn serialize(&self, _cfg: &TournamentCfg, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.rounds)?;
+        for round in self.played.iter() {
+            write!(f, " {}", round.num_games)?;
+            if let Some(bye) = round.bye {
+                write!(f, ":{}", bye)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Serialize the games in each round, followed by the standings
+    /// (`[[<round 1 game ids>], ...], [[<user_id>, <score>, <buchholz>, <sonneborn_berger>], ...]`)
+    fn serialize_games(
+        &self,
+        id: TournamentId,
+        _cfg: &TournamentCfg,
+        f: &mut Formatter<'_>,
+        db: &DBWrapper,
+    ) -> fmt::Result {
+        let games = load_paired_games(id, db)?;
+        write!(f, "[")?;
+        let mut start = 0;
+        for (index, round) in self.played.iter().enumerate() {
+            let end = (start + round.num_games).min(games.len());
+            write!(f, "[")?;
+            for (i, game) in games[start..end].iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", game.id)?;
+            }
+            write!(f, "]")?;
+            if index < self.played.len() - 1 {
+                write!(f, ", ")?;
+            }
+            start = end;
+        }
+        write!(f, "], [")?;
+        let players = db.find_tournament_players(id)?;
+        let standings = standings(&players, &games);
+        for (index, standing) in standings.iter().enumerate() {
+            write!(
+                f,
+                "[{}, {}, {}, {}]",
+                standing.user_id, standing.score, standing.buchholz, standing.sonneborn_berger
+            )?;
+            if index < standings.len() - 1 {
+                write!(f, ", ")?;
+            }
+        }
+        write!(f, "]")
+    }
+
+    fn advance(
+        &mut self,
+        id: TournamentId,
+        owner: UserId,
+        cfg: &TournamentCfg,
+        players: &[TournamentPlayer],
+        db: &DBWrapper,
+    ) -> Result<(), Error> {
+        // if no players, do nothing (tournament ended)
+        if players.is_empty() {
+            return Ok(());
+        }
+        // start any games in the current round that haven't started, and check if the round is over
+        let mut round_finished = true;
+        for game in db.find_tournament_games(id)? {
+            if game.state.is_none() {
+                db.start_game(game.id, owner)?;
+                round_finished = false;
+            } else if !game.finished {
+                round_finished = false;
+            }
+        }
+        if !round_finished || self.played.len() >= self.rounds {
+            return Ok(());
+        }
+        // pair the next round, then advance to start its games
+        self.create_round(id, owner, cfg, db)?;
+        self.advance(id, owner, cfg, players, db)
+    }
+
+    fn end_state(
+        &self,
+        started: bool,
+        id: TournamentId,
+        _cfg: &TournamentCfg,
+        players: &[TournamentPlayer],
+        db: &DBWrapper,
+    ) -> Result<GameState, Error> {
+        if !started {
+            return Ok(GameState::InProgress);
+        }
+        // no players forces a tie
+        if players.is_empty() {
+            return Ok(GameState::Tie);
+        }
+        if self.played.len() < self.rounds {
+            return Ok(GameState::InProgress);
+        }
+        let games = load_paired_games(id, db)?;
+        if games.iter().any(|g| !g.finished) {
+            return Ok(GameState::InProgress);
+        }
+
+        // the winner is the top player after tiebreaks
+        let standings = standings(players, &games);
+        match standings.as_slice() {
+            [first, second, ..] if first.cmp_rank(second) == Ordering::Equal => Ok(GameState::Tie),
+            [first, ..] => Ok(GameState::Win(first.user_id)),
+            [] => Ok(GameState::Tie),
+        }
+    }
+}
+
+/// Find each player's score and tiebreaks, ordered from first to last place.
+/// Players with equal scores and tiebreaks are left in the order they joined.
+fn standings(players: &[TournamentPlayer], games: &[PairedGame]) -> Vec<Standing> {
+    let scores = players
+        .iter()
+        .map(|p| (p.user_id, p.win as f64 + 0.5 * p.tie as f64))
+        .collect::<HashMap<UserId, f64>>();
+    let mut standings = players
+        .iter()
+        .map(|p| Standing {
+            user_id: p.user_id,
+            score: scores[&p.user_id],
+            buchholz: 0.0,
+            sonneborn_berger: 0.0,
+        })
+        .collect::<Vec<Standing>>();
+    for standing in standings.iter_mut() {
+        for game in games.iter() {
+            let opponent = if game.white == standing.user_id {
+                game.black
+            } else if game.black == standing.user_id {
+                game.white
+            } else {
+                continue;
+            };
+            let opponent_score = match scores.get(&opponent) {
+                Some(score) => *score,
+                None => continue,
+            };
+            match game.result {
+                Some(GameState::Win(winner)) => {
+                    standing.buchholz += opponent_score;
+                    if winner == standing.user_id {
+                        standing.sonneborn_berger += opponent_score;
+                    }
+                }
+                Some(GameState::Tie) => {
+                    standing.buchholz += opponent_score;
+                    standing.sonneborn_berger += opponent_score / 2.0;
+                }
+                _ => {}
+            }
+        }
+    }
+    standings.sort_by(|a, b| a.cmp_rank(b));
+    standings
+}
+
+/// Pair players for the next round. Returns the (white, black) pairs and the player given a bye, if any.
+/// Players are paired within their score group (the top half against the bottom half), without repeating
+/// pairings. If every pairing has been played, players are paired by rank regardless of who they have met.
+fn pair_round(
+    standings: &[Standing],
+    games: &[PairedGame],
+    had_bye: &HashSet<UserId>,
+) -> (Vec<(UserId, UserId)>, Option<UserId>) {
+    let met = games
+        .iter()
+        .flat_map(|g| vec![(g.white, g.black), (g.black, g.white)])
+        .collect::<HashSet<(UserId, UserId)>>();
+    let ranked = standings
+        .iter()
+        .map(|s| (s.user_id, s.score))
+        .collect::<Vec<(UserId, f64)>>();
+
+    // the lowest ranked player who hasn't had a bye sits out when there is an odd number of players
+    let bye_candidates = if ranked.len() % 2 == 1 {
+        let mut candidates = ranked
+            .iter()
+            .rev()
+            .filter(|(uid, _)| !had_bye.contains(uid))
+            .map(|(uid, _)| Some(*uid))
+            .collect::<Vec<Option<UserId>>>();
+        if candidates.is_empty() {
+            candidates.push(ranked.last().map(|(uid, _)| *uid));
+        }
+        candidates
+    } else {
+        vec![None]
+    };
+
+    let mut steps = 0;
+    let mut found = None;
+    for bye in bye_candidates.iter() {
+        let rest = ranked
+            .iter()
+            .filter(|(uid, _)| Some(*uid) != *bye)
+            .cloned()
+            .collect::<Vec<(UserId, f64)>>();
+        if let Some(pairs) = pair_players(&rest, &met, &mut steps) {
+            found = Some((pairs, *bye));
+            break;
+        }
+    }
+    let (pairs, bye) = found.unwrap_or_else(|| {
+        let bye = bye_candidates[0];
+        let pairs = ranked
+            .iter()
+            .filter(|(uid, _)| Some(*uid) != bye)
+            .map(|(uid, _)| *uid)
+            .collect::<Vec<UserId>>()
+            .chunks(2)
+            .map(|pair| (pair[0], pair[1]))
+            .collect();
+        (pairs, bye)
+    });
+
+    let pairs = pairs
+        .into_iter()
+        .map(|(a, b)| assign_colors(a, b, games))
+        .collect();
+    (pairs, bye)
+}
+
+/// Pair the first player with the best opponent they haven't met, and recursively pair the rest.
+/// Players are given in rank order. Gives up once too many pairings have been tried.
+fn pair_players(
+    players: &[(UserId, f64)],
+    met: &HashSet<(UserId, UserId)>,
+    steps: &mut usize,
+) -> Option<Vec<(UserId, UserId)>> {
+    let (first, score) = match players.first() {
+        None => return Some(vec![]),
+        Some(first) => *first,
+    };
+    *steps += 1;
+    if *steps > MAX_PAIRING_STEPS {
+        return None;
+    }
+    // prefer the player halfway down the first player's score group, then the rest of the group, then lower groups.
+    // A player alone in their group floats down to the next one.
+    let group_len = players.iter().filter(|(_, s)| *s == score).count();
+    let half = (group_len / 2).max(1);
+    let order = (half..group_len)
+        .chain((1..half).rev())
+        .chain(group_len..players.len());
+    for index in order {
+        let opponent = players[index].0;
+        if met.contains(&(first, opponent)) {
+            continue;
+        }
+        let rest = players
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != 0 && *i != index)
+            .map(|(_, p)| *p)
+            .collect::<Vec<(UserId, f64)>>();
+        if let Some(mut pairs) = pair_players(&rest, met, steps) {
+            pairs.insert(0, (first, opponent));
+            return Some(pairs);
+        }
+    }
+    None
+}
+
+/// Pick colors for two players (the first is ranked higher). The player who has played white fewer times gets
+/// white, then the player who played black most recently. Otherwise, the higher ranked player alternates colors.
+fn assign_colors(a: UserId, b: UserId, games: &[PairedGame]) -> (UserId, UserId) {
+    let balance = |user_id: UserId| {
+        games.iter().fold(0, |bal, g| {
+            if g.white == user_id {
+                bal + 1
+            } else if g.black == user_id {
+                bal - 1
+            } else {
+                bal
+            }
+        })
+    };
+    // true if the player's last game was as white
+    let last_white = |user_id: UserId| {
+        games
+            .iter()
+            .rev()
+            .find(|g| g.white == user_id || g.black == user_id)
+            .map(|g| g.white == user_id)
+    };
+
+    let (balance_a, balance_b) = (balance(a), balance(b));
+    if balance_a != balance_b {
+        return if balance_a < balance_b {
+            (a, b)
+        } else {
+            (b, a)
+        };
+    }
+    match (last_white(a), last_white(b)) {
+        (Some(true), Some(false)) => (b, a),
+        (Some(false), Some(true)) => (a, b),
+        (Some(true), _) => (b, a),
+        _ => (a, b),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tournament::test_util::{game, player};
+
+    fn standing(user_id: UserId, score: f64) -> Standing {
+        Standing {
+            user_id,
+            score,
+            buchholz: 0.0,
+            sonneborn_berger: 0.0,
+        }
+    }
+
+    #[test]
+    fn parse_serialize() {
+        let inst = SwissInstance::parse("3 2:5 2").unwrap();
+        assert_eq!(inst.rounds, 3);
+        assert_eq!(
+            inst.played,
+            vec![
+                SwissRound {
+                    num_games: 2,
+                    bye: Some(5)
+                },
+                SwissRound {
+                    num_games: 2,
+                    bye: None
+                }
+            ]
+        );
+        assert!(SwissInstance::parse("0").is_err());
+        assert!(SwissInstance::parse("abc").is_err());
+        assert!(SwissInstance::parse("").is_err());
+    }
+
+    #[test]
+    fn first_round_pairs_top_half_against_bottom_half() {
+        let standings = (1..=4).map(|id| standing(id, 0.0)).collect::<Vec<_>>();
+        let (pairs, bye) = pair_round(&standings, &[], &HashSet::new());
+        assert_eq!(pairs, vec![(1, 3), (2, 4)]);
+        assert_eq!(bye, None);
+    }
+
+    #[test]
+    fn uneven_score_groups_float_down() {
+        // players alone in their score group are paired with the top of the next group
+        let standings = vec![
+            standing(1, 2.0),
+            standing(2, 1.0),
+            standing(3, 1.0),
+            standing(4, 0.0),
+        ];
+        let (pairs, bye) = pair_round(&standings, &[], &HashSet::new());
+        assert_eq!(pairs, vec![(1, 2), (3, 4)]);
+        assert_eq!(bye, None);
+
+        let standings = vec![
+            standing(1, 2.0),
+            standing(2, 2.0),
+            standing(3, 2.0),
+            standing(4, 1.0),
+            standing(5, 0.0),
+            standing(6, 0.0),
+        ];
+        let (pairs, _) = pair_round(&standings, &[], &HashSet::new());
+        assert_eq!(pairs, vec![(1, 2), (3, 4), (5, 6)]);
+    }
+
+    #[test]
+    fn bye_goes_to_lowest_player_without_one() {
+        let standings = vec![standing(1, 1.0), standing(3, 1.0), standing(2, 0.0)];
+        let had_bye = vec![3].into_iter().collect();
+        let games = vec![game(1, 2, Some(GameState::Win(1)))];
+        let (pairs, bye) = pair_round(&standings, &games, &had_bye);
+        assert_eq!(bye, Some(2));
+        // 3 has played white less, so gets white
+        assert_eq!(pairs, vec![(3, 1)]);
+    }
+
+    #[test]
+    fn avoids_repeat_pairings() {
+        let standings = vec![
+            standing(1, 1.0),
+            standing(3, 1.0),
+            standing(2, 0.0),
+            standing(4, 0.0),
+        ];
+        let games = vec![
+            game(1, 2, Some(GameState::Win(1))),
+            game(3, 4, Some(GameState::Win(3))),
+        ];
+        let (pairs, _) = pair_round(&standings, &games, &HashSet::new());
+        assert_eq!(pairs, vec![(3, 1), (2, 4)]);
+
+        // once everyone has met, pairings repeat
+        let games = vec![
+            game(1, 2, Some(GameState::Win(1))),
+            game(3, 4, Some(GameState::Win(3))),
+            game(3, 1, Some(GameState::Tie)),
+            game(2, 4, Some(GameState::Tie)),
+            game(1, 4, Some(GameState::Tie)),
+            game(2, 3, Some(GameState::Tie)),
+        ];
+        let (pairs, _) = pair_round(&standings, &games, &HashSet::new());
+        assert_eq!(pairs.len(), 2);
+    }
+
+    #[test]
+    fn tiebreaks() {
+        let players = vec![
+            player(1, 1, 0, 1),
+            player(2, 0, 0, 2),
+            player(3, 0, 1, 1),
+            player(4, 0, 0, 2),
+        ];
+        let games = vec![
+            game(1, 3, Some(GameState::Win(1))),
+            game(2, 4, Some(GameState::Tie)),
+            game(1, 2, Some(GameState::Tie)),
+            game(4, 3, Some(GameState::Tie)),
+        ];
+        let standings = standings(&players, &games);
+        assert_eq!(
+            standings,
+            vec![
+                Standing {
+                    user_id: 1,
+                    score: 1.5,
+                    buchholz: 1.5,
+                    sonneborn_berger: 1.0
+                },
+                Standing {
+                    user_id: 2,
+                    score: 1.0,
+                    buchholz: 2.5,
+                    sonneborn_berger: 1.25
+                },
+                Standing {
+                    user_id: 4,
+                    score: 1.0,
+                    buchholz: 1.5,
+                    sonneborn_berger: 0.75
+                },
+                Standing {
+                    user_id: 3,
+                    score: 0.5,
+                    buchholz: 2.5,
+                    sonneborn_berger: 0.5
+                },
+            ]
+        );
+    }
+}
@@ -168,10 +168,18 @@ pub async fn session_test(test: &str) {
     dotenv().ok();
 
     let mut game_type_map: GameTypeMap = HashMap::new();
-    game_type_map.insert("chess", Box::new(games::chess_game::ChessGame()));
+    game_type_map.insert("chess", Box::new(games::chess_game::ChessGame(None)));
+    game_type_map.insert("chess960", Box::new(games::chess_game::Chess960Game()));
 
     let mut tournament_type_map: TournamentTypeMap = HashMap::new();
     tournament_type_map.insert("round_robin", Box::new(tournament::RoundRobin()));
+    tournament_type_map.insert("swiss", Box::new(tournament::swiss::Swiss()));
+    tournament_type_map.insert("knockout", Box::new(tournament::knockout::Knockout()));
+    tournament_type_map.insert(
+        "double_elim",
+        Box::new(tournament::knockout::DoubleElimination()),
+    );
+    tournament_type_map.insert("gauntlet", Box::new(tournament::gauntlet::Gauntlet()));
 
     // find an open port
     let listener = TcpListener::bind("127.0.0.1:0").unwrap();
@@ -179,17 +187,23 @@ pub async fn session_test(test: &str) {
     let port = addr.port();
     drop(listener);
 
-    let base_url = env::var("DATABASE_TEST_BASE_URL").expect("DATABASE_TEST_BASE_URL must be set");
-    let default_url =
-        env::var("DATABASE_TEST_DEFAULT_URL").expect("DATABASE_TEST_DEFAULT_URL must be set");
-    let db_name = format!("server_rs_test_{}", port);
-    let mut db_test_ctx = PgTestContext::new(&*base_url, &*default_url, &*db_name);
+    // test against postgres if a test database server is given, otherwise keep data in memory
+    let (db_url, mut db_test_ctx) = match env::var("DATABASE_TEST_BASE_URL") {
+        Ok(base_url) => {
+            let default_url = env::var("DATABASE_TEST_DEFAULT_URL")
+                .expect("DATABASE_TEST_DEFAULT_URL must be set");
+            let db_name = format!("server_rs_test_{}", port);
+            let ctx = PgTestContext::new(&*base_url, &*default_url, &*db_name);
+            (format!("{}/{}", base_url, db_name), Some(ctx))
+        }
+        Err(_) => ("memory".to_string(), None),
+    };
 
     // start the server
     tokio::spawn((|| async move {
         server::run_server(
             &*format!("127.0.0.1:{}", port),
-            &format!("{}/{}", base_url, db_name),
+            &db_url,
             Arc::new(game_type_map),
             Arc::new(tournament_type_map),
         )
@@ -239,7 +253,9 @@ pub async fn session_test(test: &str) {
         }
     }
 
-    db_test_ctx.remove();
+    if let Some(ctx) = db_test_ctx.as_mut() {
+        ctx.remove();
+    }
 }
 
 mod tests {
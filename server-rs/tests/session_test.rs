@@ -147,13 +147,13 @@ async fn test_game_observe() {
 [C1] new_game chess, 100000, 0
 [S1] new_game 1
 [C2] observe_game 1
-[S2] game 1, chess, 1, false, false, -, 100000, 0, -, [], -
+[S2] game 1, chess, 1, false, false, -, 100000, 0, -, [], -, -
 [C1] join_game 1
 [S1] okay
-[S2] game 1, chess, 1, false, false, -, 100000, 0, -, [[1, 0, 100000]], -
+[S2] game 1, chess, 1, false, false, -, 100000, 0, -, [[1, 0, 100000, 1500]], -, -
 [C1] leave_game 1
 [S1] okay
-[S2] game 1, chess, 1, false, false, -, 100000, 0, -, [], -
+[S2] game 1, chess, 1, false, false, -, 100000, 0, -, [], -, -
 [C2] stop_observe_game 1
 [S2] okay
 [C1] join_game 1
@@ -202,13 +202,14 @@ async fn test_game_play() {
 [C1] play 1, a2a3
 [S1] okay
 [S2] go 1, chess, *, *, rnbqkbnr/ppppp1pp/5p2/8/4P3/P7/1PPP1PPP/RNBQKBNR b KQkq - 0 2
-[C2] play 1, g7g5
+// moves can be given in SAN
+[C2] play 1, g5
 [S2] okay
 [S1] go 1, chess, *, *, rnbqkbnr/ppppp2p/5p2/6p1/4P3/P7/1PPP1PPP/RNBQKBNR w KQkq g6 0 3
 [C1] observe_game 1
-[S1] game 1, chess, 4, true, false, -, 100000, 0, *, [[4, 0, *], [5, 0, *]], rnbqkbnr/ppppp2p/5p2/6p1/4P3/P7/1PPP1PPP/RNBQKBNR w KQkq g6 0 3,[e2e4,f7f6,a2a3,g7g5]
+[S1] game 1, chess, 4, true, false, -, 100000, 0, *, [[4, 0, *, 1500], [5, 0, *, 1500]], -, rnbqkbnr/ppppp2p/5p2/6p1/4P3/P7/1PPP1PPP/RNBQKBNR w KQkq g6 0 3,[e2e4,f7f6,a2a3,g7g5]
 [C1] play 1, d1h5
-[S1] game 1, chess, 4, true, true, 4, 100000, 0, *, [[4, 1, *], [5, 0, *]], rnbqkbnr/ppppp2p/5p2/6pQ/4P3/P7/1PPP1PPP/RNB1KBNR b KQkq - 0 3,[e2e4,f7f6,a2a3,g7g5,d1h5]
+[S1] game 1, chess, 4, true, true, 4, 100000, 0, *, [[4, 1, *, 1662], [5, 0, *, 1338]], -, rnbqkbnr/ppppp2p/5p2/6pQ/4P3/P7/1PPP1PPP/RNB1KBNR b KQkq - 0 3,[e2e4,f7f6,a2a3,g7g5,d1h5]
 [S1] okay
 [C2] version 2
 [S2] okay
@@ -217,6 +218,549 @@ async fn test_game_play() {
     "#).await;
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_game_options() {
+    session_test(
+        r#"
+[C1] version 2
+[S1] okay
+[C2] version 2
+[S2] okay
+[C1] new_tmp_user Test1
+[S1] okay
+[C2] new_tmp_user Test2
+[S2] okay
+[C1] new_game chess, 100000, 0, draw_rule=sometimes
+[S1] error invalid game options: unknown draw rule: sometimes
+[C1] new_game chess, 100000, 0, draw_rule=threefold
+[S1] new_game 1
+[C1] join_game 1
+[S1] okay
+[C2] join_game 1
+[S2] okay
+[C1] start_game 1
+[S1] go 1, chess, *, *, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1
+[S1] okay
+[C1] play 1, g1f3
+[S1] okay
+[S2] go 1, chess, *, *, rnbqkbnr/pppppppp/8/8/8/5N2/PPPPPPPP/RNBQKB1R b KQkq - 0 1
+[C2] play 1, g8f6
+[S2] okay
+[S1] go 1, chess, *, *, rnbqkb1r/pppppppp/5n2/8/8/5N2/PPPPPPPP/RNBQKB1R w KQkq - 0 2
+[C1] play 1, f3g1
+[S1] okay
+[S2] go 1, chess, *, *, rnbqkb1r/pppppppp/5n2/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 2
+[C2] play 1, f6g8
+[S2] okay
+[S1] go 1, chess, *, *, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 3
+[C1] play 1, g1f3
+[S1] okay
+[S2] go 1, chess, *, *, rnbqkbnr/pppppppp/8/8/8/5N2/PPPPPPPP/RNBQKB1R b KQkq - 0 3
+[C2] play 1, g8f6
+[S2] okay
+[S1] go 1, chess, *, *, rnbqkb1r/pppppppp/5n2/8/8/5N2/PPPPPPPP/RNBQKB1R w KQkq - 0 4
+[C1] play 1, f3g1
+[S1] okay
+[S2] go 1, chess, *, *, rnbqkb1r/pppppppp/5n2/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 4
+// starting position repeats for the third time
+[C2] observe_game 1
+[S2] game 1, chess, 1, true, false, -, 100000, 0, *, [[1, 0, *, 1500], [2, 0, *, 1500]], -, rnbqkb1r/pppppppp/5n2/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 4,[g1f3,g8f6,f3g1,f6g8,g1f3,g8f6,f3g1]
+[C2] play 1, f6g8
+[S2] game 1, chess, 1, true, true, tie, 100000, 0, *, [[1, 0.5, *, 1500], [2, 0.5, *, 1500]], -, __ENDED_GAME, -, Threefold Repetition, chess, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 5,[g1f3,g8f6,f3g1,f6g8,g1f3,g8f6,f3g1,f6g8]
+[S2] okay
+    "#,
+    )
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_game_start_position() {
+    session_test(
+        r#"
+[C1] version 2
+[S1] okay
+[C2] version 2
+[S2] okay
+[C1] new_tmp_user Test1
+[S1] okay
+[C2] new_tmp_user Test2
+[S2] okay
+[C1] new_game chess, 100000, 0, fen=8/8/4k3/8/8/4KK2/8/8 w - - 0 1
+[S1] error invalid game options: invalid start position: 8/8/4k3/8/8/4KK2/8/8 w - - 0 1
+[C1] new_game chess, 100000, 0, fen=8/8/4k3/8/8/4K3/8/4R3 w - -
+[S1] new_game 1
+[C1] join_game 1
+[S1] okay
+[C2] join_game 1
+[S2] okay
+[C1] start_game 1
+[S1] go 1, chess, *, *, 8/8/4k3/8/8/4K3/8/4R3 w - - 0 1
+[S1] okay
+[C1] play 1, e1e2
+[S1] okay
+[S2] go 1, chess, *, *, 8/8/4k3/8/8/4K3/4R3/8 b - - 0 1
+[C2] observe_game 1
+[S2] game 1, chess, 1, true, false, -, 100000, 0, *, [[1, 0, *, 1500], [2, 0, *, 1500]], -, 8/8/4k3/8/8/4K3/8/4R3 w - - 0 1,8/8/4k3/8/8/4K3/4R3/8 b - - 0 1,[e1e2]
+    "#,
+    )
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_game_chess960() {
+    session_test(
+        r#"
+[C1] version 2
+[S1] okay
+[C2] version 2
+[S2] okay
+[C1] new_tmp_user Test1
+[S1] okay
+[C2] new_tmp_user Test2
+[S2] okay
+[C1] new_game chess960, 100000, 0, index=960
+[S1] error invalid game options: invalid chess960 index: 960
+[C1] new_game chess960, 100000, 0, index=408
+[S1] new_game 1
+[C1] join_game 1
+[S1] okay
+[C2] join_game 1
+[S2] okay
+[C1] start_game 1
+[S1] go 1, chess960, *, *, rbqnbnkr/pppppppp/8/8/8/8/PPPPPPPP/RBQNBNKR w HAha - 0 1
+[S1] okay
+[C1] play 1, f1g3
+[S1] okay
+[S2] go 1, chess960, *, *, rbqnbnkr/pppppppp/8/8/8/6N1/PPPPPPPP/RBQNB1KR b HAha - 0 1
+[C2] play 1, f8g6
+[S2] okay
+// castle kingside by moving the king onto its rook
+[S1] go 1, chess960, *, *, rbqnb1kr/pppppppp/6n1/8/8/6N1/PPPPPPPP/RBQNB1KR w HAha - 0 2
+[C1] play 1, g1h1
+[S1] okay
+[S2] go 1, chess960, *, *, rbqnb1kr/pppppppp/6n1/8/8/6N1/PPPPPPPP/RBQNBRK1 b ha - 0 2
+[C2] observe_game 1
+[S2] game 1, chess960, 1, true, false, -, 100000, 0, *, [[1, 0, *, 1500], [2, 0, *, 1500]], -, rbqnbnkr/pppppppp/8/8/8/8/PPPPPPPP/RBQNBNKR w HAha - 0 1,rbqnb1kr/pppppppp/6n1/8/8/6N1/PPPPPPPP/RBQNBRK1 b ha - 0 2,[f1g3,f8g6,g1h1]
+    "#,
+    )
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_game_resign() {
+    session_test(
+        r#"
+[C1] version 2
+[S1] okay
+[C2] version 2
+[S2] okay
+[C3] version 2
+[S3] okay
+[C1] new_tmp_user Test1
+[S1] okay
+[C2] new_tmp_user Test2
+[S2] okay
+[C3] new_tmp_user Test3
+[S3] okay
+[C1] new_game chess, 100000, 0
+[S1] new_game 1
+[C1] join_game 1
+[S1] okay
+[C2] join_game 1
+[S2] okay
+// games can't be resigned before they start
+[C1] resign 1
+[S1] error that game isn't in progress
+[C1] start_game 1
+[S1] go 1, chess, *, *, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1
+[S1] okay
+[C1] play 1, e2e4
+[S1] okay
+[S2] go 1, chess, *, *, rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1
+// only players can resign
+[C3] resign 1
+[S3] error you aren't a player in that game
+[C1] observe_game 1
+[S1] game 1, chess, 1, true, false, -, 100000, 0, *, [[1, 0, *, 1500], [2, 0, *, 1500]], -, rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1,[e2e4]
+// players can resign when it isn't their turn
+[C1] resign 1
+[S1] game 1, chess, 1, true, true, 2, 100000, 0, *, [[1, 0, *, 1338], [2, 0, *, 1662]], -, __ENDED_GAME, 2, Resigned, chess, rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1,[e2e4]
+[S1] okay
+[C2] resign 1
+[S2] error that game isn't in progress
+[C2] play 1, e7e5
+[S2] error it is not your turn to move in that game
+    "#,
+    )
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_game_pgn() {
+    session_test(
+        r#"
+[C1] version 2
+[S1] okay
+[C2] version 2
+[S2] okay
+[C1] new_tmp_user Test1
+[S1] okay
+[C2] new_tmp_user Test2
+[S2] okay
+[C1] new_game chess, 100000, 0
+[S1] new_game 1
+[C1] join_game 1
+[S1] okay
+[C2] join_game 1
+[S2] okay
+// games that haven't started have no moves to export
+[C1] pgn 1
+[S1] error that game can't be exported as pgn
+[C1] start_game 1
+[S1] go 1, chess, *, *, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1
+[S1] okay
+[C1] play 1, e2e4
+[S1] okay
+[S2] go 1, chess, *, *, rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1
+[C2] pgn 1
+[S2] pgn 1, [Event "Game 1"]
+[C2] play 1, e7e5
+[S2] okay
+[S1] go 1, chess, *, *, rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2
+[C1] resign 1
+[S1] okay
+// finished games can still be exported
+[C1] pgn 1
+[S1] pgn 1, [Event "Game 1"]
+[C1] pgn 2
+[S1] error *
+    "#,
+    )
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_rating() {
+    session_test(
+        r#"
+[C1] version 2
+[S1] okay
+[C2] version 2
+[S2] okay
+[C1] new_tmp_user Test1
+[S1] okay
+[C2] new_tmp_user Test2
+[S2] okay
+// users start with the default rating
+[C1] rating 1, chess
+[S1] rating 1, chess, 1500.00, 350.00, 0.060000, []
+[C1] rating 3, chess
+[S1] error no such user
+[C1] rating 1, go
+[S1] error unsupported game type: go
+[C1] new_game chess, 100000, 0
+[S1] new_game 1
+[C1] join_game 1
+[S1] okay
+[C2] join_game 1
+[S2] okay
+[C1] start_game 1
+[S1] go 1, chess, *, *, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1
+[S1] okay
+[C1] resign 1
+[S1] okay
+[C2] rating 2, chess
+[S2] rating 2, chess, 1662.31, 290.32, 0.060000, [[1, 1662.31, 290.32, *]]
+[C1] rating 1, chess
+[S1] rating 1, chess, 1337.69, 290.32, 0.060000, [[1, 1337.69, 290.32, *]]
+// ratings in each game type are separate
+[C1] rating 1, chess960
+[S1] rating 1, chess960, 1500.00, 350.00, 0.060000, []
+// imported games don't change ratings
+[C1] import_pgn 1, 2, 100000, 0, 1. f3 e5 2. g4 Qh4# 0-1
+[S1] import_pgn 2
+[C2] rating 2, chess
+[S2] rating 2, chess, 1662.31, 290.32, 0.060000, [[1, 1662.31, 290.32, *]]
+// even when they're finished by playing on
+[C1] import_pgn 1, 2, 100000, 0, 1. e4 *
+[S2] go 3, chess, *, 0, rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1
+[S1] import_pgn 3
+[C2] resign 3
+[S2] okay
+[C2] rating 2, chess
+[S2] rating 2, chess, 1662.31, 290.32, 0.060000, [[1, 1662.31, 290.32, *]]
+    "#,
+    )
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_leaderboard() {
+    session_test(
+        r#"
+[C1] version 2
+[S1] okay
+[C2] version 2
+[S2] okay
+[C3] version 2
+[S3] okay
+[C1] new_tmp_user Test1
+[S1] okay
+[C2] new_tmp_user Test2
+[S2] okay
+[C3] new_tmp_user Test3
+[S3] okay
+// users without finished games have no stats
+[C1] user_stats 1
+[S1] user_stats 1, []
+[C1] user_stats 4
+[S1] error no such user
+[C1] leaderboard chess, 0, 10
+[S1] leaderboard chess, []
+[C1] leaderboard go, 0, 10
+[S1] error unsupported game type: go
+[C1] leaderboard chess, -1, 10
+[S1] error *
+[C1] new_game chess, 100000, 0
+[S1] new_game 1
+[C1] join_game 1
+[S1] okay
+[C2] join_game 1
+[S2] okay
+[C1] start_game 1
+[S1] go 1, chess, *, *, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1
+[S1] okay
+[C1] play 1, e2e4
+[S1] okay
+[S2] go 1, chess, *, *, rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1
+[C2] resign 1
+[S2] okay
+// time forfeits are counted
+[C3] new_game chess, 500, 200
+[S3] new_game 2
+[C1] join_game 2
+[S1] okay
+[C3] join_game 2
+[S3] okay
+[C3] observe_game 2
+[S3] game 2, chess, 3, false, false, -, 500, 200, -, *
+[C3] start_game 2
+[S3] game 2, chess, 3, true, false, -, 500, 200, *
+[S1] go 2, chess, *, *, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1
+[S3] okay
+[S3] game 2, chess, 3, true, true, 3, 500, 200, 0, [[1, 0, 0, 1497], [3, 0, 500, 1732]], -, __ENDED_GAME, 3, Time Expired, *
+[C3] user_stats 3
+[S3] user_stats 3, [[chess, 1731.88, 286.93, 1, 1, 0, 0, 0, -, 0]]
+[C1] leaderboard chess, 0, 10
+[S1] leaderboard chess, [[1, 3, 1731.88, 286.93, 1, 1, 0, 0, 0, -, 0], [2, 1, 1497.45, 256.35, 2, 1, 1, 0, 0, *, 1], [3, 2, 1337.69, 290.32, 1, 0, 1, 0, 0, -, 0]]
+[C1] leaderboard chess, 1, 1
+[S1] leaderboard chess, [[2, 1, 1497.45, 256.35, 2, 1, 1, 0, 0, *, 1]]
+// imported games count towards stats, but not ratings
+[C2] import_pgn 2, 3, 100000, 0, 1. f3 e5 2. g4 Qh4# 0-1
+[S2] import_pgn 3
+[C2] user_stats 2
+[S2] user_stats 2, [[chess, 1337.69, 290.32, 2, 0, 2, 0, 0, -, 0]]
+    "#,
+    )
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_game_import_pgn() {
+    session_test(
+        r#"
+[C1] version 2
+[S1] okay
+[C2] version 2
+[S2] okay
+[C3] version 2
+[S3] okay
+[C1] new_tmp_user Test1
+[S1] okay
+[C2] new_tmp_user Test2
+[S2] okay
+[C3] new_tmp_user Test3
+[S3] okay
+// games that end by checkmate are finished
+[C1] import_pgn 1, 2, 100000, 0, [Event "Fool's mate, again"] 1. f3 e5 2. g4 Qh4# 0-1
+[S1] import_pgn 1
+[C1] observe_game 1
+[S1] game 1, chess, 1, true, true, 2, 100000, 0, *, [[1, *, 100000, 1500], [2, *, 100000, 1500]], -, rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 0 3,[f2f3,e7e5,g2g4,d8h4]
+// games ending for other reasons are ended with the pgn's result
+[C2] import_pgn 1, 2, 100000, 0, [Termination "adjudication"] 1. d4 1-0
+[S2] import_pgn 2
+[C2] observe_game 2
+[S2] game 2, chess, 2, true, true, 1, 100000, 0, *, [[1, *, *, 1500], [2, *, *, 1500]], -, __ENDED_GAME, 1, adjudication, chess, rnbqkbnr/pppppppp/8/8/3P4/8/PPP1PPPP/RNBQKBNR b KQkq d3 0 1,[d2d4]
+// unfinished games continue with the player to move, using the times from clock comments
+[C2] import_pgn 2, 1, 100000, 0, 1. e4 {[%clk 0:01:00]} e5 {[%clk 0:00:30]} *
+[S2] go 3, chess, *, 0, rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2
+[S2] import_pgn 3
+[C2] play 3, g1f3
+[S2] okay
+[S1] go 3, chess, *, 0, rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 0 2
+[C1] observe_game 3
+[S1] game 3, chess, 2, true, false, -, 100000, 0, *, [[2, *, 59*, 1500], [1, *, 30000, 1500]], -, rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 0 2,[e2e4,e7e5,g1f3]
+[C1] import_pgn 1, 2, 100000, 0, 1. e4 e4 *
+[S1] error invalid pgn: illegal move: e4
+[C1] import_pgn 1, 1, 100000, 0, 1. e4 *
+[S1] error white and black must be different players
+// only players, or the owner of a tournament both players are in, can import a game
+[C3] import_pgn 1, 2, 100000, 0, 1. d4 1-0
+[S3] error you can only import your own games, or games between players in your tournament
+[C3] new_tournament round_robin, chess, 100000, 0, 2
+[S3] new_tournament 1
+[C1] join_tournament 1
+[S1] okay
+[C3] import_pgn 1, 2, 100000, 0, 1. d4 1-0
+[S3] error you can only import your own games, or games between players in your tournament
+[C2] join_tournament 1
+[S2] okay
+[C3] import_pgn 1, 2, 100000, 0, 1. d4 1-0
+[S3] import_pgn 4
+    "#,
+    )
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_game_draw_offer() {
+    session_test(
+        r#"
+[C1] version 2
+[S1] okay
+[C2] version 2
+[S2] okay
+[C1] new_tmp_user Test1
+[S1] okay
+[C2] new_tmp_user Test2
+[S2] okay
+[C1] new_game chess, 100000, 0
+[S1] new_game 1
+[C1] join_game 1
+[S1] okay
+[C2] join_game 1
+[S2] okay
+[C1] start_game 1
+[S1] go 1, chess, *, *, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1
+[S1] okay
+// draws can only be offered on your turn
+[C2] offer_draw 1
+[S2] error it is not your turn to move in that game
+[C2] accept_draw 1
+[S2] error your opponent hasn't offered a draw in that game
+// the offer is sent with the next go
+[C1] offer_draw 1
+[S1] go 1, chess, *, *, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1
+[S1] okay
+[C1] accept_draw 1
+[S1] error your opponent hasn't offered a draw in that game
+[C1] play 1, e2e4
+[S1] okay
+[S2] go 1, chess, *, *, rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1, draw_offer
+// declining clears the offer for observers
+[C1] observe_game 1
+[S1] game 1, chess, 1, true, false, -, 100000, 0, *, [[1, 0, *, 1500], [2, 0, *, 1500]], 1, rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1,[e2e4]
+[C2] decline_draw 1
+[S1] game 1, chess, 1, true, false, -, 100000, 0, *, [[1, 0, *, 1500], [2, 0, *, 1500]], -, rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1,[e2e4]
+[S2] go 1, chess, *, *, rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1
+[S2] okay
+[C1] stop_observe_game 1
+[S1] okay
+[C2] play 1, e7e5
+[S2] okay
+[S1] go 1, chess, *, *, rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2
+// offers expire when the opponent moves
+[C1] offer_draw 1
+[S1] go 1, chess, *, *, rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2
+[S1] okay
+[C1] play 1, g1f3
+[S1] okay
+[S2] go 1, chess, *, *, rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 0 2, draw_offer
+[C2] play 1, b8c6
+[S2] okay
+[S1] go 1, chess, *, *, r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 3
+[C2] accept_draw 1
+[S2] error your opponent hasn't offered a draw in that game
+// accepting an offer ends the game in a tie
+[C1] offer_draw 1
+[S1] go 1, chess, *, *, r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 3
+[S1] okay
+[C1] play 1, f1c4
+[S1] okay
+[S2] go 1, chess, *, *, r1bqkbnr/pppp1ppp/2n5/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 0 3, draw_offer
+[C1] observe_game 1
+[S1] game 1, chess, 1, true, false, -, 100000, 0, *, [[1, 0, *, 1500], [2, 0, *, 1500]], 1, r1bqkbnr/pppp1ppp/2n5/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 0 3,[e2e4,e7e5,g1f3,b8c6,f1c4]
+[C2] accept_draw 1
+[S2] okay
+[S1] game 1, chess, 1, true, true, tie, 100000, 0, *, [[1, 0, *, 1500], [2, 0, *, 1500]], -, __ENDED_GAME, -, Draw Agreed, chess, r1bqkbnr/pppp1ppp/2n5/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 0 3,[e2e4,e7e5,g1f3,b8c6,f1c4]
+[C2] offer_draw 1
+[S2] error that game isn't in progress
+    "#,
+    )
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_game_info() {
+    session_test(
+        r#"
+// C2 uses the legacy protocol, and C3 observes
+[C1] version 2
+[S1] okay
+[C3] version 2
+[S3] okay
+[C1] new_tmp_user Test1
+[S1] okay
+[C3] new_tmp_user Test3
+[S3] okay
+[C2] new_tmp_user Test2
+[C1] new_game chess, 100000, 0
+[S1] new_game 1
+[C1] info 1, eval 20
+[S1] error you aren't a player in that game
+[C1] join_game 1
+[S1] okay
+[C2] join_game 1
+// legacy clients aren't sent okay, but errors are
+[C2] info eval 10
+[S2] error invalid info: you haven't made a move
+[C1] start_game 1
+[S1] go 1, chess, *, *, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1
+[S1] okay
+// info can only be given after making a move
+[C1] info 1, eval 20
+[S1] error invalid info: you haven't made a move in that game
+[C3] observe_game 1
+[S3] game 1, chess, 1, true, false, -, 100000, 0, *, [[1, 0, *, 1500], [3, 0, *, 1500]], -, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1,[]
+[C1] play 1, e2e4
+[S1] okay
+[S2] position rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1
+[S3] game 1, chess, 1, true, false, -, 100000, 0, *, [[1, 0, *, 1500], [3, 0, *, 1500]], -, rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1,[e2e4]
+// unknown keys are ignored
+[C1] info 1, eval 25`depth 3`nodes 1000`nps 5000`pv e2e4 e7e5`
+[S3] move_info 1, 1, 1, 25, 3, 1000, e2e4 e7e5
+[S1] okay
+[C1] info 1, depth x
+[S1] error invalid info: depth should be a number, not x
+[C2] move e7e5
+[S1] go 1, chess, *, *, rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2
+[S3] game 1, chess, 1, true, false, -, 100000, 0, *, [[1, 0, *, 1500], [3, 0, *, 1500]], -, rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2,[e2e4,e7e5]
+[C2] info eval -10`pv e7e5 g1f3`
+[S3] move_info 1, 3, 1, -10, -, -, e7e5 g1f3
+// info for the same move is merged
+[C1] info 1, eval 30.5
+[S3] move_info 1, 1, 1, 30.5, 3, 1000, e2e4 e7e5
+[S1] okay
+// info already reported is sent when observing
+[C1] observe_game 1
+[S1] move_info 1, 1, 1, 30.5, 3, 1000, e2e4 e7e5
+[S1] move_info 1, 3, 1, -10, -, -, e7e5 g1f3
+[S1] game 1, chess, 1, true, false, -, 100000, 0, *, [[1, 0, *, 1500], [3, 0, *, 1500]], -, rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2,[e2e4,e7e5]
+    "#,
+    )
+    .await;
+}
+
 #[tokio::test(flavor = "multi_thread")]
 async fn test_game_protocol_versions() {
     session_test(
@@ -295,13 +839,13 @@ async fn test_game_expiry() {
 [C2] join_game 1
 [S2] okay
 [C1] observe_game 1
-[S1] game 1, chess, 1, false, false, -, 500, 200, -, [[1, 0, 500], [2, 0, 500]], -
+[S1] game 1, chess, 1, false, false, -, 500, 200, -, [[1, 0, 500, 1500], [2, 0, 500, 1500]], -, -
 [C1] start_game 1
-[S1] game 1, chess, 1, true, false, -, 500, 200, *, [[1, 0, 500], [2, 0, 500]], rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1,[]
+[S1] game 1, chess, 1, true, false, -, 500, 200, *, [[1, 0, 500, 1500], [2, 0, 500, 1500]], -, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1,[]
 [S1] go 1, chess, *, *, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1
 [S1] okay
 // wait for game to time out
-[S1] game 1, chess, 1, true, true, 2, 500, 200, 0, [[1, 0, 0], [2, 0, 500]], __ENDED_GAME, 2, Time Expired, chess, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1,[]
+[S1] game 1, chess, 1, true, true, 2, 500, 200, 0, [[1, 0, 0, 1338], [2, 0, 500, 1662]], -, __ENDED_GAME, 2, Time Expired, chess, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1,[]
     "#,
     ).await;
 }
@@ -329,19 +873,19 @@ async fn test_tournament_create() {
 [S2] tournament 1, round_robin, 1, chess, false, false, -, [], []
 [C1] join_tournament 1
 [S1] okay
-[S2] tournament 1, round_robin, 1, chess, false, false, -, [[1, 0, 0, 0]], []
+[S2] tournament 1, round_robin, 1, chess, false, false, -, [[1, 0, 0, 0, 1500]], []
 [C1] leave_tournament 1
 [S1] okay
 [S2] tournament 1, round_robin, 1, chess, false, false, -, [], []
 [C1] join_tournament 1
 [S1] okay
-[S2] tournament 1, round_robin, 1, chess, false, false, -, [[1, 0, 0, 0]], []
+[S2] tournament 1, round_robin, 1, chess, false, false, -, [[1, 0, 0, 0, 1500]], []
 [C2] join_tournament 1
-[S2] tournament 1, round_robin, 1, chess, false, false, -, [[1, 0, 0, 0], [2, 0, 0, 0]], []
+[S2] tournament 1, round_robin, 1, chess, false, false, -, [[1, 0, 0, 0, 1500], [2, 0, 0, 0, 1500]], []
 [S2] okay
 [C3] join_tournament 1
 [S3] okay
-[S2] tournament 1, round_robin, 1, chess, false, false, -, [[1, 0, 0, 0], [2, 0, 0, 0], [3, 0, 0, 0]], []
+[S2] tournament 1, round_robin, 1, chess, false, false, -, [[1, 0, 0, 0, 1500], [2, 0, 0, 0, 1500], [3, 0, 0, 0, 1500]], []
 [C3] start_tournament 1
 [S3] error you aren't the owner of that game
 [C1] start_tournament 1
@@ -349,27 +893,283 @@ async fn test_tournament_create() {
 [S1] go 1, chess, *, *, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1
 [S1] okay
 // server gives c2 observation
-[S2] tournament 1, round_robin, 1, chess, true, false, -, [[1, 0, 0, 0], [2, 0, 0, 0], [3, 0, 0, 0]], []
+[S2] tournament 1, round_robin, 1, chess, true, false, -, [[1, 0, 0, 0, 1500], [2, 0, 0, 0, 1500], [3, 0, 0, 0, 1500]], []
 // games (3 pick 2):
-[S2] game 1, chess, 1, false, false, -, 100000, 0, -, [[1, 0, 100000], [2, 0, 100000]], -
-[S2] game 2, chess, 1, false, false, -, 100000, 0, -, [[1, 0, 100000], [3, 0, 100000]], -
-[S2] game 3, chess, 1, false, false, -, 100000, 0, -, [[2, 0, 100000], [1, 0, 100000]], -
-[S2] game 4, chess, 1, false, false, -, 100000, 0, -, [[2, 0, 100000], [3, 0, 100000]], -
-[S2] game 5, chess, 1, false, false, -, 100000, 0, -, [[3, 0, 100000], [1, 0, 100000]], -
-[S2] game 6, chess, 1, false, false, -, 100000, 0, -, [[3, 0, 100000], [2, 0, 100000]], -
+[S2] game 1, chess, 1, false, false, -, 100000, 0, -, [[1, 0, 100000, 1500], [2, 0, 100000, 1500]], -, -
+[S2] game 2, chess, 1, false, false, -, 100000, 0, -, [[1, 0, 100000, 1500], [3, 0, 100000, 1500]], -, -
+[S2] game 3, chess, 1, false, false, -, 100000, 0, -, [[2, 0, 100000, 1500], [1, 0, 100000, 1500]], -, -
+[S2] game 4, chess, 1, false, false, -, 100000, 0, -, [[2, 0, 100000, 1500], [3, 0, 100000, 1500]], -, -
+[S2] game 5, chess, 1, false, false, -, 100000, 0, -, [[3, 0, 100000, 1500], [1, 0, 100000, 1500]], -, -
+[S2] game 6, chess, 1, false, false, -, 100000, 0, -, [[3, 0, 100000, 1500], [2, 0, 100000, 1500]], -, -
 // game 1 starts:
-[S2] game 1, chess, 1, true, false, -, 100000, 0, 0, [[1, 0, 100000], [2, 0, 100000]], rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1,[]
+[S2] game 1, chess, 1, true, false, -, 100000, 0, 0, [[1, 0, 100000, 1500], [2, 0, 100000, 1500]], -, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1,[]
+
+[C1] version 2
+[S1] okay
+[C2] version 2
+[S2] okay
+[C2] version 2
+[S2] okay
+    "#,
+    ).await;
+}
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_tournament_game_options() {
+    session_test(
+        r#"
 [C1] version 2
 [S1] okay
 [C2] version 2
 [S2] okay
+[C1] new_tmp_user Test1
+[S1] okay
+[C2] new_tmp_user Test2
+[S2] okay
+[C1] new_tournament round_robin, chess, 100000, 0, 2, draw_rule=sometimes
+[S1] error invalid game options: unknown draw rule: sometimes
+[C1] new_tournament round_robin, chess, 100000, 0, 2, fen=8/8/4k3/8/8/4K3/8/4R3 w - - 0 1
+[S1] new_tournament 1
+[C1] join_tournament 1
+[S1] okay
+[C2] join_tournament 1
+[S2] okay
+[C1] start_tournament 1
+[S1] go 1, chess, *, *, 8/8/4k3/8/8/4K3/8/4R3 w - - 0 1
+[S1] okay
+    "#,
+    )
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_tournament_resign() {
+    session_test(
+        r#"
+[C1] version 2
+[S1] okay
 [C2] version 2
 [S2] okay
+[C1] new_tmp_user Test1
+[S1] okay
+[C2] new_tmp_user Test2
+[S2] okay
+[C1] new_tournament round_robin, chess, 100000, 0, 2
+[S1] new_tournament 1
+[C1] join_tournament 1
+[S1] okay
+[C2] join_tournament 1
+[S2] okay
+[C2] observe_tournament 1
+[S2] tournament 1, round_robin, 1, chess, false, false, -, [[1, 0, 0, 0, 1500], [2, 0, 0, 0, 1500]], []
+[C1] start_tournament 1
+[S1] go 1, chess, *, *, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1
+[S1] okay
+[S2] tournament 1, round_robin, 1, chess, true, false, -, [[1, 0, 0, 0, 1500], [2, 0, 0, 0, 1500]], []
+[S2] game 1, chess, 1, false, false, -, 100000, 0, -, [[1, 0, 100000, 1500], [2, 0, 100000, 1500]], -, -
+[S2] game 2, chess, 1, false, false, -, 100000, 0, -, [[2, 0, 100000, 1500], [1, 0, 100000, 1500]], -, -
+[S2] game 1, chess, 1, true, false, -, 100000, 0, 0, [[1, 0, 100000, 1500], [2, 0, 100000, 1500]], -, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1,[]
+// resigning counts as a loss, and the next game starts
+[C1] resign 1
+[S1] okay
+[S2] game 1, chess, 1, true, true, 2, 100000, 0, 0, [[1, 0, *, 1338], [2, 0, 100000, 1662]], -, __ENDED_GAME, 2, Resigned, chess, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1,[]
+[S2] tournament 1, round_robin, 1, chess, true, false, -, [[1, 0, 1, 0, 1338], [2, 1, 0, 0, 1662]], [1, 2]
+[S2] game 2, chess, 1, true, false, -, 100000, 0, 0, [[2, 0, 100000, 1662], [1, 0, 100000, 1338]], -, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1,[]
+[S2] go 2, chess, *, *, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1
     "#,
     ).await;
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn test_tournament_swiss() {
+    session_test(
+        r#"
+[C1] version 2
+[S1] okay
+[C2] version 2
+[S2] okay
+[C3] version 2
+[S3] okay
+[C1] new_tmp_user Test1
+[S1] okay
+[C2] new_tmp_user Test2
+[S2] okay
+[C3] new_tmp_user Test3
+[S3] okay
+[C1] new_tournament swiss, chess, 100000, 0, 0
+[S1] error invalid tournament options: rounds must be a positive number
+[C1] new_tournament swiss, chess, 100000, 0, 2
+[S1] new_tournament 1
+[C1] join_tournament 1
+[S1] okay
+[C2] join_tournament 1
+[S2] okay
+[C3] join_tournament 1
+[S3] okay
+[C3] observe_tournament 1
+[S3] tournament 1, swiss, 1, chess, false, false, -, [[1, 0, 0, 0, 1500], [2, 0, 0, 0, 1500], [3, 0, 0, 0, 1500]], [], [[1, 0, 0, 0], [2, 0, 0, 0], [3, 0, 0, 0]]
+// round 1: the lowest player gets a bye (and a win), and only the first round is created
+[C1] start_tournament 1
+[S1] go 1, chess, *, *, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1
+[S1] okay
+[S3] tournament 1, swiss, 1, chess, true, false, -, [[1, 0, 0, 0, 1500], [2, 0, 0, 0, 1500], [3, 0, 0, 0, 1500]], [], [[1, 0, 0, 0], [2, 0, 0, 0], [3, 0, 0, 0]]
+[S3] game 1, chess, 1, false, false, -, 100000, 0, -, [[1, 0, 100000, 1500], [2, 0, 100000, 1500]], -, -
+[S3] game 1, chess, 1, true, false, -, 100000, 0, 0, [[1, 0, 100000, 1500], [2, 0, 100000, 1500]], -, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1,[]
+[S3] tournament 1, swiss, 1, chess, true, false, -, [[1, 0, 0, 0, 1500], [2, 0, 0, 0, 1500], [3, 1, 0, 0, 1500]], [[1]], [[3, 1, 0, 0], [1, 0, 0, 0], [2, 0, 0, 0]]
+// round 2 is paired once round 1 finishes: player 3 already had a bye, and player 2 gets white after playing black
+[C1] resign 1
+[S1] okay
+[S3] game 1, chess, 1, true, true, 2, 100000, 0, 0, [[1, 0, *, 1338], [2, 0, 100000, 1662]], -, __ENDED_GAME, 2, Resigned, chess, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1,[]
+[S3] tournament 1, swiss, 1, chess, true, false, -, [[1, 0, 1, 0, 1338], [2, 1, 0, 0, 1662], [3, 1, 0, 0, 1500]], [[1]], [[2, 1, 0, 0], [3, 1, 0, 0], [1, 0, 1, 0]]
+[S3] game 2, chess, 1, false, false, -, 100000, 0, -, [[2, 0, 100000, 1662], [3, 0, 100000, 1500]], -, -
+[S3] game 2, chess, 1, true, false, -, 100000, 0, 0, [[2, 0, 100000, 1662], [3, 0, 100000, 1500]], -, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1,[]
+[S3] tournament 1, swiss, 1, chess, true, false, -, [[1, 1, 1, 0, 1338], [2, 1, 0, 0, 1662], [3, 1, 0, 0, 1500]], [[1], [2]], [[2, 1, 1, 1], [1, 1, 1, 0], [3, 1, 0, 0]]
+[S2] go 2, chess, *, *, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1
+// the tournament ends after the last round, and ties are broken by buchholz then sonneborn-berger
+[C3] resign 2
+[S3] game 2, chess, 1, true, true, 2, 100000, 0, 0, [[2, 0, *, 1751], [3, 0, *, 1383]], -, __ENDED_GAME, 2, Resigned, chess, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1,[]
+[S3] tournament 1, swiss, 1, chess, true, true, 2, [[1, 1, 1, 0, 1338], [2, 2, 0, 0, 1751], [3, 1, 1, 0, 1383]], [[1], [2]], [[2, 2, 2, 2], [1, 1, 2, 0], [3, 1, 2, 0]]
+[S3] okay
+    "#,
+    )
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_tournament_knockout() {
+    session_test(
+        r#"
+[C1] version 2
+[S1] okay
+[C2] version 2
+[S2] okay
+[C1] new_tmp_user Test1
+[S1] okay
+[C2] new_tmp_user Test2
+[S2] okay
+[C1] new_tournament knockout, chess, 100000, 0, 2 elo
+[S1] error invalid tournament options: unknown seeding: elo
+[C1] new_tournament knockout, chess, 100000, 0, 2
+[S1] new_tournament 1
+[C1] join_tournament 1
+[S1] okay
+[C2] join_tournament 1
+[S2] okay
+[C2] observe_tournament 1
+[S2] tournament 1, knockout, 1, chess, false, false, -, [[1, 0, 0, 0, 1500], [2, 0, 0, 0, 1500]], []
+// the match's games are created with alternating colors, and played one at a time
+[C1] start_tournament 1
+[S1] go 1, chess, *, *, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1
+[S1] okay
+[S2] tournament 1, knockout, 1, chess, true, false, -, [[1, 0, 0, 0, 1500], [2, 0, 0, 0, 1500]], []
+[S2] game 1, chess, 1, false, false, -, 100000, 0, -, [[1, 0, 100000, 1500], [2, 0, 100000, 1500]], -, -
+[S2] game 2, chess, 1, false, false, -, 100000, 0, -, [[2, 0, 100000, 1500], [1, 0, 100000, 1500]], -, -
+[S2] game 1, chess, 1, true, false, -, 100000, 0, 0, [[1, 0, 100000, 1500], [2, 0, 100000, 1500]], -, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1,[]
+[S2] tournament 1, knockout, 1, chess, true, false, -, [[1, 0, 0, 0, 1500], [2, 0, 0, 0, 1500]], [[[1, 2, -, [1, 2]]]]
+[C1] resign 1
+[S1] okay
+[S2] game 1, chess, 1, true, true, 2, 100000, 0, 0, [[1, 0, *, 1338], [2, 0, 100000, 1662]], -, __ENDED_GAME, 2, Resigned, chess, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1,[]
+[S2] tournament 1, knockout, 1, chess, true, false, -, [[1, 0, 1, 0, 1338], [2, 1, 0, 0, 1662]], [[[1, 2, -, [1, 2]]]]
+[S2] game 2, chess, 1, true, false, -, 100000, 0, 0, [[2, 0, 100000, 1662], [1, 0, 100000, 1338]], -, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1,[]
+[S2] go 2, chess, *, *, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1
+// a drawn match goes to a tiebreak game with a shorter time control
+[C2] resign 2
+[S2] game 2, chess, 1, true, true, 1, 100000, 0, 0, [[2, 0, *, 1433], [1, 0, 100000, 1567]], -, __ENDED_GAME, 1, Resigned, chess, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1,[]
+[S2] tournament 1, knockout, 1, chess, true, false, -, [[1, 1, 1, 0, 1567], [2, 1, 1, 0, 1433]], [[[1, 2, -, [1, 2]]]]
+[S2] game 3, chess, 1, false, false, -, 25000, 0, -, [[1, 0, 25000, 1567], [2, 0, 25000, 1433]], -, -
+[S2] game 3, chess, 1, true, false, -, 25000, 0, 0, [[1, 0, 25000, 1567], [2, 0, 25000, 1433]], -, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1,[]
+[S2] okay
+[S1] go 3, chess, *, *, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1
+[C1] resign 3
+[S1] okay
+[S2] game 3, chess, 1, true, true, 2, 25000, 0, 0, [[1, 0, *, 1418], [2, 0, 25000, 1582]], -, __ENDED_GAME, 2, Resigned, chess, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1,[]
+[S2] tournament 1, knockout, 1, chess, true, true, 2, [[1, 1, 2, 0, 1418], [2, 2, 1, 0, 1582]], [[[1, 2, 2, [1, 2, 3]]]]
+    "#,
+    )
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_tournament_gauntlet() {
+    session_test(
+        r#"
+[C1] version 2
+[S1] okay
+[C2] version 2
+[S2] okay
+[C3] version 2
+[S3] okay
+[C1] new_tmp_user Test1
+[S1] okay
+[C2] new_tmp_user Test2
+[S2] okay
+[C3] new_tmp_user Test3
+[S3] okay
+[C1] new_tournament gauntlet, chess, 100000, 0, 2
+[S1] error invalid tournament options: expected challenger user id and games per opponent
+[C1] new_tournament gauntlet, chess, 100000, 0, 2 2
+[S1] new_tournament 1
+[C1] join_tournament 1
+[S1] okay
+[C3] join_tournament 1
+[S3] okay
+[C1] start_tournament 1
+[S1] error invalid tournament options: the challenger hasn't joined the tournament
+[C2] join_tournament 1
+[S2] okay
+[C3] observe_tournament 1
+[S3] tournament 1, gauntlet, 1, chess, false, false, -, [[1, 0, 0, 0, 1500], [3, 0, 0, 0, 1500], [2, 0, 0, 0, 1500]], [[1, 0, 0, []], [3, 0, 0, []]]
+// the challenger plays each opponent with both colors, one game at a time
+[C1] start_tournament 1
+[S1] okay
+[S3] tournament 1, gauntlet, 1, chess, true, false, -, [[1, 0, 0, 0, 1500], [3, 0, 0, 0, 1500], [2, 0, 0, 0, 1500]], [[1, 0, 0, []], [3, 0, 0, []]]
+[S3] game 1, chess, 1, false, false, -, 100000, 0, -, [[2, 0, 100000, 1500], [1, 0, 100000, 1500]], -, -
+[S3] game 2, chess, 1, false, false, -, 100000, 0, -, [[1, 0, 100000, 1500], [2, 0, 100000, 1500]], -, -
+[S3] game 3, chess, 1, false, false, -, 100000, 0, -, [[2, 0, 100000, 1500], [3, 0, 100000, 1500]], -, -
+[S3] game 4, chess, 1, false, false, -, 100000, 0, -, [[3, 0, 100000, 1500], [2, 0, 100000, 1500]], -, -
+[S3] game 1, chess, 1, true, false, -, 100000, 0, 0, [[2, 0, 100000, 1500], [1, 0, 100000, 1500]], -, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1,[]
+[S2] go 1, chess, *, *, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1
+[C2] resign 1
+[S2] okay
+[S3] game 1, chess, 1, true, true, 1, 100000, 0, 0, [[2, 0, *, 1338], [1, 0, 100000, 1662]], -, __ENDED_GAME, 1, Resigned, chess, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1,[]
+[S3] tournament 1, gauntlet, 1, chess, true, false, -, [[1, 1, 0, 0, 1662], [3, 0, 0, 0, 1500], [2, 0, 1, 0, 1338]], [[1, 0, 1, [1, 2]], [3, 0, 0, [3, 4]]]
+[S3] game 2, chess, 1, true, false, -, 100000, 0, 0, [[1, 0, 100000, 1662], [2, 0, 100000, 1338]], -, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1,[]
+[S1] go 2, chess, *, *, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1
+    "#,
+    )
+    .await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_tournament_gauntlet_no_opponents() {
+    session_test(
+        r#"
+[C1] version 2
+[S1] okay
+[C2] version 2
+[S2] okay
+[C1] new_tmp_user Test1
+[S1] okay
+[C2] new_tmp_user Test2
+[S2] okay
+// a gauntlet can't start with only the challenger
+[C1] new_tournament gauntlet, chess, 100000, 0, 1 2
+[S1] new_tournament 1
+[C1] join_tournament 1
+[S1] okay
+[C1] start_tournament 1
+[S1] error invalid tournament options: the challenger has no opponents
+[C2] join_tournament 1
+[S2] okay
+// the challenger plays white first
+[C1] start_tournament 1
+[S1] go 1, chess, *, *, rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1
+[S1] okay
+    "#,
+    )
+    .await;
+}
+
 /*
 version 2
 new_tmp_user T1